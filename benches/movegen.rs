@@ -0,0 +1,69 @@
+//! Compare legal move generation against pseudo-legal generation with a legality check on each
+//! move, by running perft on a few positions.
+//!
+//! Run with `cargo bench`.
+
+use chess::{Board, MoveGen};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const POSITIONS: [(&str, usize); 3] = [
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        4,
+    ),
+    ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        4,
+    ),
+];
+
+const ITERATIONS: u32 = 5;
+
+fn perft_legal(board: &Board, depth: usize) -> usize {
+    let moves = MoveGen::new_legal(board);
+    if depth == 1 {
+        moves.len()
+    } else {
+        moves
+            .map(|m| perft_legal(&board.make_move_new(m), depth - 1))
+            .sum()
+    }
+}
+
+fn perft_pseudo_legal(board: &Board, depth: usize) -> usize {
+    let moves = MoveGen::new_pseudo_legal(board).filter(|m| board.is_legal_after_pseudo(*m));
+    if depth == 1 {
+        moves.count()
+    } else {
+        moves
+            .map(|m| perft_pseudo_legal(&board.make_move_new(m), depth - 1))
+            .sum()
+    }
+}
+
+fn bench(name: &str, perft: fn(&Board, usize) -> usize) {
+    let mut nodes = 0;
+    let mut elapsed = Duration::default();
+    for _ in 0..ITERATIONS {
+        for (fen, depth) in POSITIONS.iter() {
+            let board = Board::from_str(fen).expect("Valid FEN");
+            let start = Instant::now();
+            nodes += perft(&board, *depth);
+            elapsed += start.elapsed();
+        }
+    }
+    println!(
+        "{:<12} {:>10} nodes in {:>8.3}s ({:.1} Mnps)",
+        name,
+        nodes / ITERATIONS as usize,
+        elapsed.as_secs_f64() / ITERATIONS as f64,
+        nodes as f64 / elapsed.as_secs_f64() / 1_000_000.0
+    );
+}
+
+fn main() {
+    bench("legal", perft_legal);
+    bench("pseudo-legal", perft_pseudo_legal);
+}
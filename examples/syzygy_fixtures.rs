@@ -0,0 +1,1042 @@
+//! Generate the Syzygy tables in `tests/fixtures/syzygy`, which the tablebase tests probe.
+//!
+//! KQvK, KRvK and KPvK are solved here by retrograde analysis, and written in the Syzygy file
+//! format: RE-PAIR compressed, Huffman coded blocks, with a sparse index.  KBvK and KNvK are
+//! always drawn, so they are single value WDL tables, which are needed when probing promotions.
+//!
+//! The layouts are chosen to cover as much of the format as possible: WDL tables store both
+//! sides to move with their own piece order, and the DTZ tables store different sides to move,
+//! with wide, narrow and no value maps, in moves and in plies.  Losses are only ever stored in
+//! plies, so every DTZ value probed from these tables is exact.
+//!
+//! ```text
+//! cargo run --example syzygy_fixtures -- tests/fixtures/syzygy
+//! ```
+
+use chess::{Board, BoardBuilder, BoardStatus, Color, MoveGen, Piece, Square, ALL_PIECES};
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+const BLOCK_SIZE_BITS: u8 = 6;
+const SPAN_BITS: u8 = 10;
+const BLOCK_LENGTH_PADDING: u8 = 1;
+const MAX_PAIRS: usize = 200;
+
+/// Win, draw or loss for the side to move.
+const LOSS: i8 = -1;
+const DRAW: i8 = 0;
+const WIN: i8 = 1;
+const UNKNOWN: i8 = 2;
+
+/// Where a move leads: to a position of the same table, or to one that is already solved.
+#[derive(Copy, Clone)]
+enum Child {
+    Local { key: usize, zeroing: bool },
+    Solved { wdl: i8 },
+}
+
+/// Every position of one material, with white as the stronger side.
+struct Solved {
+    pieces: Vec<(Piece, Color)>,
+    legal: Vec<bool>,
+    wdl: Vec<i8>,
+    dtz: Vec<i32>,
+}
+
+impl Solved {
+    fn positions(&self) -> usize {
+        self.legal.len()
+    }
+
+    /// The side to move and the square of each piece, in the order of `pieces`.
+    fn squares(&self, key: usize) -> (Color, Vec<usize>) {
+        let mut rest = key;
+        let mut squares = vec![0; self.pieces.len()];
+        for square in squares.iter_mut().rev() {
+            *square = rest % 64;
+            rest /= 64;
+        }
+        (
+            if rest == 0 {
+                Color::White
+            } else {
+                Color::Black
+            },
+            squares,
+        )
+    }
+}
+
+fn key_of(board: &Board, pieces: &[(Piece, Color)]) -> usize {
+    let mut key = (board.side_to_move() == Color::Black) as usize;
+    for &(piece, color) in pieces {
+        let square = (board.pieces(piece) & board.color_combined(color)).to_square();
+        key = key * 64 + square.to_index();
+    }
+    key
+}
+
+/// The table name for the material on `board`, like `KRvK`.
+fn material(board: &Board) -> String {
+    let side = |color: Color| {
+        ALL_PIECES
+            .iter()
+            .rev()
+            .map(|&piece| {
+                let count = (board.pieces(piece) & board.color_combined(color)).popcnt() as usize;
+                piece.to_string(Color::White).repeat(count)
+            })
+            .collect::<String>()
+    };
+    format!("{}v{}", side(Color::White), side(Color::Black))
+}
+
+fn board_at(squares: &[usize], stm: Color, pieces: &[(Piece, Color)]) -> Option<Board> {
+    let mut builder = BoardBuilder::new();
+    for (i, &(piece, color)) in pieces.iter().enumerate() {
+        if squares[..i].contains(&squares[i]) {
+            return None;
+        }
+        let rank = squares[i] / 8;
+        if piece == Piece::Pawn && (rank == 0 || rank == 7) {
+            return None;
+        }
+        builder.piece(Square::new(squares[i] as u8), piece, color);
+    }
+    builder.side_to_move(stm);
+    Board::try_from(&builder).ok()
+}
+
+/// Solve every position of `pieces` by retrograde analysis.  Positions of other materials that
+/// can be reached by a capture or promotion must already be in `solved`, unless they are drawn
+/// for lack of material.
+fn solve(pieces: &[(Piece, Color)], solved: &HashMap<String, Solved>) -> Solved {
+    let size = 2 << (6 * pieces.len());
+    let mut result = Solved {
+        pieces: pieces.to_vec(),
+        legal: vec![false; size],
+        wdl: vec![UNKNOWN; size],
+        dtz: vec![0; size],
+    };
+
+    let mut mated = vec![false; size];
+    let mut children: Vec<Vec<Child>> = vec![vec![]; size];
+    for key in 0..size {
+        let (stm, squares) = result.squares(key);
+        let board = match board_at(&squares, stm, pieces) {
+            Some(board) => board,
+            None => continue,
+        };
+        result.legal[key] = true;
+        match board.status() {
+            BoardStatus::Checkmate => mated[key] = true,
+            BoardStatus::Stalemate => result.wdl[key] = DRAW,
+            BoardStatus::Ongoing => {}
+        }
+
+        for m in MoveGen::new_legal(&board) {
+            let zeroing = board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                || board.piece_on(m.get_dest()).is_some();
+            let after = board.make_move_new(m);
+            let name = material(&after);
+            children[key].push(if has_pieces(&after, pieces) {
+                Child::Local {
+                    key: key_of(&after, pieces),
+                    zeroing,
+                }
+            } else if let Some(other) = solved.get(&name) {
+                Child::Solved {
+                    wdl: other.wdl[key_of(&after, &other.pieces)],
+                }
+            } else if after.has_insufficient_material() {
+                Child::Solved { wdl: DRAW }
+            } else {
+                panic!("{} has to be solved first", name);
+            });
+        }
+    }
+
+    // win, draw or loss
+    for key in 0..size {
+        if mated[key] {
+            result.wdl[key] = LOSS;
+        }
+    }
+    let child_wdl = |wdl: &[i8], child: &Child| match *child {
+        Child::Local { key, .. } => wdl[key],
+        Child::Solved { wdl } => wdl,
+    };
+    loop {
+        let mut changed = false;
+        for key in 0..size {
+            if !result.legal[key] || result.wdl[key] != UNKNOWN {
+                continue;
+            }
+            let values: Vec<i8> = children[key]
+                .iter()
+                .map(|c| child_wdl(&result.wdl, c))
+                .collect();
+            if values.contains(&LOSS) {
+                result.wdl[key] = WIN;
+                changed = true;
+            } else if values.iter().all(|&v| v == WIN) {
+                result.wdl[key] = LOSS;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    for key in 0..size {
+        if result.legal[key] && result.wdl[key] == UNKNOWN {
+            result.wdl[key] = DRAW;
+        }
+    }
+
+    // distance to zeroing, in plies.  A position that is mated has a DTZ of -1, like the one
+    // before a zeroing move.
+    let mut remaining = (0..size)
+        .filter(|&key| result.legal[key] && result.wdl[key] != DRAW)
+        .count();
+    let mut ply = 1;
+    while remaining > 0 {
+        assert!(ply < 100, "The 50-move rule is not supported");
+        let previous = result.dtz.clone();
+        for key in 0..size {
+            if !result.legal[key] || result.wdl[key] == DRAW || previous[key] != 0 {
+                continue;
+            }
+            let dtz = if mated[key] {
+                Some(-1)
+            } else if result.wdl[key] == WIN {
+                let wins = children[key].iter().any(|c| match *c {
+                    Child::Local { key, zeroing } => {
+                        result.wdl[key] == LOSS
+                            && (zeroing || mated[key] || (ply > 1 && previous[key] == 1 - ply))
+                    }
+                    Child::Solved { wdl } => wdl == LOSS,
+                });
+                if wins {
+                    Some(ply)
+                } else {
+                    None
+                }
+            } else {
+                children[key]
+                    .iter()
+                    .map(|c| match *c {
+                        Child::Local { zeroing: true, .. } | Child::Solved { .. } => Some(1),
+                        Child::Local { key, .. } if previous[key] > 0 => Some(previous[key]),
+                        Child::Local { .. } => None,
+                    })
+                    .collect::<Option<Vec<i32>>>()
+                    .map(|distances| -1 - distances.into_iter().max().unwrap_or(0))
+            };
+            if let Some(dtz) = dtz {
+                result.dtz[key] = dtz;
+                remaining -= 1;
+            }
+        }
+        ply += 1;
+    }
+
+    result
+}
+
+/// Does `board` have exactly the pieces of the table?
+fn has_pieces(board: &Board, pieces: &[(Piece, Color)]) -> bool {
+    board.combined().popcnt() as usize == pieces.len()
+        && pieces.iter().all(|&(piece, color)| {
+            (board.pieces(piece) & board.color_combined(color)).popcnt() == 1
+        })
+}
+
+/// The parts of the index that only depend on the board geometry.
+struct Encoding {
+    map_a1d1d4: [usize; 64],
+    map_b1h1h7: [usize; 64],
+}
+
+fn off_diagonal(sq: usize) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+impl Encoding {
+    fn new() -> Encoding {
+        let mut result = Encoding {
+            map_a1d1d4: [0; 64],
+            map_b1h1h7: [0; 64],
+        };
+        let below: Vec<usize> = (0..64).filter(|&sq| off_diagonal(sq) < 0).collect();
+        for (code, &sq) in below.iter().enumerate() {
+            result.map_b1h1h7[sq] = code;
+        }
+        let triangle = (0..28).filter(|&sq| off_diagonal(sq) < 0 && sq % 8 <= 3);
+        let diagonal = (0..28).filter(|&sq| off_diagonal(sq) == 0 && sq % 8 <= 3);
+        for (code, sq) in triangle.chain(diagonal).enumerate() {
+            result.map_a1d1d4[sq] = code;
+        }
+        result
+    }
+
+    /// The index of three unique pieces without pawns, after moving the first one into the
+    /// a1-d1-d4 triangle.
+    fn pawnless_index(&self, squares: &mut [usize]) -> usize {
+        if squares[0] % 8 > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+        if squares[0] / 8 > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 56);
+        }
+        if let Some(i) = squares.iter().position(|&sq| off_diagonal(sq) != 0) {
+            if off_diagonal(squares[i]) > 0 {
+                squares[i..]
+                    .iter_mut()
+                    .for_each(|sq| *sq = (*sq >> 3) | ((*sq & 7) << 3));
+            }
+        }
+
+        let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+        let adjust1 = (s1 > s0) as usize;
+        let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+        if off_diagonal(s0) != 0 {
+            (self.map_a1d1d4[s0] * 63 + s1 - adjust1) * 62 + s2 - adjust2
+        } else if off_diagonal(s1) != 0 {
+            (6 * 63 + (s0 / 8) * 28 + self.map_b1h1h7[s1]) * 62 + s2 - adjust2
+        } else if off_diagonal(s2) != 0 {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + (s0 / 8) * 7 * 28
+                + (s1 / 8 - adjust1) * 28
+                + self.map_b1h1h7[s2]
+        } else {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + (s0 / 8) * 7 * 6
+                + (s1 / 8 - adjust1) * 6
+                + (s2 / 8 - adjust2)
+        }
+    }
+}
+
+/// How one sub-table (a side to move, and the file of the pawn) orders its pieces.
+#[derive(Clone)]
+struct SubTable {
+    /// Indexes into the pieces of the table, with the pawn first if there is one.
+    order: Vec<usize>,
+    /// Where the leading group comes in the index, for tables with pawns.
+    lead_position: usize,
+}
+
+impl SubTable {
+    fn new(order: &[usize], lead_position: usize) -> SubTable {
+        SubTable {
+            order: order.to_vec(),
+            lead_position,
+        }
+    }
+
+    fn size(&self, pawns: bool) -> usize {
+        if pawns {
+            6 * 63 * 62
+        } else {
+            31332
+        }
+    }
+
+    /// The multiplier for each group: the pawn (or the leading three pieces), then every other
+    /// piece on its own.
+    fn group_factors(&self, pawns: bool) -> Vec<usize> {
+        if !pawns {
+            return vec![1];
+        }
+        let mut factors = vec![0; 3];
+        let mut factor = 1;
+        let mut free = 63;
+        let mut next = 1;
+        for k in 0..3 {
+            if k == self.lead_position {
+                factors[0] = factor;
+                factor *= 6;
+            } else {
+                factors[next] = factor;
+                factor *= free;
+                free -= 1;
+                next += 1;
+            }
+        }
+        factors
+    }
+
+    /// The index of a position, given the squares of the pieces of the table.
+    fn index(&self, encoding: &Encoding, pawns: bool, squares: &[usize]) -> usize {
+        let mut ordered: Vec<usize> = self.order.iter().map(|&i| squares[i]).collect();
+        if !pawns {
+            return encoding.pawnless_index(&mut ordered);
+        }
+        if ordered[0] % 8 > 3 {
+            ordered.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+        let factors = self.group_factors(pawns);
+        let mut idx = (ordered[0] / 8 - 1) * factors[0];
+        for i in 1..ordered.len() {
+            let below = ordered[..i].iter().filter(|&&s| s < ordered[i]).count();
+            idx += (ordered[i] - below) * factors[i];
+        }
+        idx
+    }
+}
+
+/// How a table is written.
+struct Layout {
+    name: &'static str,
+    pieces: Vec<(Piece, Color)>,
+    /// The sub-tables for white and black to move, for each file of the pawn.
+    wdl: [Vec<SubTable>; 2],
+    /// The DTZ sub-table for each file, and the side to move it stores.
+    dtz: Vec<(SubTable, Color)>,
+    dtz_flags: u8,
+}
+
+impl Layout {
+    fn pawns(&self) -> bool {
+        self.pieces.iter().any(|&(piece, _)| piece == Piece::Pawn)
+    }
+
+    fn files(&self) -> usize {
+        if self.pawns() {
+            4
+        } else {
+            1
+        }
+    }
+
+    fn file_of(&self, squares: &[usize]) -> usize {
+        match self
+            .pieces
+            .iter()
+            .position(|&(piece, _)| piece == Piece::Pawn)
+        {
+            Some(pawn) => {
+                let file = squares[pawn] % 8;
+                file.min(7 - file)
+            }
+            None => 0,
+        }
+    }
+}
+
+fn piece_code(piece: Piece, color: Color) -> u8 {
+    piece.to_index() as u8 + 1 + if color == Color::Black { 8 } else { 0 }
+}
+
+/// A little endian byte writer.
+#[derive(Default)]
+struct Output {
+    bytes: Vec<u8>,
+}
+
+impl Output {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn align(&mut self, to: usize) {
+        while self.bytes.len() % to != 0 {
+            self.bytes.push(0);
+        }
+    }
+}
+
+/// A compressed sub-table.
+struct Pairs {
+    flags: u8,
+    single_value: Option<u8>,
+    min_len: usize,
+    max_len: usize,
+    lowest_sym: Vec<u16>,
+    tree: Vec<(u16, u16)>,
+    sparse_index: Vec<(u32, u16)>,
+    block_lengths: Vec<u16>,
+    data: Vec<u8>,
+}
+
+impl Pairs {
+    fn compress(values: &[u16], flags: u8) -> Pairs {
+        let mut pairs = Pairs {
+            flags,
+            single_value: None,
+            min_len: 0,
+            max_len: 0,
+            lowest_sym: vec![],
+            tree: vec![],
+            sparse_index: vec![],
+            block_lengths: vec![],
+            data: vec![],
+        };
+        if values.iter().all(|&v| v == values[0]) {
+            pairs.flags |= FLAG_SINGLE_VALUE;
+            pairs.single_value = Some(values[0] as u8);
+            return pairs;
+        }
+
+        // every value is a symbol, and then the most common neighbours are paired up
+        let mut leaves: Vec<u16> = values.to_vec();
+        leaves.sort_unstable();
+        leaves.dedup();
+        let mut tree: Vec<(u16, u16)> = leaves.iter().map(|&v| (v, 0xfff)).collect();
+        let mut lengths: Vec<usize> = vec![1; tree.len()];
+        let mut tokens: Vec<usize> = values
+            .iter()
+            .map(|v| leaves.binary_search(v).expect("Every value is a leaf"))
+            .collect();
+
+        for _ in 0..MAX_PAIRS {
+            let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut i = 0;
+            while i + 1 < tokens.len() {
+                let pair = (tokens[i], tokens[i + 1]);
+                *counts.entry(pair).or_insert(0) += 1;
+                // count a run like `a a a` as one pair
+                i += if pair.0 == pair.1 && tokens.get(i + 2) == Some(&pair.0) {
+                    2
+                } else {
+                    1
+                };
+            }
+            let best = counts
+                .into_iter()
+                .filter(|&((l, r), _)| lengths[l] + lengths[r] <= 256)
+                .max_by_key(|&(pair, count)| (count, Reverse(pair)));
+            let (left, right) = match best {
+                Some((pair, count)) if count >= 16 => pair,
+                _ => break,
+            };
+
+            let symbol = tree.len();
+            tree.push((left as u16, right as u16));
+            lengths.push(lengths[left] + lengths[right]);
+            let mut paired = Vec::with_capacity(tokens.len());
+            let mut i = 0;
+            while i < tokens.len() {
+                if i + 1 < tokens.len() && tokens[i] == left && tokens[i + 1] == right {
+                    paired.push(symbol);
+                    i += 2;
+                } else {
+                    paired.push(tokens[i]);
+                    i += 1;
+                }
+            }
+            tokens = paired;
+        }
+
+        // canonical Huffman codes, where the longest codes get the lowest symbols
+        let mut frequency = vec![0usize; tree.len()];
+        for &t in tokens.iter() {
+            frequency[t] += 1;
+        }
+        let code_lengths = huffman_lengths(&frequency);
+        let mut symbols: Vec<usize> = (0..tree.len()).collect();
+        symbols.sort_by_key(|&s| (code_lengths[s] == 0, Reverse(code_lengths[s]), s));
+        let mut renumber = vec![0; tree.len()];
+        for (new, &old) in symbols.iter().enumerate() {
+            renumber[old] = new;
+        }
+        pairs.tree = symbols
+            .iter()
+            .map(|&old| match tree[old] {
+                (value, 0xfff) => (value, 0xfff),
+                (l, r) => (renumber[l as usize] as u16, renumber[r as usize] as u16),
+            })
+            .collect();
+
+        pairs.max_len = *code_lengths.iter().max().expect("Some symbols are coded");
+        pairs.min_len = *code_lengths
+            .iter()
+            .filter(|&&l| l > 0)
+            .min()
+            .expect("Some symbols are coded");
+        assert!(pairs.max_len <= 32, "Huffman codes are too long");
+        let count_of = |len: usize| code_lengths.iter().filter(|&&l| l == len).count();
+        let levels = pairs.max_len - pairs.min_len + 1;
+        pairs.lowest_sym = vec![0; levels];
+        let mut base = vec![0u64; levels];
+        for i in (0..levels - 1).rev() {
+            let longer = count_of(i + 1 + pairs.min_len);
+            pairs.lowest_sym[i] = pairs.lowest_sym[i + 1] + longer as u16;
+            base[i] = (base[i + 1] + longer as u64) / 2;
+        }
+        let (min_len, lowest_sym) = (pairs.min_len, pairs.lowest_sym.clone());
+        let code = |symbol: usize| {
+            let len = code_lengths[symbols[symbol]];
+            let level = len - min_len;
+            (
+                base[level] + (symbol - lowest_sym[level] as usize) as u64,
+                len,
+            )
+        };
+
+        // fill the blocks with whole symbols
+        let block_bits = 8usize << BLOCK_SIZE_BITS;
+        let mut block_starts = vec![];
+        let mut position = 0;
+        let mut bits = Bits::default();
+        let mut block_values = 0;
+        for &token in tokens.iter() {
+            let (value, len) = code(renumber[token]);
+            if bits.len + len > block_bits || block_values + lengths[token] > 65536 {
+                pairs.block_lengths.push((block_values - 1) as u16);
+                bits.pad_to(block_bits, &mut pairs.data);
+                block_values = 0;
+            }
+            if block_values == 0 {
+                block_starts.push(position);
+            }
+            bits.push(value, len);
+            block_values += lengths[token];
+            position += lengths[token];
+        }
+        pairs.block_lengths.push((block_values - 1) as u16);
+        bits.pad_to(block_bits, &mut pairs.data);
+
+        let span = 1usize << SPAN_BITS;
+        for k in 0..(values.len() + span - 1) / span {
+            let middle = k * span + span / 2;
+            let block = block_starts
+                .iter()
+                .rposition(|&start| start <= middle)
+                .expect("The first block starts at 0");
+            let offset = middle - block_starts[block];
+            pairs
+                .sparse_index
+                .push((block as u32, u16::try_from(offset).expect("Short blocks")));
+        }
+        pairs
+    }
+
+    fn write_header(&self, out: &mut Output) {
+        out.u8(self.flags);
+        if let Some(value) = self.single_value {
+            out.u8(value);
+            return;
+        }
+        out.u8(BLOCK_SIZE_BITS);
+        out.u8(SPAN_BITS);
+        out.u8(BLOCK_LENGTH_PADDING);
+        out.u32(self.block_lengths.len() as u32);
+        out.u8(self.max_len as u8);
+        out.u8(self.min_len as u8);
+        for &lowest in self.lowest_sym.iter() {
+            out.u16(lowest);
+        }
+        out.u16(self.tree.len() as u16);
+        for &(left, right) in self.tree.iter() {
+            out.u8(left as u8);
+            out.u8(((left >> 8) as u8) | ((right as u8 & 0xf) << 4));
+            out.u8((right >> 4) as u8);
+        }
+        if self.tree.len() % 2 == 1 {
+            out.u8(0);
+        }
+    }
+
+    fn write_sparse_index(&self, out: &mut Output) {
+        for &(block, offset) in self.sparse_index.iter() {
+            out.u32(block);
+            out.u16(offset);
+        }
+    }
+
+    fn write_block_lengths(&self, out: &mut Output) {
+        if self.single_value.is_some() {
+            return;
+        }
+        for &length in self.block_lengths.iter() {
+            out.u16(length);
+        }
+        for _ in 0..BLOCK_LENGTH_PADDING {
+            out.u16(0);
+        }
+    }
+
+    fn write_data(&self, out: &mut Output) {
+        if self.single_value.is_none() {
+            out.align(64);
+            out.bytes.extend_from_slice(&self.data);
+        }
+    }
+}
+
+/// Huffman code lengths for `frequency`, with 0 for symbols that are never used on their own.
+fn huffman_lengths(frequency: &[usize]) -> Vec<usize> {
+    let mut lengths = vec![0; frequency.len()];
+    let used: Vec<usize> = (0..frequency.len()).filter(|&s| frequency[s] > 0).collect();
+    if used.len() == 1 {
+        lengths[used[0]] = 1;
+        return lengths;
+    }
+
+    // each node of the code tree lists the symbols below it
+    let mut nodes: Vec<Vec<usize>> = used.iter().map(|&s| vec![s]).collect();
+    let mut heap: BinaryHeap<Reverse<(usize, usize)>> = used
+        .iter()
+        .enumerate()
+        .map(|(node, &s)| Reverse((frequency[s], node)))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((f1, n1)) = heap.pop().expect("Two nodes");
+        let Reverse((f2, n2)) = heap.pop().expect("Two nodes");
+        let mut below = nodes[n1].clone();
+        below.extend_from_slice(&nodes[n2]);
+        for &s in below.iter() {
+            lengths[s] += 1;
+        }
+        nodes.push(below);
+        heap.push(Reverse((f1 + f2, nodes.len() - 1)));
+    }
+    lengths
+}
+
+/// Writes codes most significant bit first.
+#[derive(Default)]
+struct Bits {
+    current: u64,
+    len: usize,
+    block: Vec<u8>,
+}
+
+impl Bits {
+    fn push(&mut self, value: u64, len: usize) {
+        for bit in (0..len).rev() {
+            self.current = (self.current << 1) | ((value >> bit) & 1);
+            self.len += 1;
+            if self.len % 8 == 0 {
+                self.block.push(self.current as u8);
+                self.current = 0;
+            }
+        }
+    }
+
+    fn pad_to(&mut self, block_bits: usize, data: &mut Vec<u8>) {
+        while self.len % 8 != 0 {
+            self.push(0, 1);
+        }
+        self.block.resize(block_bits / 8, 0);
+        data.append(&mut self.block);
+        self.len = 0;
+    }
+}
+
+/// Put the values of `solved` into the sub-tables of `layout`.  Entries that no legal position
+/// maps to are filled with their neighbour, which compresses best.
+fn fill(
+    encoding: &Encoding,
+    layout: &Layout,
+    solved: &Solved,
+    sub_table: &SubTable,
+    stm: Color,
+    file: usize,
+    value: &dyn Fn(usize) -> Option<u16>,
+) -> Vec<u16> {
+    let pawns = layout.pawns();
+    let mut table: Vec<Option<u16>> = vec![None; sub_table.size(pawns)];
+    for key in 0..solved.positions() {
+        let (side, squares) = solved.squares(key);
+        if !solved.legal[key] || side != stm || layout.file_of(&squares) != file {
+            continue;
+        }
+        if let Some(v) = value(key) {
+            let idx = sub_table.index(encoding, pawns, &squares);
+            match table[idx] {
+                Some(old) => assert_eq!(old, v, "Positions with the same index differ"),
+                None => table[idx] = Some(v),
+            }
+        }
+    }
+
+    let first = table.iter().flatten().next().copied().unwrap_or(0);
+    let mut previous = first;
+    table
+        .into_iter()
+        .map(|v| {
+            previous = v.unwrap_or(previous);
+            previous
+        })
+        .collect()
+}
+
+fn write_header(out: &mut Output, layout: &Layout, magic: [u8; 4], sub_tables: &[&[SubTable]]) {
+    out.bytes.extend_from_slice(&magic);
+    let split = sub_tables.len() == 2;
+    out.u8(split as u8 | if layout.pawns() { 2 } else { 0 });
+    for file in 0..layout.files() {
+        let lead = |side: usize| {
+            sub_tables
+                .get(side)
+                .map_or(0, |s| s[file].lead_position as u8)
+        };
+        out.u8(lead(0) | (lead(1) << 4));
+        for i in 0..layout.pieces.len() {
+            let code = |side: usize| {
+                sub_tables.get(side).map_or(0, |s| {
+                    let (piece, color) = layout.pieces[s[file].order[i]];
+                    piece_code(piece, color)
+                })
+            };
+            out.u8(code(0) | (code(1) << 4));
+        }
+    }
+    out.align(2);
+}
+
+fn write_pairs_headers(out: &mut Output, pairs: &[Vec<Pairs>]) {
+    for file in pairs.iter() {
+        for p in file.iter() {
+            p.write_header(out);
+        }
+    }
+}
+
+fn write_pairs_data(out: &mut Output, pairs: &[Vec<Pairs>]) {
+    out.align(2);
+    for file in pairs.iter() {
+        for p in file.iter() {
+            p.write_sparse_index(out);
+        }
+    }
+    for file in pairs.iter() {
+        for p in file.iter() {
+            p.write_block_lengths(out);
+        }
+    }
+    for file in pairs.iter() {
+        for p in file.iter() {
+            p.write_data(out);
+        }
+    }
+}
+
+fn write_wdl(encoding: &Encoding, layout: &Layout, solved: &Solved) -> Vec<u8> {
+    let mut pairs = vec![];
+    for file in 0..layout.files() {
+        let mut sides = vec![];
+        for (side, stm) in [Color::White, Color::Black].iter().enumerate() {
+            let sub_table = &layout.wdl[side][file];
+            let value = |key: usize| Some((2 * solved.wdl[key] + 2) as u16);
+            let values = fill(encoding, layout, solved, sub_table, *stm, file, &value);
+            sides.push(Pairs::compress(&values, 0));
+        }
+        pairs.push(sides);
+    }
+
+    let mut out = Output::default();
+    write_header(
+        &mut out,
+        layout,
+        WDL_MAGIC,
+        &[&layout.wdl[0], &layout.wdl[1]],
+    );
+    write_pairs_headers(&mut out, &pairs);
+    write_pairs_data(&mut out, &pairs);
+    out.bytes
+}
+
+fn write_dtz(encoding: &Encoding, layout: &Layout, solved: &Solved) -> Vec<u8> {
+    let flags = layout.dtz_flags;
+    let stored = |key: usize| -> Option<u16> {
+        let dtz = solved.dtz[key].abs() as u16;
+        let plies = match solved.wdl[key] {
+            WIN => flags & FLAG_WIN_PLIES != 0,
+            LOSS => flags & FLAG_LOSS_PLIES != 0,
+            _ => return None,
+        };
+        // values in moves round odd distances down, so they come back exact for wins
+        Some(if plies { dtz - 1 } else { dtz / 2 })
+    };
+
+    let mut pairs = vec![];
+    let mut maps = vec![];
+    for file in 0..layout.files() {
+        let (sub_table, stm) = &layout.dtz[file];
+        let file_flags = flags | if *stm == Color::Black { FLAG_STM } else { 0 };
+
+        let values = if flags & FLAG_MAPPED != 0 {
+            // one map for wins and one for losses.  The maps for cursed wins and blessed losses
+            // stay empty.
+            let class_values = |wdl: i8| {
+                let mut v: Vec<u16> = (0..solved.positions())
+                    .filter(|&key| {
+                        let (side, squares) = solved.squares(key);
+                        solved.legal[key]
+                            && solved.wdl[key] == wdl
+                            && side == *stm
+                            && layout.file_of(&squares) == file
+                    })
+                    .filter_map(stored)
+                    .collect();
+                v.sort_unstable();
+                v.dedup();
+                v
+            };
+            let (wins, losses) = (class_values(WIN), class_values(LOSS));
+
+            // store the position of each value in its map
+            let mapped = |key: usize| {
+                let v = stored(key)?;
+                let map = if solved.wdl[key] == WIN {
+                    &wins
+                } else {
+                    &losses
+                };
+                Some(map.binary_search(&v).expect("Every value is mapped") as u16)
+            };
+            let values = fill(encoding, layout, solved, sub_table, *stm, file, &mapped);
+            maps.push([wins, losses, vec![], vec![]]);
+            values
+        } else {
+            fill(encoding, layout, solved, sub_table, *stm, file, &stored)
+        };
+        pairs.push(vec![Pairs::compress(&values, file_flags)]);
+    }
+
+    let mut out = Output::default();
+    let dtz_tables: Vec<SubTable> = layout.dtz.iter().map(|(s, _)| s.clone()).collect();
+    write_header(&mut out, layout, DTZ_MAGIC, &[&dtz_tables]);
+    write_pairs_headers(&mut out, &pairs);
+    for file_maps in maps.iter() {
+        if flags & FLAG_WIDE != 0 {
+            out.align(2);
+            for map in file_maps.iter() {
+                out.u16(map.len() as u16);
+                map.iter().for_each(|&v| out.u16(v));
+            }
+        } else {
+            for map in file_maps.iter() {
+                out.u8(map.len() as u8);
+                map.iter().for_each(|&v| out.u8(v as u8));
+            }
+        }
+    }
+    write_pairs_data(&mut out, &pairs);
+    out.bytes
+}
+
+/// A single value WDL table, for material that can only draw.
+fn write_draw(layout: &Layout) -> Vec<u8> {
+    let pairs = vec![vec![Pairs::compress(&[2], 0)]];
+    let mut out = Output::default();
+    write_header(&mut out, layout, WDL_MAGIC, &[&layout.wdl[0]]);
+    write_pairs_headers(&mut out, &pairs);
+    write_pairs_data(&mut out, &pairs);
+    out.bytes
+}
+
+fn main() {
+    let directory = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "tests/fixtures/syzygy".to_string());
+    let directory = Path::new(&directory);
+    fs::create_dir_all(directory).expect("Can create the directory");
+
+    let encoding = Encoding::new();
+    let (w, b) = (Color::White, Color::Black);
+    let pawnless = |order: &[usize]| vec![SubTable::new(order, 0)];
+    let pawn = |order: &[usize], lead: [usize; 4]| {
+        lead.iter()
+            .map(|&position| SubTable::new(order, position))
+            .collect::<Vec<_>>()
+    };
+
+    let layouts = vec![
+        Layout {
+            name: "KQvK",
+            pieces: vec![(Piece::King, w), (Piece::Queen, w), (Piece::King, b)],
+            wdl: [pawnless(&[0, 1, 2]), pawnless(&[1, 2, 0])],
+            dtz: vec![(SubTable::new(&[1, 0, 2], 0), w)],
+            dtz_flags: FLAG_MAPPED | FLAG_WIDE,
+        },
+        Layout {
+            name: "KRvK",
+            pieces: vec![(Piece::King, w), (Piece::Rook, w), (Piece::King, b)],
+            wdl: [pawnless(&[1, 0, 2]), pawnless(&[2, 0, 1])],
+            dtz: vec![(SubTable::new(&[2, 1, 0], 0), b)],
+            dtz_flags: FLAG_MAPPED | FLAG_LOSS_PLIES,
+        },
+        Layout {
+            name: "KPvK",
+            pieces: vec![(Piece::King, w), (Piece::Pawn, w), (Piece::King, b)],
+            wdl: [
+                pawn(&[1, 0, 2], [0, 1, 2, 0]),
+                pawn(&[1, 2, 0], [2, 0, 1, 1]),
+            ],
+            dtz: pawn(&[1, 2, 0], [0, 2, 1, 0])
+                .into_iter()
+                .zip([w, b, w, b].iter().copied())
+                .collect(),
+            dtz_flags: FLAG_WIN_PLIES | FLAG_LOSS_PLIES,
+        },
+    ];
+    let draws = vec![
+        Layout {
+            name: "KBvK",
+            pieces: vec![(Piece::King, w), (Piece::Bishop, w), (Piece::King, b)],
+            wdl: [pawnless(&[0, 1, 2]), vec![]],
+            dtz: vec![],
+            dtz_flags: 0,
+        },
+        Layout {
+            name: "KNvK",
+            pieces: vec![(Piece::King, w), (Piece::Knight, w), (Piece::King, b)],
+            wdl: [pawnless(&[0, 1, 2]), vec![]],
+            dtz: vec![],
+            dtz_flags: 0,
+        },
+    ];
+
+    let write = |name: String, bytes: Vec<u8>| {
+        println!("{} ({} bytes)", name, bytes.len());
+        fs::write(directory.join(name), bytes).expect("Can write the table");
+    };
+
+    let mut solved: HashMap<String, Solved> = HashMap::new();
+    for layout in layouts.iter() {
+        let result = solve(&layout.pieces, &solved);
+        write(
+            format!("{}.rtbw", layout.name),
+            write_wdl(&encoding, layout, &result),
+        );
+        write(
+            format!("{}.rtbz", layout.name),
+            write_dtz(&encoding, layout, &result),
+        );
+        solved.insert(layout.name.to_string(), result);
+    }
+    for layout in draws.iter() {
+        write(format!("{}.rtbw", layout.name), write_draw(layout));
+    }
+}
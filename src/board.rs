@@ -6,9 +6,9 @@ use crate::color::{Color, ALL_COLORS, NUM_COLORS};
 use crate::error::Error;
 use crate::file::File;
 use crate::magic::{
-    between, get_adjacent_files, get_bishop_rays, get_castle_moves, get_file, get_king_moves,
-    get_knight_moves, get_pawn_attacks, get_pawn_dest_double_moves, get_pawn_source_double_moves,
-    get_rank, get_rook_rays,
+    between, get_adjacent_files, get_bishop_moves, get_bishop_rays, get_castle_moves, get_file,
+    get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_dest_double_moves,
+    get_pawn_source_double_moves, get_rank, get_rook_moves, get_rook_rays, line,
 };
 use crate::movegen::*;
 use crate::piece::{Piece, ALL_PIECES, NUM_PIECES};
@@ -20,8 +20,24 @@ use std::hash::{Hash, Hasher};
 use std::mem;
 use std::str::FromStr;
 
+/// The dark squares, a1, c1, ... h8.
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
+
 /// A representation of a chess board.  That's why you're here, right?
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+///
+/// Two boards are equal when they hold the same position.  The halfmove clock and fullmove
+/// number are not part of the position, so they are left out of the comparison.
+///
+/// ```
+/// use chess::Board;
+/// use std::str::FromStr;
+///
+/// let early = Board::from_str("4k3/8/8/8/8/8/8/4K2R w K - 0 1").expect("Valid FEN");
+/// let late = Board::from_str("4k3/8/8/8/8/8/8/4K2R w K - 12 40").expect("Valid FEN");
+/// assert_eq!(early, late);
+/// assert_ne!(early.halfmove_clock(), late.halfmove_clock());
+/// ```
+#[derive(Copy, Clone, Debug)]
 pub struct Board {
     pieces: [BitBoard; NUM_PIECES],
     color_combined: [BitBoard; NUM_COLORS],
@@ -32,16 +48,52 @@ pub struct Board {
     checkers: BitBoard,
     hash: u64,
     en_passant: Option<Square>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    chess960: bool,
+    castle_rook_files: [(File, File); NUM_COLORS],
 }
 
 /// What is the status of this game?
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BoardStatus {
     Ongoing,
     Stalemate,
     Checkmate,
 }
 
+/// Everything `Board::unmake_move` needs to take back a move made with `Board::make_move_mut`.
+///
+/// ```
+/// use chess::{Board, ChessMove, Piece, Square};
+/// use std::str::FromStr;
+///
+/// let mut board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").expect("Valid FEN");
+/// let undo = board.make_move_mut(ChessMove::new(Square::E4, Square::D5, None));
+/// assert_eq!(undo.captured(), Some(Piece::Pawn));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct UndoInfo {
+    captured: Option<Piece>,
+    castles: bool,
+    castle_rights: [CastleRights; NUM_COLORS],
+    en_passant: Option<Square>,
+    hash: u64,
+    pinned: BitBoard,
+    checkers: BitBoard,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+}
+
+impl UndoInfo {
+    /// Which piece, if any, did the move capture?  En passant captures are not included.
+    #[inline]
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
+}
+
 /// Construct the initial position.
 impl Default for Board {
     #[inline]
@@ -51,6 +103,27 @@ impl Default for Board {
     }
 }
 
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.pieces == other.pieces
+            && self.color_combined == other.color_combined
+            && self.side_to_move == other.side_to_move
+            && self.castle_rights == other.castle_rights
+            && self.en_passant == other.en_passant
+            && self.chess960 == other.chess960
+            && ALL_COLORS.iter().all(|color| {
+                // the rook files only matter for the castle rights that are left
+                let rights = self.castle_rights(*color);
+                let (kingside, queenside) = self.castle_rook_files(*color);
+                let (other_kingside, other_queenside) = other.castle_rook_files(*color);
+                (!rights.has_kingside() || kingside == other_kingside)
+                    && (!rights.has_queenside() || queenside == other_queenside)
+            })
+    }
+}
+
+impl Eq for Board {}
+
 impl Hash for Board {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.hash.hash(state);
@@ -71,6 +144,10 @@ impl Board {
             checkers: EMPTY,
             hash: 0,
             en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
+            castle_rook_files: [(File::H, File::A); NUM_COLORS],
         }
     }
 
@@ -168,6 +245,43 @@ impl Board {
         }
     }
 
+    /// Is there too little material left for either side to checkmate, whatever the moves?
+    ///
+    /// This is the case with only kings, with kings and a single knight or bishop, and with
+    /// kings and any number of bishops that are all on squares of the same color.
+    ///
+    /// ```
+    /// use chess::Board;
+    /// use std::str::FromStr;
+    ///
+    /// assert!(!Board::default().has_insufficient_material());
+    ///
+    /// let kings = Board::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").expect("Valid FEN");
+    /// assert!(kings.has_insufficient_material());
+    ///
+    /// let bishops = Board::from_str("4k3/8/4b3/8/8/8/2B5/4K3 w - - 0 1").expect("Valid FEN");
+    /// assert!(bishops.has_insufficient_material());
+    ///
+    /// let knights = Board::from_str("4k3/8/3n4/8/8/8/2N5/4K3 w - - 0 1").expect("Valid FEN");
+    /// assert!(!knights.has_insufficient_material());
+    /// ```
+    pub fn has_insufficient_material(&self) -> bool {
+        if (self.pieces(Piece::Pawn) | self.pieces(Piece::Rook) | self.pieces(Piece::Queen))
+            != EMPTY
+        {
+            return false;
+        }
+
+        let minors = self.pieces(Piece::Knight) | self.pieces(Piece::Bishop);
+        if minors.popcnt() <= 1 {
+            return true;
+        }
+
+        let bishops = *self.pieces(Piece::Bishop);
+        minors == bishops
+            && ((bishops & DARK_SQUARES) == EMPTY || (bishops & !DARK_SQUARES) == EMPTY)
+    }
+
     /// Grab the "combined" `BitBoard`.  This is a `BitBoard` with every piece.
     ///
     /// ```
@@ -283,6 +397,70 @@ impl Board {
         unsafe { *self.castle_rights.get_unchecked(color.to_index()) }
     }
 
+    /// Is this a Chess960 (Fischer Random) position?
+    ///
+    /// In Chess960 mode, castling is encoded as the king capturing its own rook, so that it is
+    /// never ambiguous with a normal king move.  In standard mode, castling is encoded as the king
+    /// moving two squares.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, MoveGen, Square};
+    /// use std::str::FromStr;
+    ///
+    /// assert_eq!(Board::default().is_chess960(), false);
+    ///
+    /// let board = Board::from_str("rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w GAga - 0 1")
+    ///     .expect("Valid Shredder-FEN");
+    /// assert_eq!(board.is_chess960(), true);
+    ///
+    /// // White can castle kingside by moving the king onto the rook on g1.
+    /// let castles = ChessMove::new(Square::B1, Square::G1, None);
+    /// assert!(MoveGen::new_legal(&board).any(|m| m == castles));
+    /// ```
+    #[inline]
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// Which files do the rooks used for castling start on?  This returns the kingside rook file
+    /// first, and the queenside rook file second.  In standard chess, these are always the H and
+    /// A files.
+    ///
+    /// ```
+    /// use chess::{Board, Color, File};
+    /// use std::str::FromStr;
+    ///
+    /// assert_eq!(Board::default().castle_rook_files(Color::White), (File::H, File::A));
+    ///
+    /// let board = Board::from_str("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9")
+    ///     .expect("Valid Shredder-FEN");
+    /// assert_eq!(board.castle_rook_files(Color::Black), (File::H, File::E));
+    /// ```
+    #[inline]
+    pub fn castle_rook_files(&self, color: Color) -> (File, File) {
+        unsafe { *self.castle_rook_files.get_unchecked(color.to_index()) }
+    }
+
+    /// Which castle rights does `color` lose when a piece moves to or from `sq`?  This is the
+    /// Chess960 version of `CastleRights::square_to_castle_rights`.
+    fn chess960_castle_rights_lost(&self, color: Color, sq: Square) -> CastleRights {
+        let backrank = color.to_my_backrank();
+        if sq.get_rank() != backrank {
+            return CastleRights::NoRights;
+        }
+
+        let (kingside, queenside) = self.castle_rook_files(color);
+        if sq == self.king_square(color) {
+            CastleRights::Both
+        } else if sq.get_file() == kingside {
+            CastleRights::KingSide
+        } else if sq.get_file() == queenside {
+            CastleRights::QueenSide
+        } else {
+            CastleRights::NoRights
+        }
+    }
+
     /// Add castle rights for a particular side.  Note: this can create an invalid position.
     #[deprecated(
         since = "3.1.0",
@@ -649,22 +827,34 @@ impl Board {
         for color in ALL_COLORS.iter() {
             // get the castle rights
             let castle_rights = self.castle_rights(*color);
+            let backrank = color.to_my_backrank();
+            let (kingside, queenside) = self.castle_rook_files(*color);
+            let my_rooks = self.pieces(Piece::Rook) & self.color_combined(*color);
 
-            // the castle rights object will tell us which rooks shouldn't have moved yet.
-            // verify there are rooks on all those squares
-            if castle_rights.unmoved_rooks(*color)
-                & self.pieces(Piece::Rook)
-                & self.color_combined(*color)
-                != castle_rights.unmoved_rooks(*color)
+            // verify there are rooks on the squares the castle rights say shouldn't have moved
+            if castle_rights.has_kingside() && my_rooks & BitBoard::set(backrank, kingside) == EMPTY
+            {
+                return false;
+            }
+            if castle_rights.has_queenside()
+                && my_rooks & BitBoard::set(backrank, queenside) == EMPTY
             {
                 return false;
             }
+
             // if we have castle rights, make sure we have a king on the (E, {1,8}) square,
-            // depending on the color
+            // depending on the color.  In Chess960, the king must be on the back rank, between
+            // the castling rooks.
             if castle_rights != CastleRights::NoRights {
-                if self.pieces(Piece::King) & self.color_combined(*color)
-                    != get_file(File::E) & get_rank(color.to_my_backrank())
-                {
+                let ksq = self.king_square(*color);
+                if self.chess960 {
+                    if ksq.get_rank() != backrank
+                        || (castle_rights.has_kingside() && kingside <= ksq.get_file())
+                        || (castle_rights.has_queenside() && queenside >= ksq.get_file())
+                    {
+                        return false;
+                    }
+                } else if BitBoard::from_square(ksq) != get_file(File::E) & get_rank(backrank) {
                     return false;
                 }
             }
@@ -701,6 +891,11 @@ impl Board {
             } else {
                 0
             }
+            ^ if self.chess960 {
+                Zobrist::chess960()
+            } else {
+                0
+            }
     }
 
     /// Get a pawn hash of the board (a hash that only changes on color change and pawn moves).
@@ -807,6 +1002,45 @@ impl Board {
         self.en_passant
     }
 
+    /// How many halfmoves have been made since the last capture or pawn move?  This is the
+    /// counter used for the 50-move rule.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square};
+    ///
+    /// let board = Board::default()
+    ///     .make_move_new(ChessMove::new(Square::G1, Square::F3, None))
+    ///     .make_move_new(ChessMove::new(Square::G8, Square::F6, None));
+    /// assert_eq!(board.halfmove_clock(), 2);
+    ///
+    /// let board = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+    /// assert_eq!(board.halfmove_clock(), 0);
+    /// ```
+    #[inline]
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// Give me the number of the current full move.  This starts at 1, and is incremented after
+    /// Black moves.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square};
+    ///
+    /// let board = Board::default();
+    /// assert_eq!(board.fullmove_number(), 1);
+    ///
+    /// let board = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+    /// assert_eq!(board.fullmove_number(), 1);
+    ///
+    /// let board = board.make_move_new(ChessMove::new(Square::E7, Square::E5, None));
+    /// assert_eq!(board.fullmove_number(), 2);
+    /// ```
+    #[inline]
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
     /// Set the en_passant square.  Note: This must only be called when self.en_passant is already
     /// None.
     fn set_ep(&mut self, sq: Square) {
@@ -845,6 +1079,67 @@ impl Board {
         MoveGen::new_legal(&self).find(|x| *x == m).is_some()
     }
 
+    /// Finish checking the legality of a move generated by `MoveGen::new_pseudo_legal()`.
+    ///
+    /// The move generator has already made sure the move follows the rules for how its piece
+    /// moves, so this only checks that it does not leave my king in check.  Calling this with any
+    /// other move may give the wrong answer.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, MoveGen, Square};
+    /// use std::str::FromStr;
+    ///
+    /// // in check from the rook on a1
+    /// let board = Board::from_str("4k3/8/8/8/8/8/3P1P2/r3K3 w - - 0 1").expect("Valid FEN");
+    ///
+    /// assert!(board.is_legal_after_pseudo(ChessMove::new(Square::E1, Square::E2, None)));
+    /// assert!(!board.is_legal_after_pseudo(ChessMove::new(Square::E1, Square::F1, None)));
+    /// assert!(!board.is_legal_after_pseudo(ChessMove::new(Square::D2, Square::D3, None)));
+    /// ```
+    pub fn is_legal_after_pseudo(&self, m: ChessMove) -> bool {
+        let source = m.get_source();
+        let dest = m.get_dest();
+        let color = self.side_to_move;
+        let ksq = self.king_square(color);
+
+        match self.piece_on(source) {
+            Some(Piece::King) => {
+                let castles = if self.chess960 {
+                    self.color_on(dest) == Some(color)
+                } else {
+                    between(source, dest).popcnt() == 1
+                };
+                // castling moves are only generated when they are legal
+                castles || KingType::legal_king_move(self, dest)
+            }
+            Some(Piece::Pawn)
+                if source.get_file() != dest.get_file() && self.piece_on(dest).is_none() =>
+            {
+                // en passant can uncover an attack on the king in unusual ways, so look at every
+                // attacker once the move is made
+                let captured =
+                    BitBoard::from_square(Square::make_square(source.get_rank(), dest.get_file()));
+                let occupied = self.combined ^ BitBoard::from_square(source) ^ captured
+                    | BitBoard::from_square(dest);
+                self.attackers_to(ksq, occupied) & self.color_combined(!color) & !captured == EMPTY
+            }
+            Some(_) => {
+                let dest_bb = BitBoard::from_square(dest);
+                if self.checkers.popcnt() > 1
+                    || (self.checkers != EMPTY
+                        && (between(self.checkers.to_square(), ksq) | self.checkers) & dest_bb
+                            == EMPTY)
+                {
+                    false
+                } else {
+                    self.pinned & BitBoard::from_square(source) == EMPTY
+                        || line(source, ksq) & dest_bb != EMPTY
+                }
+            }
+            None => false,
+        }
+    }
+
     /// Make a chess move onto a new board.
     ///
     /// panic!() if king is captured.
@@ -862,9 +1157,33 @@ impl Board {
     #[inline]
     pub fn make_move_new(&self, m: ChessMove) -> Board {
         let mut result = *self;
-        result.remove_ep();
-        result.checkers = EMPTY;
-        result.pinned = EMPTY;
+        result.make_move_mut(m);
+        result
+    }
+
+    /// Make a chess move on this board, in place.  The returned `UndoInfo` can be given to
+    /// `unmake_move` to take the move back.
+    ///
+    /// panic!() if king is captured.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square, Color};
+    ///
+    /// let m = ChessMove::new(Square::D2,
+    ///                        Square::D4,
+    ///                        None);
+    ///
+    /// let mut board = Board::default();
+    /// let undo = board.make_move_mut(m);
+    /// assert_eq!(board.side_to_move(), Color::Black);
+    ///
+    /// board.unmake_move(m, undo);
+    /// assert_eq!(board, Board::default());
+    /// ```
+    #[inline]
+    pub fn make_move_mut(&mut self, m: ChessMove) -> UndoInfo {
+        let side = self.side_to_move;
+        let en_passant = self.en_passant;
         let source = m.get_source();
         let dest = m.get_dest();
 
@@ -873,162 +1192,214 @@ impl Board {
         let move_bb = source_bb ^ dest_bb;
         let moved = self.piece_on(source).unwrap();
 
-        result.xor(moved, source_bb, self.side_to_move);
-        result.xor(moved, dest_bb, self.side_to_move);
-        if let Some(captured) = self.piece_on(dest) {
-            result.xor(captured, dest_bb, !self.side_to_move);
+        let castles = if self.chess960 {
+            moved == Piece::King && (dest_bb & self.color_combined(side)) != EMPTY
+        } else {
+            moved == Piece::King && (move_bb & get_castle_moves()) == move_bb
+        };
+        let captured = if castles { None } else { self.piece_on(dest) };
+
+        let undo = UndoInfo {
+            captured,
+            castles,
+            castle_rights: self.castle_rights,
+            en_passant,
+            hash: self.hash,
+            pinned: self.pinned,
+            checkers: self.checkers,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        };
+
+        // the Chess960 castle rights depend on where the king stands, so work them out before
+        // moving anything
+        let (their_lost, my_lost) = if self.chess960 {
+            (
+                self.chess960_castle_rights_lost(!side, dest),
+                self.chess960_castle_rights_lost(side, source),
+            )
+        } else {
+            (
+                CastleRights::square_to_castle_rights(!side, dest),
+                CastleRights::square_to_castle_rights(side, source),
+            )
+        };
+
+        self.remove_ep();
+        self.checkers = EMPTY;
+        self.pinned = EMPTY;
+
+        if castles && self.chess960 {
+            // In Chess960, castling is encoded as the king capturing its own rook.
+            let my_backrank = side.to_my_backrank();
+            let (king_file, rook_file) = Board::chess960_castle_files(source, dest);
+            self.xor(Piece::King, source_bb, side);
+            self.xor(Piece::Rook, dest_bb, side);
+            self.xor(Piece::King, BitBoard::set(my_backrank, king_file), side);
+            self.xor(Piece::Rook, BitBoard::set(my_backrank, rook_file), side);
+        } else {
+            self.xor(moved, source_bb, side);
+            self.xor(moved, dest_bb, side);
+            if let Some(captured) = captured {
+                self.xor(captured, dest_bb, !side);
+            }
         }
 
-        #[allow(deprecated)]
-        result.remove_their_castle_rights(CastleRights::square_to_castle_rights(
-            !self.side_to_move,
-            dest,
-        ));
+        if moved == Piece::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if side == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
 
         #[allow(deprecated)]
-        result.remove_my_castle_rights(CastleRights::square_to_castle_rights(
-            self.side_to_move,
-            source,
-        ));
+        self.remove_their_castle_rights(their_lost);
 
-        let opp_king = result.pieces(Piece::King) & result.color_combined(!result.side_to_move);
+        #[allow(deprecated)]
+        self.remove_my_castle_rights(my_lost);
 
-        let castles = moved == Piece::King && (move_bb & get_castle_moves()) == move_bb;
+        let opp_king = self.pieces(Piece::King) & self.color_combined(!side);
 
         let ksq = opp_king.to_square();
 
-        const CASTLE_ROOK_START: [File; 8] = [
-            File::A,
-            File::A,
-            File::A,
-            File::A,
-            File::H,
-            File::H,
-            File::H,
-            File::H,
-        ];
-        const CASTLE_ROOK_END: [File; 8] = [
-            File::D,
-            File::D,
-            File::D,
-            File::D,
-            File::F,
-            File::F,
-            File::F,
-            File::F,
-        ];
-
         if moved == Piece::Knight {
-            result.checkers ^= get_knight_moves(ksq) & dest_bb;
+            self.checkers ^= get_knight_moves(ksq) & dest_bb;
         } else if moved == Piece::Pawn {
             if let Some(Piece::Knight) = m.get_promotion() {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(Piece::Knight, dest_bb, self.side_to_move);
-                result.checkers ^= get_knight_moves(ksq) & dest_bb;
+                self.xor(Piece::Pawn, dest_bb, side);
+                self.xor(Piece::Knight, dest_bb, side);
+                self.checkers ^= get_knight_moves(ksq) & dest_bb;
             } else if let Some(promotion) = m.get_promotion() {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(promotion, dest_bb, self.side_to_move);
+                self.xor(Piece::Pawn, dest_bb, side);
+                self.xor(promotion, dest_bb, side);
             } else if (source_bb & get_pawn_source_double_moves()) != EMPTY
                 && (dest_bb & get_pawn_dest_double_moves()) != EMPTY
             {
-                result.set_ep(dest);
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
-            } else if Some(dest.ubackward(self.side_to_move)) == self.en_passant {
-                result.xor(
+                self.set_ep(dest);
+                self.checkers ^= get_pawn_attacks(ksq, !side, dest_bb);
+            } else if Some(dest.ubackward(side)) == en_passant {
+                self.xor(
                     Piece::Pawn,
-                    BitBoard::from_square(dest.ubackward(self.side_to_move)),
-                    !self.side_to_move,
+                    BitBoard::from_square(dest.ubackward(side)),
+                    !side,
                 );
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
+                self.checkers ^= get_pawn_attacks(ksq, !side, dest_bb);
             } else {
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
+                self.checkers ^= get_pawn_attacks(ksq, !side, dest_bb);
             }
-        } else if castles {
-            let my_backrank = self.side_to_move.to_my_backrank();
-            let index = dest.get_file().to_index();
-            let start = BitBoard::set(my_backrank, unsafe {
-                *CASTLE_ROOK_START.get_unchecked(index)
-            });
-            let end = BitBoard::set(my_backrank, unsafe {
-                *CASTLE_ROOK_END.get_unchecked(index)
-            });
-            result.xor(Piece::Rook, start, self.side_to_move);
-            result.xor(Piece::Rook, end, self.side_to_move);
+        } else if castles && !self.chess960 {
+            let (start, end) = Board::castle_rook_squares(side, dest);
+            self.xor(Piece::Rook, start, side);
+            self.xor(Piece::Rook, end, side);
         }
         // now, lets see if we're in check or pinned
-        let attackers = result.color_combined(result.side_to_move)
-            & ((get_bishop_rays(ksq)
-                & (result.pieces(Piece::Bishop) | result.pieces(Piece::Queen)))
-                | (get_rook_rays(ksq)
-                    & (result.pieces(Piece::Rook) | result.pieces(Piece::Queen))));
+        let attackers = self.color_combined(side)
+            & ((get_bishop_rays(ksq) & (self.pieces(Piece::Bishop) | self.pieces(Piece::Queen)))
+                | (get_rook_rays(ksq) & (self.pieces(Piece::Rook) | self.pieces(Piece::Queen))));
 
         for sq in attackers {
-            let between = between(sq, ksq) & result.combined();
+            let between = between(sq, ksq) & self.combined();
             if between == EMPTY {
-                result.checkers ^= BitBoard::from_square(sq);
+                self.checkers ^= BitBoard::from_square(sq);
             } else if between.popcnt() == 1 {
-                result.pinned ^= between;
+                self.pinned ^= between;
             }
         }
 
-        result.side_to_move = !result.side_to_move;
-        result
+        self.side_to_move = !side;
+        undo
     }
 
-    /// Make a chess move onto an already allocated `Board`.
-    ///
-    /// panic!() if king is captured.
+    /// Take back a move made with `make_move_mut`, restoring the board exactly as it was.  `m`
+    /// and `undo` must be the move that was made and the `UndoInfo` it returned.
     ///
     /// ```
-    /// use chess::{Board, ChessMove, Square, Color};
+    /// use chess::{Board, ChessMove, Square};
+    /// use std::str::FromStr;
     ///
-    /// let m = ChessMove::new(Square::D2,
-    ///                        Square::D4,
-    ///                        None);
+    /// let mut board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+    ///     .expect("Valid FEN");
+    /// let original = board;
     ///
-    /// let board = Board::default();
-    /// let mut result = Board::default();
-    /// board.make_move(m, &mut result);
-    /// assert_eq!(result.side_to_move(), Color::Black);
+    /// let m = ChessMove::new(Square::E1, Square::G1, None);
+    /// let undo = board.make_move_mut(m);
+    /// assert_eq!(board.to_string(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+    ///
+    /// board.unmake_move(m, undo);
+    /// assert_eq!(board, original);
+    /// assert_eq!(board.get_hash(), original.get_hash());
     /// ```
     #[inline]
-    pub fn make_move(&self, m: ChessMove, result: &mut Board) {
-        *result = *self;
-        result.remove_ep();
-        result.checkers = EMPTY;
-        result.pinned = EMPTY;
+    pub fn unmake_move(&mut self, m: ChessMove, undo: UndoInfo) {
+        let side = !self.side_to_move;
         let source = m.get_source();
         let dest = m.get_dest();
 
         let source_bb = BitBoard::from_square(source);
         let dest_bb = BitBoard::from_square(dest);
-        let move_bb = source_bb ^ dest_bb;
-        let moved = self.piece_on(source).unwrap();
-
-        result.xor(moved, source_bb, self.side_to_move);
-        result.xor(moved, dest_bb, self.side_to_move);
-        if let Some(captured) = self.piece_on(dest) {
-            result.xor(captured, dest_bb, !self.side_to_move);
-        }
 
-        #[allow(deprecated)]
-        result.remove_their_castle_rights(CastleRights::square_to_castle_rights(
-            !self.side_to_move,
-            dest,
-        ));
+        if undo.castles && self.chess960 {
+            let my_backrank = side.to_my_backrank();
+            let (king_file, rook_file) = Board::chess960_castle_files(source, dest);
+            self.xor(Piece::King, BitBoard::set(my_backrank, king_file), side);
+            self.xor(Piece::Rook, BitBoard::set(my_backrank, rook_file), side);
+            self.xor(Piece::King, source_bb, side);
+            self.xor(Piece::Rook, dest_bb, side);
+        } else {
+            if undo.castles {
+                let (start, end) = Board::castle_rook_squares(side, dest);
+                self.xor(Piece::Rook, end, side);
+                self.xor(Piece::Rook, start, side);
+            }
 
-        #[allow(deprecated)]
-        result.remove_my_castle_rights(CastleRights::square_to_castle_rights(
-            self.side_to_move,
-            source,
-        ));
+            let moved = self.piece_on(dest).unwrap();
+            self.xor(moved, dest_bb, side);
+            if m.get_promotion().is_some() {
+                self.xor(Piece::Pawn, source_bb, side);
+            } else {
+                self.xor(moved, source_bb, side);
+            }
 
-        let opp_king = result.pieces(Piece::King) & result.color_combined(!result.side_to_move);
+            if let Some(captured) = undo.captured {
+                self.xor(captured, dest_bb, !side);
+            } else if moved == Piece::Pawn
+                && m.get_promotion().is_none()
+                && Some(dest.ubackward(side)) == undo.en_passant
+            {
+                self.xor(
+                    Piece::Pawn,
+                    BitBoard::from_square(dest.ubackward(side)),
+                    !side,
+                );
+            }
+        }
 
-        let castles = moved == Piece::King && (move_bb & get_castle_moves()) == move_bb;
+        self.side_to_move = side;
+        self.castle_rights = undo.castle_rights;
+        self.en_passant = undo.en_passant;
+        self.hash = undo.hash;
+        self.pinned = undo.pinned;
+        self.checkers = undo.checkers;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+    }
 
-        let ksq = opp_king.to_square();
+    /// Where do the king and rook end up when castling from `king` with the rook on `rook` in
+    /// Chess960?
+    #[inline]
+    fn chess960_castle_files(king: Square, rook: Square) -> (File, File) {
+        if rook.get_file() > king.get_file() {
+            (File::G, File::F)
+        } else {
+            (File::C, File::D)
+        }
+    }
 
+    /// Where does the rook start and end when the king castles to `dest` in standard chess?
+    #[inline]
+    fn castle_rook_squares(color: Color, dest: Square) -> (BitBoard, BitBoard) {
         const CASTLE_ROOK_START: [File; 8] = [
             File::A,
             File::A,
@@ -1050,60 +1421,36 @@ impl Board {
             File::F,
         ];
 
-        if moved == Piece::Knight {
-            result.checkers ^= get_knight_moves(ksq) & dest_bb;
-        } else if moved == Piece::Pawn {
-            if let Some(Piece::Knight) = m.get_promotion() {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(Piece::Knight, dest_bb, self.side_to_move);
-                result.checkers ^= get_knight_moves(ksq) & dest_bb;
-            } else if let Some(promotion) = m.get_promotion() {
-                result.xor(Piece::Pawn, dest_bb, self.side_to_move);
-                result.xor(promotion, dest_bb, self.side_to_move);
-            } else if (source_bb & get_pawn_source_double_moves()) != EMPTY
-                && (dest_bb & get_pawn_dest_double_moves()) != EMPTY
-            {
-                result.set_ep(dest);
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
-            } else if Some(dest.ubackward(self.side_to_move)) == self.en_passant {
-                result.xor(
-                    Piece::Pawn,
-                    BitBoard::from_square(dest.ubackward(self.side_to_move)),
-                    !self.side_to_move,
-                );
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
-            } else {
-                result.checkers ^= get_pawn_attacks(ksq, !result.side_to_move, dest_bb);
-            }
-        } else if castles {
-            let my_backrank = self.side_to_move.to_my_backrank();
-            let index = dest.get_file().to_index();
-            let start = BitBoard::set(my_backrank, unsafe {
-                *CASTLE_ROOK_START.get_unchecked(index)
-            });
-            let end = BitBoard::set(my_backrank, unsafe {
-                *CASTLE_ROOK_END.get_unchecked(index)
-            });
-            result.xor(Piece::Rook, start, self.side_to_move);
-            result.xor(Piece::Rook, end, self.side_to_move);
-        }
-        // now, lets see if we're in check or pinned
-        let attackers = result.color_combined(result.side_to_move)
-            & ((get_bishop_rays(ksq)
-                & (result.pieces(Piece::Bishop) | result.pieces(Piece::Queen)))
-                | (get_rook_rays(ksq)
-                    & (result.pieces(Piece::Rook) | result.pieces(Piece::Queen))));
-
-        for sq in attackers {
-            let between = between(sq, ksq) & result.combined();
-            if between == EMPTY {
-                result.checkers ^= BitBoard::from_square(sq);
-            } else if between.popcnt() == 1 {
-                result.pinned ^= between;
-            }
-        }
+        let my_backrank = color.to_my_backrank();
+        let index = dest.get_file().to_index();
+        let start = BitBoard::set(my_backrank, unsafe {
+            *CASTLE_ROOK_START.get_unchecked(index)
+        });
+        let end = BitBoard::set(my_backrank, unsafe {
+            *CASTLE_ROOK_END.get_unchecked(index)
+        });
+        (start, end)
+    }
 
-        result.side_to_move = !result.side_to_move;
+    /// Make a chess move onto an already allocated `Board`.
+    ///
+    /// panic!() if king is captured.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square, Color};
+    ///
+    /// let m = ChessMove::new(Square::D2,
+    ///                        Square::D4,
+    ///                        None);
+    ///
+    /// let board = Board::default();
+    /// let mut result = Board::default();
+    /// board.make_move(m, &mut result);
+    /// assert_eq!(result.side_to_move(), Color::Black);
+    /// ```
+    #[inline]
+    pub fn make_move(&self, m: ChessMove, result: &mut Board) {
+        *result = self.make_move_new(m);
     }
 
     /// Update the pin information.
@@ -1148,6 +1495,101 @@ impl Board {
     pub fn checkers(&self) -> &BitBoard {
         &self.checkers
     }
+
+    /// Give me every piece, of either color, that attacks `square` when exactly the squares in
+    /// `occupied` hold pieces.
+    ///
+    /// Pieces missing from `occupied` neither attack nor block, so sliding pieces see straight
+    /// through them.  Mask the result with `Board::color_combined()` to get one side's attackers.
+    ///
+    /// ```
+    /// use chess::{BitBoard, Board, Color, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1").expect("Valid FEN");
+    ///
+    /// let attackers = board.attackers_to(Square::E5, *board.combined());
+    /// assert_eq!(attackers, BitBoard::from_square(Square::E2));
+    ///
+    /// // with the rook on e2 gone, the rook on e1 sees through it
+    /// let occupied = *board.combined() ^ BitBoard::from_square(Square::E2);
+    /// let attackers = board.attackers_to(Square::E5, occupied);
+    /// assert_eq!(attackers, BitBoard::from_square(Square::E1));
+    /// ```
+    pub fn attackers_to(&self, square: Square, occupied: BitBoard) -> BitBoard {
+        let pawns = self.pieces(Piece::Pawn);
+        let rooks = self.pieces(Piece::Rook) | self.pieces(Piece::Queen);
+        let bishops = self.pieces(Piece::Bishop) | self.pieces(Piece::Queen);
+
+        let attackers = (get_knight_moves(square) & self.pieces(Piece::Knight))
+            | (get_king_moves(square) & self.pieces(Piece::King))
+            | (get_rook_moves(square, occupied) & rooks)
+            | (get_bishop_moves(square, occupied) & bishops)
+            | get_pawn_attacks(
+                square,
+                Color::White,
+                pawns & self.color_combined(Color::Black),
+            )
+            | get_pawn_attacks(
+                square,
+                Color::Black,
+                pawns & self.color_combined(Color::White),
+            );
+
+        attackers & occupied
+    }
+
+    /// Is `square` attacked by any piece of `color`?
+    ///
+    /// ```
+    /// use chess::{Board, Color, Square};
+    ///
+    /// let board = Board::default();
+    ///
+    /// assert!(board.is_attacked_by(Square::F3, Color::White));
+    /// assert!(!board.is_attacked_by(Square::F3, Color::Black));
+    /// assert!(!board.is_attacked_by(Square::E4, Color::White));
+    /// ```
+    pub fn is_attacked_by(&self, square: Square, color: Color) -> bool {
+        self.attackers_to(square, *self.combined()) & self.color_combined(color) != EMPTY
+    }
+
+    /// Give me every square attacked by a piece of `color`, whether or not it is empty.
+    ///
+    /// ```
+    /// use chess::{Board, Color, Rank, get_rank};
+    ///
+    /// let board = Board::default();
+    ///
+    /// // everything on the third rank, plus most of the first two
+    /// assert_eq!(board.attacks(Color::White).popcnt(), 22);
+    /// assert_eq!(
+    ///     board.attacks(Color::White) & get_rank(Rank::Third),
+    ///     get_rank(Rank::Third)
+    /// );
+    /// ```
+    pub fn attacks(&self, color: Color) -> BitBoard {
+        let occupied = *self.combined();
+        let mut attacks = EMPTY;
+
+        for square in self.color_combined(color) & self.pieces(Piece::Pawn) {
+            attacks |= get_pawn_attacks(square, color, !EMPTY);
+        }
+        for square in self.color_combined(color) & self.pieces(Piece::Knight) {
+            attacks |= get_knight_moves(square);
+        }
+        for square in
+            self.color_combined(color) & (self.pieces(Piece::Bishop) | self.pieces(Piece::Queen))
+        {
+            attacks |= get_bishop_moves(square, occupied);
+        }
+        for square in
+            self.color_combined(color) & (self.pieces(Piece::Rook) | self.pieces(Piece::Queen))
+        {
+            attacks |= get_rook_moves(square, occupied);
+        }
+        attacks | get_king_moves(self.king_square(color))
+    }
 }
 
 impl fmt::Display for Board {
@@ -1170,6 +1612,13 @@ impl TryFrom<&BoardBuilder> for Board {
         }
 
         board.side_to_move = fen.get_side_to_move();
+        board.chess960 = fen.is_chess960();
+        board.castle_rook_files = [
+            fen.get_castle_rook_files(Color::White),
+            fen.get_castle_rook_files(Color::Black),
+        ];
+        board.halfmove_clock = fen.get_halfmove_clock();
+        board.fullmove_number = fen.get_fullmove_number();
 
         if let Some(ep) = fen.get_en_passant() {
             board.side_to_move = !board.side_to_move;
@@ -1224,3 +1673,147 @@ fn test_null_move_en_passant() {
         Board::from_str("rnbqkbnr/pppp2pp/8/4pP2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 0").unwrap();
     assert_eq!(start.null_move().unwrap(), expected);
 }
+
+#[test]
+fn test_move_counters() {
+    let board = Board::from_str("r3k3/8/8/8/8/8/4P3/R3K3 w Qq - 10 20").unwrap();
+    assert_eq!(board.halfmove_clock(), 10);
+    assert_eq!(board.fullmove_number(), 20);
+
+    // a quiet rook move increments the halfmove clock, but white moving does not change the
+    // fullmove number
+    let board = board.make_move_new(ChessMove::new(Square::A1, Square::A2, None));
+    assert_eq!(board.halfmove_clock(), 11);
+    assert_eq!(board.fullmove_number(), 20);
+
+    // a capture resets the halfmove clock, and black moving increments the fullmove number
+    let board = board.make_move_new(ChessMove::new(Square::A8, Square::A2, None));
+    assert_eq!(board.halfmove_clock(), 0);
+    assert_eq!(board.fullmove_number(), 21);
+
+    // a pawn move resets the halfmove clock
+    let board = board
+        .make_move_new(ChessMove::new(Square::E1, Square::F1, None))
+        .make_move_new(ChessMove::new(Square::E8, Square::F8, None));
+    assert_eq!(board.halfmove_clock(), 2);
+    let board = board.make_move_new(ChessMove::new(Square::E2, Square::E4, None));
+    assert_eq!(board.halfmove_clock(), 0);
+    assert_eq!(format!("{}", board), "5k2/8/8/8/4P3/8/r7/5K2 b - - 0 22");
+}
+
+#[test]
+fn test_chess960_castles() {
+    let board = Board::from_str("rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w GAga - 0 1").expect("Valid FEN");
+    assert!(board.is_chess960());
+    assert_eq!(
+        board.to_string(),
+        "rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w KQkq - 0 1"
+    );
+
+    // The king lands on the square the rook started on
+    let white_castles = ChessMove::from_san(&board, "O-O").expect("Legal castle");
+    assert_eq!(white_castles, ChessMove::new(Square::B1, Square::G1, None));
+    let board = board.make_move_new(white_castles);
+    assert_eq!(
+        board.to_string(),
+        "rk4r1/pp4pp/8/8/8/8/PP4PP/R4RK1 b kq - 1 1"
+    );
+
+    // The king does not move at all
+    let black_castles = ChessMove::from_san(&board, "O-O-O").expect("Legal castle");
+    assert_eq!(black_castles, ChessMove::new(Square::B8, Square::A8, None));
+    assert_eq!(black_castles.to_san(&board), "O-O-O");
+    let board = board.make_move_new(black_castles);
+    assert_eq!(
+        board.to_string(),
+        "2kr2r1/pp4pp/8/8/8/8/PP4PP/R4RK1 w - - 2 2"
+    );
+    let mut builder =
+        BoardBuilder::from_str("2kr2r1/pp4pp/8/8/8/8/PP4PP/R4RK1 w - - 2 2").expect("Valid FEN");
+    let castled = Board::try_from(builder.chess960(true)).expect("Valid position");
+    assert_eq!(board.get_hash(), castled.get_hash());
+    // with no castle rights left, the files the rooks started on do not matter
+    assert_eq!(board, castled);
+    assert_ne!(
+        castled.castle_rook_files(Color::White),
+        board.castle_rook_files(Color::White)
+    );
+    // but a position of Chess960 is not one of standard chess
+    let standard = Board::try_from(builder.chess960(false)).expect("Valid position");
+    assert_ne!(board, standard);
+    assert_ne!(board.get_hash(), standard.get_hash());
+
+    // The rook on b1 is shielding the king from the queen, so it cannot castle
+    let board = Board::from_str("7k/8/8/8/8/8/8/qRK5 w B - 0 1").expect("Valid FEN");
+    assert!(!board.legal(ChessMove::new(Square::C1, Square::B1, None)));
+}
+
+#[cfg(test)]
+fn unmake_move_perft(board: &mut Board, depth: usize) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    let mut result = 0;
+    for m in MoveGen::new_legal(board) {
+        let original = *board;
+        let undo = board.make_move_mut(m);
+        assert_eq!(*board, original.make_move_new(m));
+        assert_eq!(
+            board.halfmove_clock(),
+            original.make_move_new(m).halfmove_clock()
+        );
+        result += unmake_move_perft(board, depth - 1);
+        board.unmake_move(m, undo);
+        assert_eq!(*board, original);
+        assert_eq!(board.halfmove_clock(), original.halfmove_clock());
+        assert_eq!(board.fullmove_number(), original.fullmove_number());
+        assert_eq!(board.get_hash(), original.get_hash());
+    }
+    result
+}
+
+#[test]
+fn test_unmake_move() {
+    for (fen, nodes) in [
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            97862,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 2812),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            9467,
+        ),
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            12189,
+        ),
+    ]
+    .iter()
+    {
+        let mut board = Board::from_str(fen).expect("Valid FEN");
+        assert_eq!(unmake_move_perft(&mut board, 3), *nodes);
+    }
+}
+
+#[test]
+fn test_attacks_match_attackers_to() {
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+    ]
+    .iter()
+    {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        for color in ALL_COLORS.iter() {
+            let attacks = board.attacks(*color);
+            for sq in ALL_SQUARES.iter() {
+                assert_eq!(
+                    board.is_attacked_by(*sq, *color),
+                    attacks & BitBoard::from_square(*sq) != EMPTY
+                );
+            }
+        }
+    }
+}
@@ -1,11 +1,12 @@
 use crate::board::Board;
 use crate::castle_rights::CastleRights;
-use crate::color::Color;
+use crate::color::{Color, ALL_COLORS};
 use crate::error::Error;
 use crate::file::{File, ALL_FILES};
 use crate::piece::Piece;
 use crate::rank::{Rank, ALL_RANKS};
 use crate::square::{Square, ALL_SQUARES};
+use crate::variant_board::VariantBoard;
 
 use std::fmt;
 use std::ops::{Index, IndexMut};
@@ -53,6 +54,10 @@ pub struct BoardBuilder {
     side_to_move: Color,
     castle_rights: [CastleRights; 2],
     en_passant: Option<File>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    chess960: bool,
+    castle_rook_files: [(File, File); 2],
 }
 
 impl BoardBuilder {
@@ -62,6 +67,8 @@ impl BoardBuilder {
     /// * `CastleRights` are empty for both sides
     /// * `en_passant` is not set
     /// * `side_to_move` is Color::White
+    /// * `halfmove_clock` is 0, and `fullmove_number` is 1
+    /// * The position is not Chess960, and castling uses the rooks on the H and A files
     /// ```
     /// use chess::{BoardBuilder, Board, Square, Color, Piece};
     /// use std::convert::TryInto;
@@ -81,6 +88,10 @@ impl BoardBuilder {
             side_to_move: Color::White,
             castle_rights: [CastleRights::NoRights, CastleRights::NoRights],
             en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
+            castle_rook_files: [(File::H, File::A), (File::H, File::A)],
         }
     }
 
@@ -116,6 +127,10 @@ impl BoardBuilder {
             side_to_move: side_to_move,
             castle_rights: [white_castle_rights, black_castle_rights],
             en_passant: en_passant,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            chess960: false,
+            castle_rook_files: [(File::H, File::A), (File::H, File::A)],
         };
 
         for piece in pieces.into_iter() {
@@ -149,6 +164,36 @@ impl BoardBuilder {
         self.castle_rights[color.to_index()]
     }
 
+    /// Is this a Chess960 position?
+    ///
+    /// ```
+    /// use chess::BoardBuilder;
+    /// use std::str::FromStr;
+    ///
+    /// assert_eq!(BoardBuilder::default().is_chess960(), false);
+    ///
+    /// let bb = BoardBuilder::from_str("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9")
+    ///     .expect("Valid Shredder-FEN");
+    /// assert_eq!(bb.is_chess960(), true);
+    /// ```
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// Get the files of the rooks used for castling, kingside first.
+    ///
+    /// ```
+    /// use chess::{BoardBuilder, Color, File};
+    /// use std::str::FromStr;
+    ///
+    /// let bb = BoardBuilder::from_str("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9")
+    ///     .expect("Valid Shredder-FEN");
+    /// assert_eq!(bb.get_castle_rook_files(Color::Black), (File::H, File::F));
+    /// ```
+    pub fn get_castle_rook_files(&self, color: Color) -> (File, File) {
+        self.castle_rook_files[color.to_index()]
+    }
+
     /// Get the current en_passant square
     ///
     /// ```
@@ -167,6 +212,32 @@ impl BoardBuilder {
             .map(|f| Square::make_square((!self.get_side_to_move()).to_fourth_rank(), f))
     }
 
+    /// Get the number of halfmoves since the last capture or pawn move
+    ///
+    /// ```
+    /// use chess::BoardBuilder;
+    /// use std::str::FromStr;
+    ///
+    /// let bb = BoardBuilder::from_str("8/8/8/8/8/8/8/K1k5 w - - 17 52").unwrap();
+    /// assert_eq!(bb.get_halfmove_clock(), 17);
+    /// ```
+    pub fn get_halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// Get the fullmove number
+    ///
+    /// ```
+    /// use chess::BoardBuilder;
+    /// use std::str::FromStr;
+    ///
+    /// let bb = BoardBuilder::from_str("8/8/8/8/8/8/8/K1k5 w - - 17 52").unwrap();
+    /// assert_eq!(bb.get_fullmove_number(), 52);
+    /// ```
+    pub fn get_fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
     /// Set the side to move on the position
     ///
     /// This function can be used on self directly or in a builder pattern.
@@ -205,6 +276,56 @@ impl BoardBuilder {
         self
     }
 
+    /// Turn Chess960 mode on or off.
+    ///
+    /// This function can be used on self directly or in a builder pattern.
+    ///
+    /// ```
+    /// use chess::{Board, BoardBuilder, ChessMove, MoveGen, Square};
+    /// use std::convert::TryInto;
+    ///
+    /// let board: Board = BoardBuilder::default()
+    ///     .chess960(true)
+    ///     .try_into()
+    ///     .expect("Valid Position");
+    /// assert!(board.is_chess960());
+    /// ```
+    pub fn chess960(&mut self, chess960: bool) -> &mut Self {
+        self.chess960 = chess960;
+        self
+    }
+
+    /// Set the files of the rooks used for castling for a particular color.  This only makes
+    /// sense for Chess960 positions, where the rooks may start on any file.
+    ///
+    /// This function can be used on self directly or in a builder pattern.
+    ///
+    /// ```
+    /// use chess::{Board, BoardBuilder, CastleRights, Color, File, Piece, Square};
+    /// use std::convert::TryInto;
+    ///
+    /// let board: Board = BoardBuilder::new()
+    ///     .piece(Square::B1, Piece::King, Color::White)
+    ///     .piece(Square::A1, Piece::Rook, Color::White)
+    ///     .piece(Square::C1, Piece::Rook, Color::White)
+    ///     .piece(Square::G8, Piece::King, Color::Black)
+    ///     .castle_rights(Color::White, CastleRights::Both)
+    ///     .castle_rook_files(Color::White, File::C, File::A)
+    ///     .chess960(true)
+    ///     .try_into()
+    ///     .expect("Valid Position");
+    /// assert_eq!(board.to_string(), "6k1/8/8/8/8/8/8/RKR5 w KQ - 0 1");
+    /// ```
+    pub fn castle_rook_files(
+        &mut self,
+        color: Color,
+        kingside: File,
+        queenside: File,
+    ) -> &mut Self {
+        self.castle_rook_files[color.to_index()] = (kingside, queenside);
+        self
+    }
+
     /// Set a piece on a square.
     ///
     /// Note that this can and will overwrite another piece on the square if need.
@@ -259,6 +380,36 @@ impl BoardBuilder {
         self.en_passant = file;
         self
     }
+
+    /// Set the number of halfmoves since the last capture or pawn move.
+    ///
+    /// This function can be used directly or in a builder pattern.
+    ///
+    /// ```
+    /// use chess::BoardBuilder;
+    ///
+    /// BoardBuilder::new()
+    ///              .halfmove_clock(12);
+    /// ```
+    pub fn halfmove_clock(&mut self, halfmove_clock: u16) -> &mut Self {
+        self.halfmove_clock = halfmove_clock;
+        self
+    }
+
+    /// Set the fullmove number.
+    ///
+    /// This function can be used directly or in a builder pattern.
+    ///
+    /// ```
+    /// use chess::BoardBuilder;
+    ///
+    /// BoardBuilder::new()
+    ///              .fullmove_number(40);
+    /// ```
+    pub fn fullmove_number(&mut self, fullmove_number: u16) -> &mut Self {
+        self.fullmove_number = fullmove_number;
+        self
+    }
 }
 
 impl Index<Square> for BoardBuilder {
@@ -312,16 +463,16 @@ impl fmt::Display for BoardBuilder {
             write!(f, "b ")?;
         }
 
-        write!(
-            f,
-            "{}",
-            self.castle_rights[Color::White.to_index()].to_string(Color::White)
-        )?;
-        write!(
-            f,
-            "{}",
-            self.castle_rights[Color::Black.to_index()].to_string(Color::Black)
-        )?;
+        for color in ALL_COLORS.iter() {
+            let castle_rights = self.castle_rights[color.to_index()];
+            let (kingside, queenside) = self.castle_rook_files[color.to_index()];
+            if castle_rights.has_kingside() {
+                write!(f, "{}", self.castle_char(*color, kingside, 'k'))?;
+            }
+            if castle_rights.has_queenside() {
+                write!(f, "{}", self.castle_char(*color, queenside, 'q'))?;
+            }
+        }
         if self.castle_rights[0] == CastleRights::NoRights
             && self.castle_rights[1] == CastleRights::NoRights
         {
@@ -335,7 +486,7 @@ impl fmt::Display for BoardBuilder {
             write!(f, "-")?;
         }
 
-        write!(f, " 0 1")
+        write!(f, " {} {}", self.halfmove_clock, self.fullmove_number)
     }
 }
 
@@ -452,34 +603,134 @@ impl FromStr for BoardBuilder {
             }
         }
 
-        if castles.contains("K") && castles.contains("Q") {
-            fen.castle_rights[Color::White.to_index()] = CastleRights::Both;
-        } else if castles.contains("K") {
-            fen.castle_rights[Color::White.to_index()] = CastleRights::KingSide;
-        } else if castles.contains("Q") {
-            fen.castle_rights[Color::White.to_index()] = CastleRights::QueenSide;
-        } else {
-            fen.castle_rights[Color::White.to_index()] = CastleRights::NoRights;
-        }
+        // Castle rights may be given as standard FEN or X-FEN ("KQkq", where "K" means the
+        // outermost rook on the kingside), or as Shredder-FEN ("HAha", giving the rook files).
+        for x in castles.chars() {
+            let color = if x.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king_file = fen.king_file(color).unwrap_or(File::E);
+            let (side, rook_file) = match x.to_ascii_lowercase() {
+                'k' => (
+                    CastleRights::KingSide,
+                    fen.outermost_rook(color, true).unwrap_or(File::H),
+                ),
+                'q' => (
+                    CastleRights::QueenSide,
+                    fen.outermost_rook(color, false).unwrap_or(File::A),
+                ),
+                'a'..='h' => {
+                    fen.chess960 = true;
+                    let rook_file =
+                        File::from_index(x.to_ascii_lowercase() as usize - 'a' as usize);
+                    if rook_file > king_file {
+                        (CastleRights::KingSide, rook_file)
+                    } else {
+                        (CastleRights::QueenSide, rook_file)
+                    }
+                }
+                '-' => continue,
+                _ => {
+                    return Err(Error::InvalidFen {
+                        fen: value.to_string(),
+                    })
+                }
+            };
+
+            fen.castle_rights[color.to_index()] = fen.castle_rights[color.to_index()].add(side);
+            let files = &mut fen.castle_rook_files[color.to_index()];
+            if side == CastleRights::KingSide {
+                files.0 = rook_file;
+            } else {
+                files.1 = rook_file;
+            }
 
-        if castles.contains("k") && castles.contains("q") {
-            fen.castle_rights[Color::Black.to_index()] = CastleRights::Both;
-        } else if castles.contains("k") {
-            fen.castle_rights[Color::Black.to_index()] = CastleRights::KingSide;
-        } else if castles.contains("q") {
-            fen.castle_rights[Color::Black.to_index()] = CastleRights::QueenSide;
-        } else {
-            fen.castle_rights[Color::Black.to_index()] = CastleRights::NoRights;
+            // Anything other than a king on the E file and rooks in the corners can only be
+            // Chess960.
+            if king_file != File::E || (rook_file != File::H && rook_file != File::A) {
+                fen.chess960 = true;
+            }
         }
 
         if let Ok(sq) = Square::from_str(&ep) {
             fen = fen.en_passant(Some(sq.get_file()));
         }
 
+        if let Some(halfmove_clock) = tokens.get(4) {
+            fen = fen.halfmove_clock(u16::from_str(halfmove_clock).map_err(|_| {
+                Error::InvalidFen {
+                    fen: value.to_string(),
+                }
+            })?);
+        }
+
+        if let Some(fullmove_number) = tokens.get(5) {
+            fen = fen.fullmove_number(u16::from_str(fullmove_number).map_err(|_| {
+                Error::InvalidFen {
+                    fen: value.to_string(),
+                }
+            })?);
+        }
+
         Ok(*fen)
     }
 }
 
+impl BoardBuilder {
+    /// Which file is the king of `color` on, if it is on its back rank?
+    fn king_file(&self, color: Color) -> Option<File> {
+        ALL_FILES.iter().cloned().find(|file| {
+            self[Square::make_square(color.to_my_backrank(), *file)] == Some((Piece::King, color))
+        })
+    }
+
+    /// Find the rook of `color` on its back rank that is furthest from its king, on the
+    /// kingside or the queenside.
+    fn outermost_rook(&self, color: Color, kingside: bool) -> Option<File> {
+        let king_file = self.king_file(color)?;
+        let is_rook = |file: &File| {
+            self[Square::make_square(color.to_my_backrank(), *file)] == Some((Piece::Rook, color))
+        };
+        if kingside {
+            ALL_FILES
+                .iter()
+                .rev()
+                .take_while(|file| **file > king_file)
+                .cloned()
+                .find(is_rook)
+        } else {
+            ALL_FILES
+                .iter()
+                .take_while(|file| **file < king_file)
+                .cloned()
+                .find(is_rook)
+        }
+    }
+
+    /// Convert one castle right into its X-FEN character.  This is "K" or "Q" for the outermost
+    /// rook, and the file of the rook otherwise.  Chess960 positions that look like standard
+    /// chess also use the file of the rook, so they are still recognized as Chess960 when parsed.
+    fn castle_char(&self, color: Color, rook_file: File, side: char) -> char {
+        let looks_standard = self.king_file(color) == Some(File::E)
+            && (rook_file == File::H || rook_file == File::A);
+        let c = if !self.chess960
+            || (!looks_standard && self.outermost_rook(color, side == 'k') == Some(rook_file))
+        {
+            side
+        } else {
+            (b'a' + rook_file.to_index() as u8) as char
+        };
+
+        if color == Color::White {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
+    }
+}
+
 impl From<&Board> for BoardBuilder {
     fn from(board: &Board) -> Self {
         let mut pieces = vec![];
@@ -490,13 +741,22 @@ impl From<&Board> for BoardBuilder {
             }
         }
 
-        BoardBuilder::setup(
+        let mut result = BoardBuilder::setup(
             &pieces,
             board.side_to_move(),
             board.castle_rights(Color::White),
             board.castle_rights(Color::Black),
             board.en_passant().map(|sq| sq.get_file()),
-        )
+        );
+        result
+            .halfmove_clock(board.halfmove_clock())
+            .fullmove_number(board.fullmove_number())
+            .chess960(board.is_chess960());
+        for color in ALL_COLORS.iter() {
+            let (kingside, queenside) = board.castle_rook_files(*color);
+            result.castle_rook_files(*color, kingside, queenside);
+        }
+        result
     }
 }
 
@@ -506,6 +766,31 @@ impl From<Board> for BoardBuilder {
     }
 }
 
+impl From<&VariantBoard> for BoardBuilder {
+    /// The variant, and the checks left in Three-check, are not kept.
+    fn from(board: &VariantBoard) -> Self {
+        let mut pieces = vec![];
+        for sq in ALL_SQUARES.iter() {
+            if let Some(piece) = board.piece_on(*sq) {
+                let color = board.color_on(*sq).unwrap();
+                pieces.push((*sq, piece, color));
+            }
+        }
+
+        let mut result = BoardBuilder::setup(
+            &pieces,
+            board.side_to_move(),
+            board.castle_rights(Color::White),
+            board.castle_rights(Color::Black),
+            board.en_passant().map(|sq| sq.get_file()),
+        );
+        result
+            .halfmove_clock(board.halfmove_clock())
+            .fullmove_number(board.fullmove_number());
+        result
+    }
+}
+
 #[cfg(test)]
 use crate::bitboard::BitBoard;
 #[cfg(test)]
@@ -555,3 +840,52 @@ fn test_in_check() {
     let res: Result<Board, _> = bb.try_into();
     assert!(res.is_err()); // My opponent cannot be in check when it's my move.
 }
+
+#[test]
+fn test_move_counters_round_trip() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let bb = BoardBuilder::from_str(fen).unwrap();
+    assert_eq!(bb.get_halfmove_clock(), 2);
+    assert_eq!(bb.get_fullmove_number(), 3);
+    assert_eq!(format!("{}", bb), fen);
+
+    let board: Board = bb.try_into().unwrap();
+    assert_eq!(format!("{}", board), fen);
+
+    // The move counters are optional
+    let bb = BoardBuilder::from_str("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+    assert_eq!(format!("{}", bb), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+
+    assert!(BoardBuilder::from_str("4k3/8/8/8/8/8/8/4K3 w - - x 1").is_err());
+    assert!(BoardBuilder::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 -1").is_err());
+}
+
+#[test]
+fn test_chess960_castle_rights() {
+    // X-FEN uses the file of the rook when it is not the outermost rook.
+    let bb = BoardBuilder::from_str("1r1k1r1r/8/8/8/8/8/8/RR1K3R w KBkf - 0 1").unwrap();
+    assert!(bb.is_chess960());
+    assert_eq!(bb.get_castle_rights(Color::White), CastleRights::Both);
+    assert_eq!(bb.get_castle_rook_files(Color::White), (File::H, File::B));
+    assert_eq!(bb.get_castle_rights(Color::Black), CastleRights::KingSide);
+    assert_eq!(bb.get_castle_rook_files(Color::Black).0, File::F);
+    assert_eq!(format!("{}", bb), "1r1k1r1r/8/8/8/8/8/8/RR1K3R w KBf - 0 1");
+
+    // Shredder-FEN is accepted, and written back as X-FEN.
+    let bb = BoardBuilder::from_str("1r1k1r1r/8/8/8/8/8/8/RR1K3R w HBf - 0 1").unwrap();
+    assert_eq!(format!("{}", bb), "1r1k1r1r/8/8/8/8/8/8/RR1K3R w KBf - 0 1");
+
+    // Standard positions are not Chess960, unless asked.
+    let bb = BoardBuilder::default();
+    assert!(!bb.is_chess960());
+    let mut bb960 = bb;
+    bb960.chess960(true);
+    let board: Board = bb960.try_into().unwrap();
+    assert_eq!(
+        format!("{}", board),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+    );
+    assert_eq!(Board::from_str(&format!("{}", board)).unwrap(), board);
+
+    assert!(BoardBuilder::from_str("4k3/8/8/8/8/8/8/4K3 w X - 0 1").is_err());
+}
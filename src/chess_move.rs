@@ -1,10 +1,12 @@
-use crate::board::Board;
+use crate::board::{Board, BoardStatus};
+use crate::color::Color;
 use crate::error::Error;
 use crate::file::File;
 use crate::movegen::MoveGen;
 use crate::piece::Piece;
 use crate::rank::Rank;
 use crate::square::Square;
+use crate::variant_board::VariantBoard;
 
 use std::cmp::Ordering;
 use std::fmt;
@@ -60,16 +62,35 @@ impl ChessMove {
     /// ```
     pub fn from_san(board: &Board, move_text: &str) -> Result<ChessMove, Error> {
         // Castles first...
-        if move_text == "O-O" || move_text == "O-O-O" {
+        let castle_text = move_text.trim_end_matches(['+', '#']);
+        if castle_text == "O-O" || castle_text == "O-O-O" {
             let rank = board.side_to_move().to_my_backrank();
-            let source_file = File::E;
-            let dest_file = if move_text == "O-O" { File::G } else { File::C };
-
-            let m = ChessMove::new(
-                Square::make_square(rank, source_file),
-                Square::make_square(rank, dest_file),
-                None,
-            );
+            let m = if board.is_chess960() {
+                // In Chess960, castling is encoded as the king capturing its own rook
+                let (kingside, queenside) = board.castle_rook_files(board.side_to_move());
+                let dest_file = if castle_text == "O-O" {
+                    kingside
+                } else {
+                    queenside
+                };
+                ChessMove::new(
+                    board.king_square(board.side_to_move()),
+                    Square::make_square(rank, dest_file),
+                    None,
+                )
+            } else {
+                let source_file = File::E;
+                let dest_file = if castle_text == "O-O" {
+                    File::G
+                } else {
+                    File::C
+                };
+                ChessMove::new(
+                    Square::make_square(rank, source_file),
+                    Square::make_square(rank, dest_file),
+                    None,
+                )
+            };
             if MoveGen::new_legal(&board).any(|l| l == m) {
                 return Ok(m);
             } else {
@@ -123,7 +144,7 @@ impl ChessMove {
         // [Optional Source Specifier] ( "" | "a-h" | "1-8" | ("a-h" + "1-8"))
         // [Optional Takes Specifier] ("" | "x")
         // [Full Destination Square] ("a-h" + "0-8")
-        // [Optional Promotion Specifier] ("" | "N" | "B" | "R" | "Q")
+        // [Optional Promotion Specifier] ("" | "N" | "B" | "R" | "Q" | "=N" | "=B" | "=R" | "=Q")
         // [Optional Check(mate) Specifier] ("" | "+" | "#")
         // [Optional En Passant Specifier] ("" | " e.p.")
 
@@ -269,6 +290,10 @@ impl ChessMove {
             sq
         };
 
+        if move_text.get(cur_index..(cur_index + 1)) == Some("=") {
+            cur_index += 1;
+        }
+
         let promotion = if let Some(s) = move_text.get(cur_index..(cur_index + 1)) {
             match s {
                 "N" => {
@@ -370,6 +395,176 @@ impl ChessMove {
 
         found_move.ok_or(error.clone())
     }
+
+    /// Convert this move into SAN (Standard Algebraic Notation), given the `Board` it is played
+    /// on.  The move is disambiguated by file, then rank, then square only when another piece of
+    /// the same type could move to the same square, and a `+` or `#` suffix is added for checks
+    /// and checkmates.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Piece, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::default();
+    /// assert_eq!(ChessMove::new(Square::G1, Square::F3, None).to_san(&board), "Nf3");
+    ///
+    /// let board = Board::from_str("4k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1").expect("Valid FEN");
+    /// assert_eq!(ChessMove::new(Square::B7, Square::B8, Some(Piece::Queen)).to_san(&board), "b8=Q+");
+    /// assert_eq!(ChessMove::new(Square::E1, Square::C1, None).to_san(&board), "O-O-O");
+    /// assert_eq!(ChessMove::new(Square::A1, Square::A8, None).to_san(&board), "Ra8+");
+    /// assert_eq!(ChessMove::new(Square::H1, Square::F1, None).to_san(&board), "Rf1");
+    /// ```
+    pub fn to_san(&self, board: &Board) -> String {
+        let source = self.source;
+        let dest = self.dest;
+        let piece = board
+            .piece_on(source)
+            .expect("A piece on the source square");
+
+        let castles = if board.is_chess960() {
+            piece == Piece::King && board.color_on(dest) == Some(board.side_to_move())
+        } else {
+            piece == Piece::King
+                && (source.get_file().to_index() as i8 - dest.get_file().to_index() as i8).abs()
+                    == 2
+        };
+        let captures = board.piece_on(dest).is_some()
+            || (piece == Piece::Pawn && source.get_file() != dest.get_file());
+        let others: Vec<ChessMove> = if castles || piece == Piece::Pawn {
+            vec![]
+        } else {
+            MoveGen::new_legal(board)
+                .filter(|o| {
+                    o.get_dest() == dest
+                        && o.get_source() != source
+                        && board.piece_on(o.get_source()) == Some(piece)
+                })
+                .collect()
+        };
+        let mut san = self.san_without_suffix(piece, castles, captures, &others);
+
+        let after = board.make_move_new(*self);
+        if after.status() == BoardStatus::Checkmate {
+            san.push('#');
+        } else if after.checkers().popcnt() > 0 {
+            san.push('+');
+        }
+
+        san
+    }
+
+    /// Convert this move into SAN, given the `VariantBoard` it is played on, as `to_san()` does
+    /// for a `Board`.  A move that wins by a rule of the variant, such as reaching the hill in
+    /// King of the Hill, gets no suffix unless it also gives check.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Piece, Square, Variant, VariantBoard};
+    ///
+    /// let board = VariantBoard::from_fen(Variant::Antichess, "8/1P6/8/8/8/8/8/k7 w - - 0 1")
+    ///     .expect("Valid position");
+    /// let m = ChessMove::new(Square::B7, Square::B8, Some(Piece::King));
+    /// assert_eq!(m.to_variant_san(&board), "b8=K");
+    ///
+    /// let board = VariantBoard::from_fen(Variant::Atomic, "4k3/3p4/8/8/8/8/8/3QK3 w - - 0 1")
+    ///     .expect("Valid position");
+    /// let m = ChessMove::new(Square::D1, Square::D7, None);
+    /// assert_eq!(m.to_variant_san(&board), "Qxd7");
+    /// ```
+    pub fn to_variant_san(&self, board: &VariantBoard) -> String {
+        let source = self.source;
+        let dest = self.dest;
+        let piece = board
+            .piece_on(source)
+            .expect("A piece on the source square");
+
+        let castles = piece == Piece::King
+            && (source.get_file().to_index() as i8 - dest.get_file().to_index() as i8).abs() == 2;
+        let captures = board.is_capture(*self);
+        let others: Vec<ChessMove> = if castles || piece == Piece::Pawn {
+            vec![]
+        } else {
+            board
+                .legal_moves()
+                .into_iter()
+                .filter(|o| {
+                    o.get_dest() == dest
+                        && o.get_source() != source
+                        && board.piece_on(o.get_source()) == Some(piece)
+                })
+                .collect()
+        };
+        let mut san = self.san_without_suffix(piece, castles, captures, &others);
+
+        let after = board.make_move_new(*self);
+        if after.is_check() {
+            if after.legal_moves().is_empty() && after.variant_end().is_none() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+
+        san
+    }
+
+    /// The SAN of this move without a check suffix, disambiguated from `others`, the other legal
+    /// moves of the same kind of piece to the same square.
+    fn san_without_suffix(
+        &self,
+        piece: Piece,
+        castles: bool,
+        captures: bool,
+        others: &[ChessMove],
+    ) -> String {
+        let source = self.source;
+        let dest = self.dest;
+        let mut san = String::new();
+        let source_text = source.to_string();
+        let (source_file, source_rank) = source_text.split_at(1);
+
+        if castles {
+            if dest.get_file() > source.get_file() {
+                san.push_str("O-O");
+            } else {
+                san.push_str("O-O-O");
+            }
+            return san;
+        }
+
+        if piece == Piece::Pawn {
+            if captures {
+                san.push_str(source_file);
+            }
+        } else {
+            san.push_str(&piece.to_string(Color::White));
+            if !others.is_empty() {
+                if others
+                    .iter()
+                    .all(|o| o.get_source().get_file() != source.get_file())
+                {
+                    san.push_str(source_file);
+                } else if others
+                    .iter()
+                    .all(|o| o.get_source().get_rank() != source.get_rank())
+                {
+                    san.push_str(source_rank);
+                } else {
+                    san.push_str(&source_text);
+                }
+            }
+        }
+
+        if captures {
+            san.push('x');
+        }
+        san.push_str(&dest.to_string());
+
+        if let Some(promotion) = self.promotion {
+            san.push('=');
+            san.push_str(&promotion.to_string(Color::White));
+        }
+        san
+    }
 }
 
 impl fmt::Display for ChessMove {
@@ -440,3 +635,63 @@ fn test_basic_moves() {
         ChessMove::new(Square::E2, Square::E4, None)
     );
 }
+
+#[test]
+fn test_san_promotions_and_castles_with_check() {
+    let board = Board::from_str("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1").expect("Valid FEN");
+    let promotion = ChessMove::new(Square::B7, Square::B8, Some(Piece::Queen));
+    assert_eq!(ChessMove::from_san(&board, "b8Q").ok(), Some(promotion));
+    assert_eq!(ChessMove::from_san(&board, "b8=Q+").ok(), Some(promotion));
+    assert_eq!(
+        ChessMove::from_san(&board, "b8=N").ok(),
+        Some(ChessMove::new(Square::B7, Square::B8, Some(Piece::Knight)))
+    );
+
+    let castles = ChessMove::new(Square::E1, Square::G1, None);
+    assert_eq!(ChessMove::from_san(&board, "O-O").ok(), Some(castles));
+    assert_eq!(ChessMove::from_san(&board, "O-O+").ok(), Some(castles));
+    assert!(ChessMove::from_san(&board, "O-O-O").is_err());
+}
+
+#[test]
+fn test_to_san() {
+    let board = Board::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").expect("Valid FEN");
+    assert_eq!(
+        ChessMove::new(Square::E5, Square::D6, None).to_san(&board),
+        "exd6"
+    );
+
+    // Knights on b1, f1, b5 and f5 need different amounts of disambiguation to reach each square.
+    let board = Board::from_str("4k3/8/8/1N3N2/8/8/8/1N2KN2 w - - 0 1").expect("Valid FEN");
+    assert_eq!(
+        ChessMove::new(Square::B1, Square::D2, None).to_san(&board),
+        "Nbd2"
+    );
+    assert_eq!(
+        ChessMove::new(Square::B5, Square::A3, None).to_san(&board),
+        "N5a3"
+    );
+    assert_eq!(
+        ChessMove::new(Square::B5, Square::A7, None).to_san(&board),
+        "Na7"
+    );
+
+    let board = Board::from_str("4k3/8/8/1N3N2/8/5N2/8/1N2KN2 w - - 0 1").expect("Valid FEN");
+    assert_eq!(
+        ChessMove::new(Square::F5, Square::D4, None).to_san(&board),
+        "Nf5d4"
+    );
+
+    let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").expect("Valid FEN");
+    assert_eq!(
+        ChessMove::new(Square::A1, Square::A8, None).to_san(&board),
+        "Ra8#"
+    );
+
+    let board =
+        Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .expect("Valid FEN");
+    for m in MoveGen::new_legal(&board) {
+        assert_eq!(ChessMove::from_san(&board, &m.to_san(&board)).ok(), Some(m));
+    }
+}
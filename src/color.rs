@@ -3,6 +3,7 @@ use std::ops::Not;
 
 /// Represent a color.
 #[derive(PartialOrd, PartialEq, Eq, Copy, Clone, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     White,
     Black,
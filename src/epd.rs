@@ -0,0 +1,479 @@
+use crate::board::Board;
+use crate::board_builder::BoardBuilder;
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// The value of an EPD operation.
+///
+/// How the operands are read depends on the opcode:
+///
+/// * `bm`, `am`, `pm` and `sm` take SAN moves in the position, and `pv` takes a line of SAN
+///   moves starting from the position.
+/// * `acd`, `acn`, `acs`, `ce`, `dm`, `fmvn`, `hmvc`, `rc` and the perft counts `D1`, `D2`, ...
+///   take a single integer.
+/// * Any other opcode with a single quoted operand (such as `id` or `c0`) is a `String`, and
+///   anything else is kept as a list of `Tokens`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EpdValue {
+    Moves(Vec<ChessMove>),
+    Integer(i64),
+    String(String),
+    Tokens(Vec<String>),
+}
+
+/// A position from an EPD (Extended Position Description) record, with its operations.
+///
+/// The four FEN fields may also be followed by the halfmove clock and fullmove number, as in the
+/// full FENs of most perft suites.
+///
+/// ```
+/// use chess::{ChessMove, Epd, EpdValue, Square};
+/// use std::str::FromStr;
+///
+/// let epd = Epd::from_str(
+///     r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#,
+/// )
+/// .expect("Valid EPD");
+///
+/// assert_eq!(epd.id(), Some("WAC.001"));
+/// assert_eq!(epd.best_moves(), &[ChessMove::new(Square::G3, Square::G6, None)]);
+/// assert_eq!(
+///     epd.to_string(),
+///     r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Epd {
+    board: Board,
+    operations: Vec<(String, EpdValue)>,
+}
+
+impl Epd {
+    /// An EPD record for `board`, without any operations.
+    pub fn new(board: Board) -> Epd {
+        Epd {
+            board,
+            operations: vec![],
+        }
+    }
+
+    /// The position.  If the record has `hmvc` or `fmvn` operations, they give the halfmove
+    /// clock and fullmove number.
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    /// The operations, in the order they were written.
+    pub fn operations(&self) -> &[(String, EpdValue)] {
+        &self.operations
+    }
+
+    /// The value of the operation with this opcode.
+    ///
+    /// ```
+    /// use chess::{Epd, EpdValue};
+    /// use std::str::FromStr;
+    ///
+    /// let epd = Epd::from_str("4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D2 66").expect("Valid EPD");
+    /// assert_eq!(epd.get("D2"), Some(&EpdValue::Integer(66)));
+    /// assert_eq!(epd.get("D3"), None);
+    /// ```
+    pub fn get(&self, opcode: &str) -> Option<&EpdValue> {
+        self.operations
+            .iter()
+            .find(|(o, _)| o == opcode)
+            .map(|(_, value)| value)
+    }
+
+    /// The `id` of the record.
+    pub fn id(&self) -> Option<&str> {
+        match self.get("id") {
+            Some(EpdValue::String(id)) => Some(id),
+            Some(EpdValue::Tokens(tokens)) if tokens.len() == 1 => Some(&tokens[0]),
+            _ => None,
+        }
+    }
+
+    /// The best moves (`bm`).
+    pub fn best_moves(&self) -> &[ChessMove] {
+        match self.get("bm") {
+            Some(EpdValue::Moves(moves)) => moves,
+            _ => &[],
+        }
+    }
+
+    /// The moves to avoid (`am`).
+    pub fn avoid_moves(&self) -> &[ChessMove] {
+        match self.get("am") {
+            Some(EpdValue::Moves(moves)) => moves,
+            _ => &[],
+        }
+    }
+
+    /// Set an operation, replacing any operation with the same opcode.
+    ///
+    /// This function can be used directly or in a builder pattern.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Epd, EpdValue, Square};
+    ///
+    /// let e4 = ChessMove::new(Square::E2, Square::E4, None);
+    /// let epd = Epd::new(Board::default())
+    ///     .operation("bm", EpdValue::Moves(vec![e4]))
+    ///     .operation("id", EpdValue::String("start".to_string()))
+    ///     .to_string();
+    ///
+    /// assert_eq!(
+    ///     epd,
+    ///     r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4; id "start";"#
+    /// );
+    /// ```
+    pub fn operation(&mut self, opcode: &str, value: EpdValue) -> &mut Self {
+        match self.operations.iter_mut().find(|(o, _)| o == opcode) {
+            Some(operation) => operation.1 = value,
+            None => self.operations.push((opcode.to_string(), value)),
+        }
+        self
+    }
+}
+
+impl FromStr for Epd {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        parse_epd(line).map_err(|message| Error::InvalidEpd { line: 1, message })
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = self.board.to_string();
+        let fields = fen.split(' ').take(4).collect::<Vec<_>>();
+        write!(f, "{}", fields.join(" "))?;
+
+        for (opcode, value) in self.operations.iter() {
+            write!(f, " {}", opcode)?;
+            match value {
+                EpdValue::Moves(moves) => {
+                    let mut board = self.board;
+                    for m in moves.iter() {
+                        write!(f, " {}", m.to_san(&board))?;
+                        if opcode == "pv" {
+                            board = board.make_move_new(*m);
+                        }
+                    }
+                }
+                EpdValue::Integer(value) => write!(f, " {}", value)?,
+                EpdValue::String(value) => write!(f, " \"{}\"", value)?,
+                EpdValue::Tokens(tokens) => {
+                    for token in tokens.iter() {
+                        if token.is_empty()
+                            || token.contains(|c: char| c.is_whitespace() || c == ';')
+                        {
+                            write!(f, " \"{}\"", token)?;
+                        } else {
+                            write!(f, " {}", token)?;
+                        }
+                    }
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read EPD records from a stream, one per line.
+///
+/// Blank lines, and lines starting with `#`, are skipped.  A line that cannot be parsed gives an
+/// `Error::InvalidEpd` with its line number, and reading carries on with the next line.
+///
+/// ```
+/// use chess::{EpdReader, Error};
+///
+/// let epd = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+///
+/// 8/7p/5k2/5p2/p1p2P2/Pr1pPK2/1P1R3P/8 b - - bm Qxb2; id "WAC.002";
+/// 5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - bm Rg3; id "WAC.003";
+/// "#;
+///
+/// let records: Vec<_> = EpdReader::new(epd.as_bytes()).collect();
+/// assert_eq!(records.len(), 3);
+/// assert_eq!(records[0].as_ref().unwrap().id(), Some("WAC.001"));
+/// match records[1] {
+///     Err(Error::InvalidEpd { line, .. }) => assert_eq!(line, 3),
+///     _ => panic!("There is no queen to play Qxb2"),
+/// }
+/// assert_eq!(records[2].as_ref().unwrap().id(), Some("WAC.003"));
+/// ```
+pub struct EpdReader<R: BufRead> {
+    reader: R,
+    line_number: usize,
+    eof: bool,
+}
+
+impl<R: BufRead> EpdReader<R> {
+    /// Create a new `EpdReader` reading from `reader`.
+    pub fn new(reader: R) -> EpdReader<R> {
+        EpdReader {
+            reader,
+            line_number: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EpdReader<R> {
+    type Item = Result<Epd, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        while !self.eof {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => self.eof = true,
+                Ok(_) => {
+                    self.line_number += 1;
+                    let text = line.trim();
+                    if text.is_empty() || text.starts_with('#') {
+                        continue;
+                    }
+                    return Some(parse_epd(text).map_err(|message| Error::InvalidEpd {
+                        line: self.line_number,
+                        message,
+                    }));
+                }
+                Err(e) => {
+                    self.eof = true;
+                    return Some(Err(Error::Io {
+                        message: e.to_string(),
+                    }));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// An operand, and whether it was quoted.
+type Operand<'a> = (&'a str, bool);
+
+fn parse_epd(line: &str) -> Result<Epd, String> {
+    let mut rest = line.trim();
+    let mut fields = vec![];
+    for _ in 0..4 {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err("expected four FEN fields".to_string());
+        }
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    // a full FEN, as perft suites often use, also has the halfmove clock and fullmove number,
+    // which can not be mistaken for opcodes
+    let mut counters = vec![];
+    let mut after = rest;
+    while counters.len() < 2 {
+        let end = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        if end == 0 {
+            break;
+        }
+        counters.push(&after[..end]);
+        after = after[end..].trim_start();
+    }
+    if counters.len() == 2 {
+        fields.extend(counters);
+        rest = after;
+    }
+
+    let mut board =
+        Board::from_str(&fields.join(" ")).map_err(|_| "invalid position".to_string())?;
+
+    let mut operations = vec![];
+    for operation in split_operations(rest)? {
+        let (opcode, quoted) = operation[0];
+        if quoted {
+            return Err(format!("expected an opcode, found \"{}\"", opcode));
+        }
+        let value = parse_value(&board, opcode, &operation[1..])?;
+        operations.push((opcode.to_string(), value));
+    }
+
+    // the halfmove clock and fullmove number come from the operations, not the FEN
+    let mut builder = BoardBuilder::from(&board);
+    for (opcode, value) in operations.iter() {
+        match (opcode.as_str(), value) {
+            ("hmvc", EpdValue::Integer(n)) => {
+                builder.halfmove_clock(u16::try_from(*n).map_err(|_| "invalid hmvc")?);
+            }
+            ("fmvn", EpdValue::Integer(n)) => {
+                builder.fullmove_number(u16::try_from(*n).map_err(|_| "invalid fmvn")?);
+            }
+            _ => {}
+        }
+    }
+    board = Board::try_from(&builder).map_err(|_| "invalid position".to_string())?;
+
+    Ok(Epd { board, operations })
+}
+
+/// Split the operations up at each `;`, ignoring any inside quotes.
+fn split_operations(text: &str) -> Result<Vec<Vec<Operand<'_>>>, String> {
+    let mut operations = vec![];
+    let mut current = vec![];
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(after) = rest.strip_prefix(';') {
+            if !current.is_empty() {
+                operations.push(current);
+                current = vec![];
+            }
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('"') {
+            let end = after
+                .find('"')
+                .ok_or_else(|| "unterminated string".to_string())?;
+            if current.is_empty() {
+                return Err("expected an opcode, found a string".to_string());
+            }
+            current.push((&after[..end], true));
+            rest = &after[end + 1..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == ';' || c == '"')
+                .unwrap_or(rest.len());
+            current.push((&rest[..end], false));
+            rest = &rest[end..];
+        }
+    }
+    if !current.is_empty() {
+        operations.push(current);
+    }
+    Ok(operations)
+}
+
+fn parse_value(board: &Board, opcode: &str, operands: &[Operand]) -> Result<EpdValue, String> {
+    let is_perft = opcode.len() > 1
+        && opcode.starts_with('D')
+        && opcode[1..].chars().all(|c| c.is_ascii_digit());
+
+    match opcode {
+        "bm" | "am" | "pm" | "sm" | "pv" => {
+            let mut position = *board;
+            let mut moves = vec![];
+            for (san, _) in operands.iter() {
+                let m = ChessMove::from_san(&position, san)
+                    .map_err(|_| format!("invalid move {} for {}", san, opcode))?;
+                if opcode == "pv" {
+                    position = position.make_move_new(m);
+                }
+                moves.push(m);
+            }
+            Ok(EpdValue::Moves(moves))
+        }
+        "acd" | "acn" | "acs" | "ce" | "dm" | "fmvn" | "hmvc" | "rc" => {
+            parse_integer(opcode, operands)
+        }
+        _ if is_perft => parse_integer(opcode, operands),
+        _ => match operands {
+            [(value, true)] => Ok(EpdValue::String(value.to_string())),
+            _ => Ok(EpdValue::Tokens(
+                operands.iter().map(|(t, _)| t.to_string()).collect(),
+            )),
+        },
+    }
+}
+
+fn parse_integer(opcode: &str, operands: &[Operand]) -> Result<EpdValue, String> {
+    match operands {
+        [(value, false)] => value
+            .parse()
+            .map(EpdValue::Integer)
+            .map_err(|_| format!("expected an integer for {}", opcode)),
+        _ => Err(format!("expected an integer for {}", opcode)),
+    }
+}
+
+#[cfg(test)]
+use crate::square::Square;
+
+#[test]
+fn test_epd_round_trip() {
+    let records = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - bm Qxh7+; id "WAC.004";
+1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id "STS(v1.0) Undermine.001"; c0 "f5=10, Be5+=2, Bf2=3, Bg4=2";
+r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - D1 48; D2 2039;
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am f3 g4; pv e4 e5 Nf3; ce 35; acd 12; hmvc 0; fmvn 1; xyz a "b c";"#;
+
+    for line in records.lines() {
+        let epd = Epd::from_str(line).expect("Valid EPD");
+        assert_eq!(epd.to_string(), line);
+    }
+
+    let epd = Epd::from_str(records.lines().nth(4).unwrap()).expect("Valid EPD");
+    assert_eq!(epd.avoid_moves().len(), 2);
+    assert_eq!(
+        epd.get("pv"),
+        Some(&EpdValue::Moves(vec![
+            ChessMove::new(Square::E2, Square::E4, None),
+            ChessMove::new(Square::E7, Square::E5, None),
+            ChessMove::new(Square::G1, Square::F3, None),
+        ]))
+    );
+    assert_eq!(epd.get("ce"), Some(&EpdValue::Integer(35)));
+    assert_eq!(
+        epd.get("xyz"),
+        Some(&EpdValue::Tokens(vec!["a".to_string(), "b c".to_string()]))
+    );
+
+    let epd = Epd::from_str(records.lines().nth(2).unwrap()).expect("Valid EPD");
+    assert_eq!(epd.id(), Some("STS(v1.0) Undermine.001"));
+    assert_eq!(
+        epd.get("c0"),
+        Some(&EpdValue::String("f5=10, Be5+=2, Bf2=3, Bg4=2".to_string()))
+    );
+}
+
+#[test]
+fn test_epd_move_counters() {
+    let epd = Epd::from_str("4k3/8/8/8/8/8/8/4K3 b - - hmvc 37; fmvn 60;").expect("Valid EPD");
+    assert_eq!(epd.board().halfmove_clock(), 37);
+    assert_eq!(epd.board().fullmove_number(), 60);
+
+    // the counters of a full FEN, which the operations still override
+    let epd = Epd::from_str("4k3/8/8/8/8/8/8/4K3 b - - 12 40 ;D1 5 ;hmvc 3").expect("Valid EPD");
+    assert_eq!(epd.board().halfmove_clock(), 3);
+    assert_eq!(epd.board().fullmove_number(), 40);
+    assert_eq!(epd.get("D1"), Some(&EpdValue::Integer(5)));
+}
+
+#[test]
+fn test_epd_invalid() {
+    let invalid = [
+        "4k3/8/8 w -",
+        "4k3/8/8/8/8/8/8/4K3 w - - bm Qd1;",
+        "4k3/8/8/8/8/8/8/4K3 w - - id \"unterminated;",
+        "4k3/8/8/8/8/8/8/4K3 w - - D1 lots;",
+        "4k3/8/8/8/8/8/8/4K3 w - - acd 1 2;",
+        "4k3/8/8/8/8/8/8/4K3 w - - \"id\" 1;",
+        "4k3/8/8/8/8/8/8/4K3 w - - hmvc -1;",
+    ];
+    for line in invalid.iter() {
+        match Epd::from_str(line) {
+            Err(Error::InvalidEpd { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("Expected {} to be invalid", line),
+        }
+    }
+}
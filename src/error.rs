@@ -13,6 +13,10 @@ pub enum Error {
     )]
     InvalidBoard,
 
+    /// A move was made after the game was over
+    #[fail(display = "The game is over, so no more moves can be made")]
+    GameOver,
+
     /// An attempt was made to create a square from an invalid string
     #[fail(display = "The string specified does not contain a valid algebraic notation square")]
     InvalidSquare,
@@ -32,4 +36,55 @@ pub enum Error {
     /// An attempt was made to convert a string not equal to "a"-"h" to a file
     #[fail(display = "The string specified does not contain a valid file")]
     InvalidFile,
+
+    /// The PGN text could not be parsed
+    #[fail(
+        display = "Invalid PGN at line {}, column {}: {}",
+        line, column, message
+    )]
+    InvalidPgn {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// The Polyglot opening book is not a whole number of entries long
+    #[fail(display = "The Polyglot book is not a whole number of 16 byte entries long")]
+    InvalidPolyglotBook,
+
+    /// Reading or writing failed
+    #[fail(display = "I/O error: {}", message)]
+    Io { message: String },
+
+    /// No Syzygy table was found for the material on the board
+    #[fail(display = "No Syzygy table found for {}", name)]
+    MissingTablebase { name: String },
+
+    /// A Syzygy table file could not be read
+    #[fail(display = "Invalid Syzygy table: {}", name)]
+    InvalidTablebase { name: String },
+
+    /// Syzygy tables do not contain positions where castling is still possible
+    #[fail(display = "Syzygy tables do not contain positions with castling rights")]
+    TablebaseCastling,
+
+    /// A perft test suite could not be parsed
+    #[fail(display = "Invalid perft suite at line {}: {}", line, message)]
+    InvalidPerftSuite { line: usize, message: String },
+
+    /// The EPD text could not be parsed
+    #[fail(display = "Invalid EPD at line {}: {}", line, message)]
+    InvalidEpd { line: usize, message: String },
+
+    /// A UCI command or message could not be parsed
+    #[fail(display = "Invalid UCI: {}", message)]
+    InvalidUci { message: String },
+
+    /// The time control is not in the format of the PGN `TimeControl` tag
+    #[fail(display = "Invalid time control: {}", time_control)]
+    InvalidTimeControl { time_control: String },
+
+    /// The name of a chess variant was not recognised
+    #[fail(display = "Invalid variant: {}", name)]
+    InvalidVariant { name: String },
 }
@@ -4,6 +4,7 @@ use std::str::FromStr;
 /// Describe a file (column) on a chess board
 #[repr(u8)]
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum File {
     A = 0,
     B = 1,
@@ -1,23 +1,40 @@
 use crate::board::{Board, BoardStatus};
+use crate::board_builder::BoardBuilder;
 use crate::chess_move::ChessMove;
-use crate::color::Color;
+use crate::color::{Color, ALL_COLORS};
 use crate::error::Error;
-use crate::movegen::MoveGen;
+use crate::magic::{get_adjacent_files, get_rank};
 use crate::piece::Piece;
+use crate::time_control::{Clock, TimeControl};
+use crate::variant::{Outcome, Variant};
+use crate::variant_board::VariantBoard;
+use crate::zobrist::Zobrist;
+use std::convert::TryFrom;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Contains all actions supported within the game
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     MakeMove(ChessMove),
     OfferDraw(Color),
     AcceptDraw,
+    DeclineDraw,
     DeclareDraw,
+    /// A draw claimed with the move before it.
+    ClaimDraw,
     Resign(Color),
+    /// `Color` ran out of time.
+    Timeout(Color),
+    /// `Color` asked to take back their last move.
+    TakebackRequest(Color),
 }
 
 /// What was the result of this game?
 #[derive(Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GameResult {
     WhiteCheckmates,
     WhiteResigns,
@@ -26,17 +43,167 @@ pub enum GameResult {
     Stalemate,
     DrawAccepted,
     DrawDeclared,
+    /// The same position appeared five times.
+    FivefoldRepetition,
+    /// 75 moves were made by each player without a pawn move or capture.
+    SeventyFiveMoveRule,
+    /// Neither player has enough material left to checkmate.
+    InsufficientMaterial,
+    /// White ran out of time.
+    WhiteLosesOnTime,
+    /// Black ran out of time.
+    BlackLosesOnTime,
+    /// A player ran out of time, but their opponent only had a king left.
+    TimeoutVsInsufficientMaterial,
+    /// A draw was claimed with a move that repeated a position for the third time.
+    ThreefoldRepetition,
+    /// A draw was claimed with a move that made 50 moves by each player without a pawn move or
+    /// capture.
+    FiftyMoveRule,
+    /// White won by a rule of the variant being played, other than checkmate.
+    WhiteVariantWin,
+    /// Black won by a rule of the variant being played, other than checkmate.
+    BlackVariantWin,
 }
 
 /// For UI/UCI Servers, store a game object which allows you to determine
 /// draw by 3 fold repitition, draw offers, resignations, and moves.
 ///
+/// Games of chess variants are played with a `VariantGame` instead.
+///
 /// This structure is slow compared to using `Board` directly, so it is
 /// not recommended for engines.
 #[derive(Clone, Debug)]
 pub struct Game {
-    start_pos: Board,
     moves: Vec<Action>,
+    positions: Vec<Position>,
+    /// How long each action took, for moves made while the clocks were running.
+    elapsed: Vec<Option<Duration>>,
+    timing: Option<Timing>,
+}
+
+/// The clocks of a timed `Game`.
+#[derive(Clone, Debug)]
+struct Timing {
+    control: TimeControl,
+    clock: Arc<dyn Clock>,
+    /// When the side to move started thinking.
+    turn_started: Duration,
+}
+
+/// A position reached in a `Game`, kept so that repetitions can be found without replaying the
+/// game.
+#[derive(Copy, Clone, Debug)]
+struct Position {
+    /// The position, unless the game is of a variant and the position is not one standard chess
+    /// allows, such as any position of Horde.
+    board: Option<Board>,
+    /// The position, in a game of a variant.
+    variant: Option<VariantBoard>,
+    /// `Board::get_hash()`, but without the en passant square if no en passant capture is legal,
+    /// as such positions are the same for the repetition rules.
+    key: u64,
+    /// The number of plies since the last pawn move, capture or loss of castle rights.  None of
+    /// the positions before that can be repeated.
+    reversible: usize,
+}
+
+impl Position {
+    fn new(board: Board, reversible: usize) -> Position {
+        let mut key = board.get_hash();
+        if let Some(ep) = board.en_passant() {
+            let side = board.side_to_move();
+            let mut capturers = get_adjacent_files(ep.get_file())
+                & get_rank(ep.get_rank())
+                & board.pieces(Piece::Pawn)
+                & board.color_combined(side);
+            let dest = ep.uforward(side);
+            if !capturers.any(|src| board.legal(ChessMove::new(src, dest, None))) {
+                key ^= Zobrist::en_passant(ep.get_file(), !side);
+            }
+        }
+        Position {
+            board: Some(board),
+            variant: None,
+            key,
+            reversible,
+        }
+    }
+
+    fn new_variant(variant: VariantBoard, reversible: usize) -> Position {
+        let board = Board::try_from(&BoardBuilder::from(&variant)).ok();
+        let mut key = variant.get_hash();
+        if let Some(ep) = variant.en_passant() {
+            // the square behind the pawn is empty, so only an en passant capture can reach it
+            let side = variant.side_to_move();
+            let dest = ep.uforward(side);
+            let capturable = variant.legal_moves().iter().any(|m| {
+                m.get_dest() == dest && variant.piece_on(m.get_source()) == Some(Piece::Pawn)
+            });
+            if !capturable {
+                key ^= Zobrist::en_passant(ep.get_file(), !side);
+            }
+        }
+        Position {
+            board,
+            variant: Some(variant),
+            key,
+            reversible,
+        }
+    }
+
+    /// The position as a `Board`.  Only a `VariantGame` has positions without one, and it never
+    /// asks for them.
+    fn board(&self) -> Board {
+        self.board
+            .expect("The position of the variant is not a position of standard chess")
+    }
+
+    fn side_to_move(&self) -> Color {
+        match self.variant {
+            Some(variant) => variant.side_to_move(),
+            None => self.board().side_to_move(),
+        }
+    }
+
+    fn halfmove_clock(&self) -> u16 {
+        match self.variant {
+            Some(variant) => variant.halfmove_clock(),
+            None => self.board().halfmove_clock(),
+        }
+    }
+
+    fn legal(&self, m: ChessMove) -> bool {
+        match self.variant {
+            Some(variant) => variant.legal(m),
+            None => self.board().legal(m),
+        }
+    }
+
+    /// The position after `m`.
+    fn after(&self, m: ChessMove) -> Position {
+        if let Some(variant) = self.variant {
+            let after = variant.make_move_new(m);
+            let irreversible = after.halfmove_clock() == 0
+                || ALL_COLORS.iter().any(|c| {
+                    after.castle_rights(*c) != variant.castle_rights(*c)
+                        || after.remaining_checks(*c) != variant.remaining_checks(*c)
+                });
+            return Position::new_variant(
+                after,
+                if irreversible { 0 } else { self.reversible + 1 },
+            );
+        }
+
+        let before = self.board();
+        let board = before.make_move_new(m);
+        let irreversible = before.piece_on(m.get_source()) == Some(Piece::Pawn)
+            || before.piece_on(m.get_dest()).is_some()
+            || ALL_COLORS
+                .iter()
+                .any(|c| board.castle_rights(*c) != before.castle_rights(*c));
+        Position::new(board, if irreversible { 0 } else { self.reversible + 1 })
+    }
 }
 
 impl Game {
@@ -49,10 +216,7 @@ impl Game {
     /// assert_eq!(game.current_position(), Board::default());
     /// ```
     pub fn new() -> Game {
-        Game {
-            start_pos: Board::default(),
-            moves: vec![],
-        }
+        Game::new_with_board(Board::default())
     }
 
     /// Create a new `Game` with a specific starting position.
@@ -65,11 +229,30 @@ impl Game {
     /// ```
     pub fn new_with_board(board: Board) -> Game {
         Game {
-            start_pos: board,
             moves: vec![],
+            positions: vec![Position::new(board, 0)],
+            elapsed: vec![],
+            timing: None,
         }
     }
 
+    /// A game played by the rules of a chess variant, starting from `board`, for `VariantGame`.
+    pub(crate) fn new_variant(board: VariantBoard) -> Game {
+        Game {
+            moves: vec![],
+            positions: vec![Position::new_variant(board, 0)],
+            elapsed: vec![],
+            timing: None,
+        }
+    }
+
+    /// The variant this game is played by.
+    pub(crate) fn variant(&self) -> Variant {
+        self.current()
+            .variant
+            .map_or(Variant::Standard, |board| board.variant())
+    }
+
     /// Get all actions made in this game (moves, draw offers, resignations, etc.)
     ///
     /// ```
@@ -88,33 +271,59 @@ impl Game {
 
     /// What is the status of this game?
     ///
+    /// Besides checkmate, stalemate, resignations and agreed draws, the game is drawn
+    /// automatically by a fivefold repetition, by the 75-move rule, and when neither side can
+    /// checkmate any more.
+    ///
     /// ```
-    /// use chess::Game;
+    /// use chess::{ChessMove, Game, GameResult, Square};
+    /// use std::str::FromStr;
     ///
     /// let game = Game::new();
     /// assert!(game.result().is_none());
+    ///
+    /// let mut game = Game::from_str("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").expect("Valid FEN");
+    /// game.make_move(ChessMove::new(Square::E1, Square::D2, None));
+    /// assert_eq!(game.result(), Some(GameResult::InsufficientMaterial));
     /// ```
     pub fn result(&self) -> Option<GameResult> {
-        match self.current_position().status() {
-            BoardStatus::Checkmate => {
-                if self.side_to_move() == Color::White {
-                    Some(GameResult::BlackCheckmates)
-                } else {
-                    Some(GameResult::WhiteCheckmates)
-                }
-            }
-            BoardStatus::Stalemate => Some(GameResult::Stalemate),
-            BoardStatus::Ongoing => {
-                if self.moves.len() == 0 {
+        match self.position_result() {
+            Some(result) => Some(result),
+            None => {
+                if self.repetitions() >= 5 {
+                    Some(GameResult::FivefoldRepetition)
+                } else if self.current().halfmove_clock() >= 150 {
+                    Some(GameResult::SeventyFiveMoveRule)
+                } else if self.moves.len() == 0 {
                     None
                 } else if self.moves[self.moves.len() - 1] == Action::AcceptDraw {
                     Some(GameResult::DrawAccepted)
                 } else if self.moves[self.moves.len() - 1] == Action::DeclareDraw {
                     Some(GameResult::DrawDeclared)
+                } else if self.moves[self.moves.len() - 1] == Action::ClaimDraw {
+                    if self.repetitions() >= 3 {
+                        Some(GameResult::ThreefoldRepetition)
+                    } else {
+                        Some(GameResult::FiftyMoveRule)
+                    }
                 } else if self.moves[self.moves.len() - 1] == Action::Resign(Color::White) {
                     Some(GameResult::WhiteResigns)
                 } else if self.moves[self.moves.len() - 1] == Action::Resign(Color::Black) {
                     Some(GameResult::BlackResigns)
+                } else if let Action::Timeout(color) = self.moves[self.moves.len() - 1] {
+                    let lone_king = match self.current().board {
+                        Some(board) if self.current().variant.is_none() => {
+                            board.color_combined(!color).popcnt() == 1
+                        }
+                        _ => false,
+                    };
+                    if lone_king {
+                        Some(GameResult::TimeoutVsInsufficientMaterial)
+                    } else if color == Color::White {
+                        Some(GameResult::WhiteLosesOnTime)
+                    } else {
+                        Some(GameResult::BlackLosesOnTime)
+                    }
                 } else {
                     None
                 }
@@ -122,6 +331,44 @@ impl Game {
         }
     }
 
+    /// The result the current position decides on its own: checkmate, stalemate, insufficient
+    /// material, or the ways a variant ends.
+    fn position_result(&self) -> Option<GameResult> {
+        let checkmated = |color: Color| {
+            if color == Color::White {
+                GameResult::BlackCheckmates
+            } else {
+                GameResult::WhiteCheckmates
+            }
+        };
+
+        let variant = match self.current().variant {
+            Some(variant) => variant,
+            None => {
+                let board = self.current_position();
+                return match board.status() {
+                    BoardStatus::Checkmate => Some(checkmated(board.side_to_move())),
+                    BoardStatus::Stalemate => Some(GameResult::Stalemate),
+                    BoardStatus::Ongoing if board.has_insufficient_material() => {
+                        Some(GameResult::InsufficientMaterial)
+                    }
+                    BoardStatus::Ongoing => None,
+                };
+            }
+        };
+
+        match variant.outcome()? {
+            Outcome::Draw => Some(GameResult::Stalemate),
+            Outcome::Winner(color)
+                if variant.variant_end().is_none() && variant.variant() != Variant::Antichess =>
+            {
+                Some(checkmated(!color))
+            }
+            Outcome::Winner(Color::White) => Some(GameResult::WhiteVariantWin),
+            Outcome::Winner(Color::Black) => Some(GameResult::BlackVariantWin),
+        }
+    }
+
     /// Create a new `Game` object from an FEN string.
     ///
     /// ```
@@ -147,6 +394,26 @@ impl Game {
         Game::from_str(fen).ok()
     }
 
+    /// Get the position this `Game` started from.
+    ///
+    /// ```
+    /// use chess::{Game, Board, MoveGen};
+    ///
+    /// let mut game = Game::new();
+    /// let mut movegen = MoveGen::new_legal(&game.current_position());
+    ///
+    /// game.make_move(movegen.next().expect("At least one legal move"));
+    /// assert_eq!(game.start_position(), Board::default());
+    /// ```
+    pub fn start_position(&self) -> Board {
+        self.positions[0].board()
+    }
+
+    /// The position a game of a variant started from, or `None` for a game of standard chess.
+    pub(crate) fn start_variant_position(&self) -> Option<VariantBoard> {
+        self.positions[0].variant
+    }
+
     /// Get the current position on the board from the `Game` object.
     ///
     /// ```
@@ -156,18 +423,46 @@ impl Game {
     /// assert_eq!(game.current_position(), Board::default());
     /// ```
     pub fn current_position(&self) -> Board {
-        let mut copy = self.start_pos;
+        self.current().board()
+    }
 
-        for x in self.moves.iter() {
-            match *x {
-                Action::MakeMove(m) => {
-                    copy = copy.make_move_new(m);
-                }
-                _ => {}
-            }
-        }
+    /// The current position of a game of a variant, or `None` for a game of standard chess.
+    pub(crate) fn current_variant_position(&self) -> Option<VariantBoard> {
+        self.current().variant
+    }
 
-        copy
+    fn current(&self) -> &Position {
+        self.positions
+            .last()
+            .expect("The start position is always there")
+    }
+
+    /// How many times has the current position appeared in this game, counting this time?
+    ///
+    /// Positions are the same if the same pieces are on the same squares, with the same side to
+    /// move, castle rights and legal en passant captures.
+    ///
+    /// ```
+    /// use chess::{Game, Square, ChessMove};
+    ///
+    /// let mut game = Game::new();
+    /// assert_eq!(game.repetitions(), 1);
+    ///
+    /// game.make_move(ChessMove::new(Square::G1, Square::F3, None));
+    /// game.make_move(ChessMove::new(Square::G8, Square::F6, None));
+    /// game.make_move(ChessMove::new(Square::F3, Square::G1, None));
+    /// game.make_move(ChessMove::new(Square::F6, Square::G8, None));
+    /// assert_eq!(game.repetitions(), 2);
+    /// ```
+    pub fn repetitions(&self) -> usize {
+        let current = self.current();
+        self.positions
+            .iter()
+            .rev()
+            .take(current.reversible + 1)
+            .step_by(2)
+            .filter(|p| p.key == current.key)
+            .count()
     }
 
     /// Determine if a player can legally declare a draw by 3-fold repetition or 50-move rule.
@@ -202,59 +497,7 @@ impl Game {
             return false;
         }
 
-        let mut legal_moves_per_turn: Vec<(u64, Vec<ChessMove>)> = vec![];
-
-        let mut board = self.start_pos;
-        let mut reversible_moves = 0;
-
-        // Loop over each move, counting the reversible_moves for draw by 50 move rule,
-        // and filling a list of legal_moves_per_turn list for 3-fold repitition
-        legal_moves_per_turn.push((board.get_hash(), MoveGen::new_legal(&board).collect()));
-        for x in self.moves.iter() {
-            match *x {
-                Action::MakeMove(m) => {
-                    let white_castle_rights = board.castle_rights(Color::White);
-                    let black_castle_rights = board.castle_rights(Color::Black);
-                    if board.piece_on(m.get_source()) == Some(Piece::Pawn) {
-                        reversible_moves = 0;
-                        legal_moves_per_turn.clear();
-                    } else if board.piece_on(m.get_dest()).is_some() {
-                        reversible_moves = 0;
-                        legal_moves_per_turn.clear();
-                    } else {
-                        reversible_moves += 1;
-                    }
-                    board = board.make_move_new(m);
-
-                    if board.castle_rights(Color::White) != white_castle_rights
-                        || board.castle_rights(Color::Black) != black_castle_rights
-                    {
-                        reversible_moves = 0;
-                        legal_moves_per_turn.clear();
-                    }
-                    legal_moves_per_turn
-                        .push((board.get_hash(), MoveGen::new_legal(&board).collect()));
-                }
-                _ => {}
-            }
-        }
-
-        if reversible_moves >= 100 {
-            return true;
-        }
-
-        // Detect possible draw by 3 fold repitition
-        let last_moves = legal_moves_per_turn[legal_moves_per_turn.len() - 1].clone();
-
-        for i in 1..(legal_moves_per_turn.len() - 1) {
-            for j in 0..i {
-                if legal_moves_per_turn[i] == last_moves && legal_moves_per_turn[j] == last_moves {
-                    return true;
-                }
-            }
-        }
-
-        return false;
+        self.current().halfmove_clock() >= 100 || self.repetitions() >= 3
     }
 
     /// Declare a draw by 3-fold repitition or 50-move rule.
@@ -287,15 +530,58 @@ impl Game {
     /// ```
     pub fn declare_draw(&mut self) -> bool {
         if self.can_declare_draw() {
-            self.moves.push(Action::DeclareDraw);
+            self.push(Action::DeclareDraw, None);
             true
         } else {
             false
         }
     }
 
+    /// Claim a draw by 3-fold repetition or 50-move rule that `chess_move` would bring about, as
+    /// FIDE allows by writing the move down and claiming before playing it.
+    ///
+    /// If the claim is right, `chess_move` is made and the game is drawn, with a result naming the
+    /// rule.  If it is wrong, `chess_move` still has to be played, and the game goes on.  Returns
+    /// true if the draw was claimed, and false if it was not, or `chess_move` is illegal.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Game, GameResult, Square};
+    ///
+    /// let b1c3 = ChessMove::new(Square::B1, Square::C3, None);
+    /// let c3b1 = ChessMove::new(Square::C3, Square::B1, None);
+    /// let b8c6 = ChessMove::new(Square::B8, Square::C6, None);
+    /// let c6b8 = ChessMove::new(Square::C6, Square::B8, None);
+    ///
+    /// let mut game = Game::new();
+    /// for m in [b1c3, b8c6, c3b1, c6b8, b1c3, b8c6, c3b1].iter() {
+    ///     game.make_move(*m);
+    /// }
+    ///
+    /// // the draw can only be claimed with the move that repeats the position
+    /// assert!(!game.can_declare_draw());
+    /// assert!(game.claim_draw_with_move(c6b8));
+    /// assert_eq!(game.result(), Some(GameResult::ThreefoldRepetition));
+    /// ```
+    pub fn claim_draw_with_move(&mut self, chess_move: ChessMove) -> bool {
+        self.make_move(chess_move) && self.claim_draw()
+    }
+
+    /// Record a draw claimed with the last move.
+    pub(crate) fn claim_draw(&mut self) -> bool {
+        if let Some(Action::MakeMove(_)) = self.moves.last() {
+            if self.can_declare_draw() {
+                self.push(Action::ClaimDraw, None);
+                return true;
+            }
+        }
+        false
+    }
+
     /// Make a chess move on the board
     ///
+    /// In a timed game, the time the move took is recorded, and a player who ran out of time
+    /// loses on time (see `check_timeout()`) instead of making the move.
+    ///
     /// ```
     /// use chess::{Game, MoveGen};
     ///
@@ -309,8 +595,19 @@ impl Game {
         if self.result().is_some() {
             return false;
         }
-        if self.current_position().legal(chess_move) {
-            self.moves.push(Action::MakeMove(chess_move));
+        if self.check_timeout() {
+            return false;
+        }
+        if self.current().legal(chess_move) {
+            let elapsed = self.timing.as_mut().map(|timing| {
+                let now = timing.clock.now();
+                let elapsed = now.checked_sub(timing.turn_started).unwrap_or_default();
+                timing.turn_started = now;
+                elapsed
+            });
+            self.push(Action::MakeMove(chess_move), elapsed);
+            let position = self.current().after(chess_move);
+            self.positions.push(position);
             true
         } else {
             false
@@ -326,29 +623,11 @@ impl Game {
     /// assert_eq!(game.side_to_move(), Color::White);
     /// ```
     pub fn side_to_move(&self) -> Color {
-        let move_count = self
-            .moves
-            .iter()
-            .filter(|m| match *m {
-                Action::MakeMove(_) => true,
-                _ => false,
-            })
-            .count()
-            + if self.start_pos.side_to_move() == Color::White {
-                0
-            } else {
-                1
-            };
-
-        if move_count % 2 == 0 {
-            Color::White
-        } else {
-            Color::Black
-        }
+        self.current().side_to_move()
     }
 
     /// Offer a draw to my opponent.  `color` is the player who offered the draw.  The draw must be
-    /// accepted before my opponent moves.
+    /// accepted before my opponent moves, or the offer expires.
     ///
     /// ```
     /// use chess::{Game, Color};
@@ -360,7 +639,7 @@ impl Game {
         if self.result().is_some() {
             return false;
         }
-        self.moves.push(Action::OfferDraw(color));
+        self.push(Action::OfferDraw(color), None);
         return true;
     }
 
@@ -380,26 +659,67 @@ impl Game {
     /// assert_eq!(game2.accept_draw(), false);
     /// ```
     pub fn accept_draw(&mut self) -> bool {
-        if self.result().is_some() {
+        if self.result().is_some() || self.draw_offer().is_none() {
             return false;
         }
-        if self.moves.len() > 0 {
-            if self.moves[self.moves.len() - 1] == Action::OfferDraw(Color::White)
-                || self.moves[self.moves.len() - 1] == Action::OfferDraw(Color::Black)
-            {
-                self.moves.push(Action::AcceptDraw);
-                return true;
-            }
+        self.push(Action::AcceptDraw, None);
+        true
+    }
+
+    /// Decline a draw offer from my opponent.
+    ///
+    /// ```
+    /// use chess::{Game, Color};
+    ///
+    /// let mut game = Game::new();
+    /// assert_eq!(game.decline_draw(), false);
+    /// game.offer_draw(Color::Black);
+    /// assert_eq!(game.decline_draw(), true);
+    /// assert_eq!(game.accept_draw(), false);
+    /// ```
+    pub fn decline_draw(&mut self) -> bool {
+        if self.result().is_some() || self.draw_offer().is_none() {
+            return false;
         }
+        self.push(Action::DeclineDraw, None);
+        true
+    }
 
-        if self.moves.len() > 1 {
-            if self.moves[self.moves.len() - 2] == Action::OfferDraw(!self.side_to_move()) {
-                self.moves.push(Action::AcceptDraw);
-                return true;
+    /// Who has a draw offer open?  An offer is open until it is accepted or declined, or the
+    /// opponent of the player who offered it makes a move.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, Square};
+    ///
+    /// let mut game = Game::new();
+    /// game.offer_draw(Color::White);
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    /// assert_eq!(game.draw_offer(), Some(Color::White));
+    ///
+    /// game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+    /// assert_eq!(game.draw_offer(), None);
+    /// ```
+    pub fn draw_offer(&self) -> Option<Color> {
+        let mut movers = self.positions.iter().rev().skip(1);
+        let mut opponent_moved = [false; 2];
+        for action in self.moves.iter().rev() {
+            match action {
+                Action::MakeMove(_) => {
+                    let mover = movers.next().expect("A position before each move");
+                    opponent_moved[mover.side_to_move().to_index()] = true;
+                }
+                Action::OfferDraw(color) => {
+                    return if opponent_moved[(!*color).to_index()] {
+                        None
+                    } else {
+                        Some(*color)
+                    };
+                }
+                Action::AcceptDraw | Action::DeclineDraw => return None,
+                _ => {}
             }
         }
-
-        false
+        None
     }
 
     /// `color` resigns the game
@@ -414,9 +734,270 @@ impl Game {
         if self.result().is_some() {
             return false;
         }
-        self.moves.push(Action::Resign(color));
+        self.push(Action::Resign(color), None);
         return true;
     }
+
+    /// `color` ran out of time.  This is for games whose clocks are kept somewhere else; timed
+    /// games notice on their own with `check_timeout()`.
+    ///
+    /// If the opponent of `color` only has a king left, the game is drawn, and otherwise `color`
+    /// loses on time.
+    ///
+    /// ```
+    /// use chess::{Color, Game, GameResult};
+    ///
+    /// let mut game = Game::new();
+    /// assert!(game.timeout(Color::White));
+    /// assert_eq!(game.result(), Some(GameResult::WhiteLosesOnTime));
+    /// ```
+    pub fn timeout(&mut self, color: Color) -> bool {
+        if self.result().is_some() {
+            return false;
+        }
+        self.push(Action::Timeout(color), None);
+        true
+    }
+
+    /// Run the game with `control`, using the time from `clock`.  The clock of the side to move
+    /// starts now.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, GameResult, ManualClock, Square, TimeControl};
+    /// use std::str::FromStr;
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut game = Game::new();
+    /// game.set_time_control(TimeControl::from_str("60+1").expect("Valid"), clock.clone());
+    ///
+    /// clock.advance(Duration::from_secs(10));
+    /// assert!(game.make_move(ChessMove::new(Square::E2, Square::E4, None)));
+    /// assert_eq!(game.remaining_time(Color::White), Some(Duration::from_secs(51)));
+    /// assert_eq!(game.elapsed_times(), &[Some(Duration::from_secs(10))]);
+    ///
+    /// // black thinks for too long
+    /// clock.advance(Duration::from_secs(61));
+    /// assert!(game.check_timeout());
+    /// assert_eq!(game.result(), Some(GameResult::BlackLosesOnTime));
+    /// ```
+    pub fn set_time_control<C: Clock + 'static>(&mut self, control: TimeControl, clock: C) {
+        let turn_started = clock.now();
+        self.timing = Some(Timing {
+            control,
+            clock: Arc::new(clock),
+            turn_started,
+        });
+    }
+
+    /// The time control of this game, if it is timed.
+    pub fn time_control(&self) -> Option<&TimeControl> {
+        self.timing.as_ref().map(|timing| &timing.control)
+    }
+
+    /// How long each action took, in the same order as `actions()`.  This is only recorded for
+    /// moves made while the game was timed.
+    pub fn elapsed_times(&self) -> &[Option<Duration>] {
+        &self.elapsed
+    }
+
+    /// The time `color` has left, or `None` if the game is not timed.  The clock of the side to
+    /// move counts down until the move is made, and reads zero once it has run out.
+    pub fn remaining_time(&self, color: Color) -> Option<Duration> {
+        let timing = self.timing.as_ref()?;
+        Some(self.clock_remaining(timing, color).unwrap_or_default())
+    }
+
+    /// If the side to move has run out of time, record a timeout (see `timeout()`) and return
+    /// true.
+    pub fn check_timeout(&mut self) -> bool {
+        let color = self.side_to_move();
+        let flagged = match self.timing {
+            Some(ref timing) => self.clock_remaining(timing, color).is_none(),
+            None => false,
+        };
+        flagged && self.timeout(color)
+    }
+
+    /// The time left on the clock of `color`, or `None` if it has run out.
+    fn clock_remaining(&self, timing: &Timing, color: Color) -> Option<Duration> {
+        if self.moves.last() == Some(&Action::Timeout(color)) {
+            return None;
+        }
+        let movers = self.positions.iter().map(|p| p.side_to_move());
+        let elapsed = self
+            .moves
+            .iter()
+            .zip(self.elapsed.iter())
+            .filter(|(action, _)| matches!(action, Action::MakeMove(_)))
+            .zip(movers)
+            .filter(|(_, mover)| *mover == color)
+            .filter_map(|((_, elapsed), _)| *elapsed)
+            .collect::<Vec<_>>();
+
+        let thinking = if color == self.side_to_move() && self.result().is_none() {
+            timing
+                .clock
+                .now()
+                .checked_sub(timing.turn_started)
+                .unwrap_or_default()
+        } else {
+            Duration::from_secs(0)
+        };
+        timing.control.remaining(&elapsed, thinking)
+    }
+
+    /// How many moves have been made in this game.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, Square};
+    ///
+    /// let mut game = Game::new();
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    /// game.offer_draw(Color::Black);
+    /// assert_eq!(game.ply(), 1);
+    /// ```
+    pub fn ply(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    /// Go back to the position after `ply` moves, removing every action after that move.
+    /// Returns false if fewer than `ply` moves were made.
+    ///
+    /// The game is left as it was when the move was made: a result from later on (such as a
+    /// resignation or checkmate) is gone, and a draw offer made later is withdrawn, but one that
+    /// was open when the move was made is open again.  In a timed game, the time spent on the
+    /// removed moves is given back, and the clock of the side to move starts again.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, Square};
+    ///
+    /// let mut game = Game::new();
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    /// game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+    /// game.resign(Color::White);
+    ///
+    /// assert!(game.truncate(1));
+    /// assert_eq!(game.actions().len(), 1);
+    /// assert_eq!(game.result(), None);
+    /// assert_eq!(game.side_to_move(), Color::Black);
+    /// assert!(!game.truncate(2));
+    /// ```
+    pub fn truncate(&mut self, ply: usize) -> bool {
+        if ply > self.ply() {
+            return false;
+        }
+
+        let mut made = 0;
+        let end = self
+            .moves
+            .iter()
+            .take_while(|action| {
+                let before = made < ply;
+                if let Action::MakeMove(_) = action {
+                    made += 1;
+                }
+                before
+            })
+            .count();
+        self.moves.truncate(end);
+        self.elapsed.truncate(end);
+        self.positions.truncate(ply + 1);
+        if let Some(timing) = self.timing.as_mut() {
+            timing.turn_started = timing.clock.now();
+        }
+        true
+    }
+
+    /// Take back the last move, and anything that happened after it (see `truncate()`).
+    /// Returns the move, or `None` if no moves were made.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Game, Square};
+    ///
+    /// let e4 = ChessMove::new(Square::E2, Square::E4, None);
+    ///
+    /// let mut game = Game::new();
+    /// game.make_move(e4);
+    /// assert_eq!(game.undo(), Some(e4));
+    /// assert_eq!(game.undo(), None);
+    /// assert!(game.actions().is_empty());
+    /// ```
+    pub fn undo(&mut self) -> Option<ChessMove> {
+        let ply = self.ply().checked_sub(1)?;
+        let last = self.moves.iter().rev().find_map(|action| match action {
+            Action::MakeMove(m) => Some(*m),
+            _ => None,
+        });
+        self.truncate(ply);
+        last
+    }
+
+    /// `color` asks to take back their last move.  The request must be accepted before the next
+    /// move.  Returns false if the game is over, or `color` has not made a move.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, Square};
+    ///
+    /// let mut game = Game::new();
+    /// assert!(!game.takeback_request(Color::White));
+    ///
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    /// assert!(game.takeback_request(Color::White));
+    /// ```
+    pub fn takeback_request(&mut self, color: Color) -> bool {
+        if self.result().is_some() || self.last_move_by(color).is_none() {
+            return false;
+        }
+        self.push(Action::TakebackRequest(color), None);
+        true
+    }
+
+    /// Accept a take back request from my opponent, going back to before their last move (see
+    /// `truncate()`).  If I have replied to that move already, my reply is taken back too.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Color, Game, Square};
+    ///
+    /// let mut game = Game::new();
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    /// game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+    /// game.takeback_request(Color::White);
+    ///
+    /// assert!(game.accept_takeback());
+    /// assert_eq!(game.ply(), 0);
+    /// assert!(!game.accept_takeback());
+    /// ```
+    pub fn accept_takeback(&mut self) -> bool {
+        if self.result().is_some() {
+            return false;
+        }
+        let color = self
+            .moves
+            .iter()
+            .rev()
+            .take_while(|action| !matches!(action, Action::MakeMove(_)))
+            .find_map(|action| match action {
+                Action::TakebackRequest(color) => Some(*color),
+                _ => None,
+            });
+        match color.and_then(|color| self.last_move_by(color)) {
+            Some(ply) => self.truncate(ply),
+            None => false,
+        }
+    }
+
+    /// The number of moves made before the last move by `color`.
+    fn last_move_by(&self, color: Color) -> Option<usize> {
+        self.positions[..self.ply()]
+            .iter()
+            .rposition(|p| p.side_to_move() == color)
+    }
+
+    fn push(&mut self, action: Action, elapsed: Option<Duration>) {
+        self.moves.push(action);
+        self.elapsed.push(elapsed);
+    }
 }
 
 impl FromStr for Game {
@@ -475,3 +1056,356 @@ pub fn test_can_declare_draw() {
     let game = fake_pgn_parser("1. d4 Nf6 2. c4 g6 3. Nc3 Bg7 4. e4 d6 5. Nf3 O-O 6. Be2 e5 7. O-O Nc6 8. d5 Ne7 9. Nd2 a5 10. Rb1 Nd7 11. a3 f5 12. b4 Kh8 13. f3 Ng8 14. Qc2 Ngf6 15. Nb5 axb4 16. axb4 Nh5 17. g3 Ndf6 18. c5 Bd7 19. Rb3 Nxg3 20. hxg3 Nh5 21. f4 exf4 22. c6 bxc6 23. dxc6 Nxg3 24. Rxg3 fxg3 25. cxd7 g2 26. Rf3 Qxd7 27. Bb2 fxe4 28. Rxf8+ Rxf8 29. Bxg7+ Qxg7 30. Qxe4 Qf6 31. Nf3 Qf4 32. Qe7 Rf7 33. Qe6 Rf6 34. Qe8+ Rf8 35. Qe7 Rf7 36. Qe6 Rf6 37. Qb3 g5 38. Nxc7 g4 39. Nd5 Qc1+ 40. Qd1 Qxd1+ 41. Bxd1 Rf5 42. Ne3 Rf4 43. Ne1 Rxb4 44. Bxg4 h5 45. Bf3 d5 46. N3xg2 h4 47. Nd3 Ra4 48. Ngf4 Kg7 49. Kg2 Kf6 50. Bxd5 Ra5 51. Bc6 Ra6 52. Bb7 Ra3 53. Be4 Ra4 54. Bd5 Ra5 55. Bc6 Ra6 56. Bf3 Kg5 57. Bb7 Ra1 58. Bc8 Ra4 59. Kf3 Rc4 60. Bd7 Kf6 61. Kg4 Rd4 62. Bc6 Rd8 63. Kxh4 Rg8 64. Be4 Rg1 65. Nh5+ Ke6 66. Ng3 Kf6 67. Kg4 Ra1 68. Bd5 Ra5 69. Bf3 Ra1 70. Kf4 Ke6 71. Nc5+ Kd6 72. Nge4+ Ke7 73. Ke5 Rf1 74. Bg4 Rg1 75. Be6 Re1 76. Bc8 Rc1 77. Kd4 Rd1+ 78. Nd3 Kf7 79. Ke3 Ra1 80. Kf4 Ke7 81. Nb4 Rc1 82. Nd5+ Kf7 83. Bd7 Rf1+ 84. Ke5 Ra1 85. Ng5+ Kg6 86. Nf3 Kg7 87. Bg4 Kg6 88. Nf4+ Kg7 89. Nd4 Re1+ 90. Kf5 Rc1 91. Be2 Re1 92. Bh5 Ra1 93. Nfe6+ Kh6 94. Be8 Ra8 95. Bc6 Ra1 96. Kf6 Kh7 97. Ng5+ Kh8 98. Nde6 Ra6 99. Be8 Ra8 100. Bh5 Ra1 101. Bg6 Rf1+ 102. Ke7 Ra1 103. Nf7+ Kg8 104. Nh6+ Kh8 105. Nf5 Ra7+ 106. Kf6 Ra1 107. Ne3 Re1 108. Nd5 Rg1 109. Bf5 Rf1 110. Ndf4 Ra1 111. Ng6+ Kg8 112. Ne7+ Kh8");
     assert!(!game.can_declare_draw());
 }
+
+#[cfg(test)]
+use crate::square::Square;
+
+#[cfg(test)]
+fn play_uci(fen: &str, moves: &str) -> Game {
+    moves
+        .split_whitespace()
+        .fold(Game::from_str(fen).expect("Valid FEN"), |mut g, m| {
+            assert!(
+                g.make_move(ChessMove::from_str(m).expect("Valid UCI move")),
+                "{}",
+                m
+            );
+            g
+        })
+}
+
+#[test]
+fn test_repetition_en_passant() {
+    // after c7c5 the en passant square is set, but bxc6 would leave the white king in check, so
+    // the position repeats as soon as the knights return
+    let game = play_uci("1n2k3/2p5/8/KP5r/8/8/8/1N6 b - - 0 1", "c7c5");
+    assert!(game.current_position().en_passant().is_some());
+    let game = play_uci(
+        "1n2k3/2p5/8/KP5r/8/8/8/1N6 b - - 0 1",
+        "c7c5 b1c3 b8a6 c3b1 a6b8 b1c3 b8a6 c3b1 a6b8",
+    );
+    assert_eq!(game.repetitions(), 3);
+    assert!(game.can_declare_draw());
+
+    // here exd6 is legal, so the position after d7d5 is never repeated
+    let game = play_uci(
+        "4k1n1/3p4/8/4P3/8/8/8/4K1N1 b - - 0 1",
+        "d7d5 g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8",
+    );
+    assert_eq!(game.repetitions(), 2);
+    assert!(!game.can_declare_draw());
+    let game = play_uci(
+        "4k1n1/3p4/8/4P3/8/8/8/4K1N1 b - - 0 1",
+        "d7d5 g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8",
+    );
+    assert!(game.can_declare_draw());
+}
+
+#[test]
+fn test_repetition_castle_rights() {
+    // the rook move loses the castle rights, so the positions before it can not be repeated
+    let game = play_uci(
+        "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+        "h1h2 e8d8 h2h1 d8e8 e1f1 e8d8 f1e1 d8e8 e1f1 e8d8 f1e1 d8e8",
+    );
+    assert_eq!(game.repetitions(), 3);
+    assert_eq!(game.actions().len(), 12);
+    let game = play_uci("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "h1h2 e8d8 h2h1 d8e8");
+    assert_eq!(game.repetitions(), 1);
+}
+
+#[test]
+fn test_fifty_moves_from_fen() {
+    let game = play_uci("4k3/8/8/8/8/8/8/4K2R w - - 98 80", "h1h2");
+    assert!(!game.can_declare_draw());
+    let game = play_uci("4k3/8/8/8/8/8/8/4K2R w - - 98 80", "h1h2 e8d8");
+    assert!(game.can_declare_draw());
+}
+
+#[test]
+fn test_automatic_draws() {
+    let shuffle = "g1f3 g8f6 f3g1 f6g8";
+    let game = play_uci(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[shuffle; 3].join(" "),
+    );
+    assert_eq!(game.repetitions(), 4);
+    assert_eq!(game.result(), None);
+    let mut game = play_uci(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[shuffle; 4].join(" "),
+    );
+    assert_eq!(game.repetitions(), 5);
+    assert_eq!(game.result(), Some(GameResult::FivefoldRepetition));
+    assert!(!game.make_move(ChessMove::new(Square::E2, Square::E4, None)));
+    assert!(!game.can_declare_draw());
+
+    let game = play_uci("4k3/8/8/8/8/8/8/R3K3 w - - 148 100", "a1a2");
+    assert_eq!(game.result(), None);
+    let game = play_uci("4k3/8/8/8/8/8/8/R3K3 w - - 148 100", "a1a2 e8d8");
+    assert_eq!(game.result(), Some(GameResult::SeventyFiveMoveRule));
+
+    // checkmate on the 75th move still counts
+    let game = play_uci("3k4/8/3K4/8/8/8/8/R7 w - - 149 100", "a1a8");
+    assert_eq!(game.result(), Some(GameResult::WhiteCheckmates));
+}
+
+#[test]
+fn test_insufficient_material() {
+    let cases = [
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/8/4KB2 w - - 0 1", true),
+        ("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1", false),
+        ("4k1b1/8/8/8/8/8/8/4KB2 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/B1B5/4K3 w - - 0 1", true),
+        ("4k3/8/8/8/8/8/BB6/4K3 w - - 0 1", false),
+        ("4kn2/8/8/8/8/8/8/4KB2 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/P7/4K3 w - - 0 1", false),
+        ("4k3/8/8/8/8/8/8/4K2R w - - 0 1", false),
+        ("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", false),
+    ];
+    for (fen, insufficient) in cases.iter() {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        assert_eq!(board.has_insufficient_material(), *insufficient, "{}", fen);
+    }
+}
+
+#[cfg(test)]
+use crate::time_control::{ManualClock, TimePeriod};
+
+#[test]
+fn test_clock_periods() {
+    let secs = Duration::from_secs;
+    let clock = ManualClock::new();
+    let mut game = Game::new();
+    assert_eq!(game.remaining_time(Color::White), None);
+    game.set_time_control(TimeControl::from_str("2/60:30+1").unwrap(), clock.clone());
+
+    for (m, seconds) in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4"].iter().zip(1..) {
+        clock.advance(secs(seconds));
+        assert!(game.make_move(ChessMove::from_str(m).unwrap()));
+    }
+    // white: 60 - 1 - 3 + 30 - 5 + 1, black: 60 - 2 - 4 + 30
+    assert_eq!(game.remaining_time(Color::White), Some(secs(82)));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(84)));
+    assert_eq!(
+        game.elapsed_times(),
+        &[
+            Some(secs(1)),
+            Some(secs(2)),
+            Some(secs(3)),
+            Some(secs(4)),
+            Some(secs(5))
+        ]
+    );
+
+    // only the clock of the side to move runs
+    clock.advance(secs(80));
+    assert_eq!(game.remaining_time(Color::White), Some(secs(82)));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(4)));
+    assert!(!game.check_timeout());
+
+    game.offer_draw(Color::Black);
+    clock.advance(secs(5));
+    assert!(!game.make_move(ChessMove::from_str("g8f6").unwrap()));
+    assert_eq!(game.result(), Some(GameResult::BlackLosesOnTime));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(0)));
+    assert_eq!(game.actions().last(), Some(&Action::Timeout(Color::Black)));
+    assert_eq!(game.elapsed_times().len(), game.actions().len());
+}
+
+#[test]
+fn test_clock_delay() {
+    let secs = Duration::from_secs;
+    let clock = ManualClock::new();
+    let mut game = Game::new();
+    game.set_time_control(
+        TimeControl::new(*TimePeriod::new(secs(10)).delay(secs(5))),
+        clock.clone(),
+    );
+
+    clock.advance(secs(4));
+    assert!(game.make_move(ChessMove::from_str("e2e4").unwrap()));
+    assert_eq!(game.remaining_time(Color::White), Some(secs(10)));
+    clock.advance(secs(15));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(0)));
+    assert!(!game.check_timeout());
+    clock.advance(secs(1));
+    assert!(game.check_timeout());
+    assert!(!game.check_timeout());
+}
+
+#[test]
+fn test_timeout_vs_insufficient_material() {
+    let mut game = Game::from_str("4k3/8/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+    assert!(game.timeout(Color::White));
+    assert_eq!(
+        game.result(),
+        Some(GameResult::TimeoutVsInsufficientMaterial)
+    );
+
+    let mut game = Game::from_str("4k3/8/8/8/8/8/P7/4K3 b - - 0 1").unwrap();
+    assert!(game.timeout(Color::Black));
+    assert_eq!(game.result(), Some(GameResult::BlackLosesOnTime));
+    assert!(!game.timeout(Color::White));
+}
+
+#[test]
+fn test_undo_restores_result_and_repetitions() {
+    let mut game = play_uci(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "f2f3 e7e5 g2g4 d8h4",
+    );
+    assert_eq!(game.result(), Some(GameResult::BlackCheckmates));
+    assert_eq!(game.undo(), Some(ChessMove::from_str("d8h4").unwrap()));
+    assert_eq!(game.result(), None);
+    assert_eq!(game.side_to_move(), Color::Black);
+
+    let mut game = play_uci(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8",
+    );
+    assert!(game.can_declare_draw());
+    assert!(game.declare_draw());
+    assert_eq!(game.undo(), Some(ChessMove::from_str("f6g8").unwrap()));
+    assert_eq!(game.repetitions(), 2);
+    assert!(!game.can_declare_draw());
+    assert_eq!(game.result(), None);
+    assert!(game.make_move(ChessMove::from_str("f6g8").unwrap()));
+    assert_eq!(game.repetitions(), 3);
+
+    assert!(game.truncate(0));
+    assert!(game.actions().is_empty());
+    assert_eq!(game.current_position(), Board::default());
+}
+
+#[test]
+fn test_truncate_draw_offers() {
+    let mut game = Game::new();
+    game.offer_draw(Color::White);
+    game.make_move(ChessMove::from_str("e2e4").unwrap());
+    game.make_move(ChessMove::from_str("e7e5").unwrap());
+    game.offer_draw(Color::Black);
+    assert!(game.truncate(1));
+
+    // black's offer came later, and is gone, but white's offer with 1. e4 is open again
+    assert_eq!(game.actions().len(), 2);
+    assert!(game.accept_draw());
+    assert_eq!(game.result(), Some(GameResult::DrawAccepted));
+    assert!(game.truncate(1));
+    assert_eq!(game.result(), None);
+}
+
+#[test]
+fn test_takeback() {
+    let mut game = play_uci(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "e2e4 e7e5 g1f3",
+    );
+    assert!(!game.accept_takeback());
+
+    // white takes back the last move
+    assert!(game.takeback_request(Color::White));
+    assert!(game.accept_takeback());
+    assert_eq!(game.ply(), 2);
+    assert_eq!(game.actions().len(), 2);
+
+    // black has to take back white's reply too
+    game.make_move(ChessMove::from_str("g1f3").unwrap());
+    assert!(game.takeback_request(Color::Black));
+    assert!(game.accept_takeback());
+    assert_eq!(game.ply(), 1);
+    assert_eq!(game.side_to_move(), Color::Black);
+
+    // a request lapses once a move is made
+    assert!(game.takeback_request(Color::White));
+    game.make_move(ChessMove::from_str("c7c5").unwrap());
+    assert!(!game.accept_takeback());
+
+    game.resign(Color::Black);
+    assert!(!game.takeback_request(Color::White));
+    assert!(!game.accept_takeback());
+}
+
+#[test]
+fn test_undo_refunds_time() {
+    let secs = Duration::from_secs;
+    let clock = ManualClock::new();
+    let mut game = Game::new();
+    game.set_time_control(TimeControl::from_str("60").unwrap(), clock.clone());
+
+    clock.advance(secs(10));
+    game.make_move(ChessMove::from_str("e2e4").unwrap());
+    clock.advance(secs(20));
+    game.make_move(ChessMove::from_str("e7e5").unwrap());
+    clock.advance(secs(5));
+    assert_eq!(game.remaining_time(Color::White), Some(secs(45)));
+
+    assert!(game.truncate(1));
+    assert_eq!(game.elapsed_times(), &[Some(secs(10))]);
+    assert_eq!(game.remaining_time(Color::White), Some(secs(50)));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(60)));
+    clock.advance(secs(7));
+    assert_eq!(game.remaining_time(Color::Black), Some(secs(53)));
+}
+
+#[test]
+fn test_draw_offer_expiry() {
+    let mut game = Game::new();
+    game.offer_draw(Color::White);
+    game.make_move(ChessMove::from_str("e2e4").unwrap());
+    game.offer_draw(Color::White);
+    assert_eq!(game.draw_offer(), Some(Color::White));
+    game.make_move(ChessMove::from_str("e7e5").unwrap());
+    assert_eq!(game.draw_offer(), None);
+    assert!(!game.accept_draw());
+    assert!(!game.decline_draw());
+
+    // black offers out of turn, and white declines by moving
+    game.offer_draw(Color::Black);
+    game.make_move(ChessMove::from_str("g1f3").unwrap());
+    assert!(!game.accept_draw());
+
+    // an offer with a move stays open while the opponent thinks
+    game.offer_draw(Color::Black);
+    game.make_move(ChessMove::from_str("b8c6").unwrap());
+    game.resign(Color::White);
+    assert_eq!(game.result(), Some(GameResult::WhiteResigns));
+    assert!(!game.accept_draw());
+
+    // taking back the move also takes back the offer made with it
+    game.undo();
+    assert_eq!(game.draw_offer(), None);
+    game.offer_draw(Color::Black);
+    game.make_move(ChessMove::from_str("b8c6").unwrap());
+    assert!(game.accept_draw());
+    assert_eq!(game.result(), Some(GameResult::DrawAccepted));
+}
+
+#[test]
+fn test_claim_draw_with_move() {
+    // the claim fails, but the move is still made
+    let mut game = Game::new();
+    assert!(!game.claim_draw_with_move(ChessMove::from_str("e2e4").unwrap()));
+    assert_eq!(game.ply(), 1);
+    assert_eq!(game.result(), None);
+    assert!(!game.claim_draw_with_move(ChessMove::from_str("e2e4").unwrap()));
+    assert_eq!(game.ply(), 1);
+
+    let mut game = play_uci("4k3/8/8/8/8/8/8/R3K3 w - - 98 80", "a1a2");
+    assert!(!game.can_declare_draw());
+    assert!(game.claim_draw_with_move(ChessMove::from_str("e8d8").unwrap()));
+    assert_eq!(game.result(), Some(GameResult::FiftyMoveRule));
+    assert_eq!(game.actions().last(), Some(&Action::ClaimDraw));
+
+    // a move that resets the count cannot be used to claim
+    let mut game = play_uci("4k3/8/8/8/8/8/r7/R3K3 w - - 99 80", "");
+    assert!(!game.claim_draw_with_move(ChessMove::from_str("a1a2").unwrap()));
+    assert_eq!(game.current_position().halfmove_clock(), 0);
+    assert_eq!(game.result(), None);
+    let mut game = play_uci("4k3/8/8/8/8/8/r7/R3K3 w - - 99 80", "");
+    assert!(game.claim_draw_with_move(ChessMove::from_str("e1d1").unwrap()));
+    assert_eq!(game.result(), Some(GameResult::FiftyMoveRule));
+}
@@ -0,0 +1,938 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+use crate::game::{Action, Game};
+use crate::pgn::{result_marker, split_comment, PgnGame, PgnNode, PgnVariation};
+use crate::square::Square;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Refers to one node (a position, and the move that led to it) of a `GameTree`.
+///
+/// A `NodeId` stays valid until its node is deleted.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct NodeId(usize);
+
+/// The colors of the arrows and squares drawn on a position, as in the `%cal` and `%csl`
+/// commands of PGN comments.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum AnnotationColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl AnnotationColor {
+    /// The letter for this color in `%cal` and `%csl` commands.
+    fn to_char(self) -> char {
+        match self {
+            AnnotationColor::Red => 'R',
+            AnnotationColor::Green => 'G',
+            AnnotationColor::Blue => 'B',
+            AnnotationColor::Yellow => 'Y',
+        }
+    }
+
+    fn from_char(c: char) -> Option<AnnotationColor> {
+        match c {
+            'R' => Some(AnnotationColor::Red),
+            'G' => Some(AnnotationColor::Green),
+            'B' => Some(AnnotationColor::Blue),
+            'Y' => Some(AnnotationColor::Yellow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    parent: Option<NodeId>,
+    chess_move: Option<ChessMove>,
+    board: Board,
+    children: Vec<NodeId>,
+    comment: Option<String>,
+    nags: Vec<u8>,
+    arrows: Vec<(Square, Square, AnnotationColor)>,
+    squares: Vec<(Square, AnnotationColor)>,
+}
+
+impl Node {
+    fn new(parent: Option<NodeId>, chess_move: Option<ChessMove>, board: Board) -> Node {
+        Node {
+            parent,
+            chess_move,
+            board,
+            children: vec![],
+            comment: None,
+            nags: vec![],
+            arrows: vec![],
+            squares: vec![],
+        }
+    }
+}
+
+/// A game with variations, for analysis.
+///
+/// Every node holds a position and the move that led to it, except the root, which holds the
+/// start position.  The first child of a node continues its line, and any other children are
+/// variations.  Nodes can carry a comment, NAGs, and arrows and squares to draw on the board.
+///
+/// The tree also keeps a current node, which `make_move()`, `back()`, `forward()` and `go_to()`
+/// move around, like an analysis board.
+///
+/// ```
+/// use chess::{ChessMove, GameTree, Square};
+///
+/// let e4 = ChessMove::new(Square::E2, Square::E4, None);
+/// let d4 = ChessMove::new(Square::D2, Square::D4, None);
+/// let e5 = ChessMove::new(Square::E7, Square::E5, None);
+///
+/// let mut tree = GameTree::new();
+/// let after_e4 = tree.make_move(e4).expect("Legal move");
+/// tree.make_move(e5);
+///
+/// // go back to the start, and try 1. d4 instead
+/// tree.go_to_start();
+/// let after_d4 = tree.make_move(d4).expect("Legal move");
+/// assert_eq!(tree.children(tree.root()), &[after_e4, after_d4]);
+/// assert!(!tree.is_mainline(after_d4));
+///
+/// tree.set_comment(after_d4, "Also good");
+/// tree.promote_to_main(after_d4);
+/// assert_eq!(tree.mainline(), vec![d4]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    nodes: Vec<Option<Node>>,
+    current: NodeId,
+}
+
+impl GameTree {
+    /// Create a `GameTree` starting from the initial position.
+    pub fn new() -> GameTree {
+        GameTree::new_with_board(Board::default())
+    }
+
+    /// Create a `GameTree` starting from `board`.
+    pub fn new_with_board(board: Board) -> GameTree {
+        GameTree {
+            nodes: vec![Some(Node::new(None, None, board))],
+            current: NodeId(0),
+        }
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(|n| n.as_ref())
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0).and_then(|n| n.as_mut())
+    }
+
+    /// The root node, which holds the start position.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// The current node.
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// The position at the current node.
+    pub fn board(&self) -> Board {
+        self.node(self.current)
+            .expect("The current node exists")
+            .board
+    }
+
+    /// Does `node` exist?  (It may have been deleted.)
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.node(node).is_some()
+    }
+
+    /// The position at `node`.
+    pub fn board_at(&self, node: NodeId) -> Option<Board> {
+        self.node(node).map(|n| n.board)
+    }
+
+    /// The move that led to `node`, or `None` for the root.
+    pub fn chess_move(&self, node: NodeId) -> Option<ChessMove> {
+        self.node(node).and_then(|n| n.chess_move)
+    }
+
+    /// The node before `node`, or `None` for the root.
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.node(node).and_then(|n| n.parent)
+    }
+
+    /// The nodes after `node`.  The first one continues the line, and the rest are variations.
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        self.node(node).map(|n| &n.children[..]).unwrap_or(&[])
+    }
+
+    /// Is `node` on the mainline, which follows the first child from the root?
+    pub fn is_mainline(&self, node: NodeId) -> bool {
+        let mut node = node;
+        while let Some(parent) = self.parent(node) {
+            if self.children(parent).first() != Some(&node) {
+                return false;
+            }
+            node = parent;
+        }
+        self.contains(node)
+    }
+
+    /// The moves from the root to `node`.
+    pub fn moves_to(&self, node: NodeId) -> Vec<ChessMove> {
+        let mut moves = vec![];
+        let mut node = node;
+        while let Some(m) = self.chess_move(node) {
+            moves.push(m);
+            node = self.parent(node).expect("Only the root has no parent");
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// The moves of the mainline.
+    pub fn mainline(&self) -> Vec<ChessMove> {
+        self.moves_to(self.line_end(self.root()))
+    }
+
+    /// Follow the first child from `node` to the end of its line.
+    fn line_end(&self, node: NodeId) -> NodeId {
+        let mut node = node;
+        while let Some(child) = self.children(node).first() {
+            node = *child;
+        }
+        node
+    }
+
+    /// Add `m` after `node`, without changing the current node.  If `m` is already there, the
+    /// existing node is returned.  A new move becomes a variation, unless it is the first move
+    /// after `node`.  Returns `None` if `m` is illegal, or `node` does not exist.
+    pub fn add_move(&mut self, node: NodeId, m: ChessMove) -> Option<NodeId> {
+        let board = self.board_at(node)?;
+        if let Some(child) = self
+            .children(node)
+            .iter()
+            .find(|c| self.chess_move(**c) == Some(m))
+        {
+            return Some(*child);
+        }
+        if !board.legal(m) {
+            return None;
+        }
+
+        let id = NodeId(self.nodes.len());
+        self.nodes
+            .push(Some(Node::new(Some(node), Some(m), board.make_move_new(m))));
+        self.node_mut(node)?.children.push(id);
+        Some(id)
+    }
+
+    /// Play `m` from the current node (see `add_move()`), and make its node the current node.
+    pub fn make_move(&mut self, m: ChessMove) -> Option<NodeId> {
+        let id = self.add_move(self.current, m)?;
+        self.current = id;
+        Some(id)
+    }
+
+    /// Make `node` the current node.  Returns false if it does not exist.
+    pub fn go_to(&mut self, node: NodeId) -> bool {
+        if self.contains(node) {
+            self.current = node;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Go back one move.  Returns false at the root.
+    pub fn back(&mut self) -> bool {
+        match self.parent(self.current) {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go forward one move, along the current line.  Returns false at the end of the line.
+    pub fn forward(&mut self) -> bool {
+        match self.children(self.current).first() {
+            Some(child) => {
+                self.current = *child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go back to the root.
+    pub fn go_to_start(&mut self) {
+        self.current = self.root();
+    }
+
+    /// Go forward to the end of the current line.
+    pub fn go_to_end(&mut self) {
+        self.current = self.line_end(self.current);
+    }
+
+    /// Move `node` one place up among its siblings.  Promoting the second child makes it the
+    /// continuation of the line.  Returns false if it was already first, or is the root.
+    pub fn promote(&mut self, node: NodeId) -> bool {
+        self.swap_with_sibling(node, -1)
+    }
+
+    /// Move `node` one place down among its siblings.  Returns false if it was already last, or
+    /// is the root.
+    pub fn demote(&mut self, node: NodeId) -> bool {
+        self.swap_with_sibling(node, 1)
+    }
+
+    fn swap_with_sibling(&mut self, node: NodeId, offset: isize) -> bool {
+        let parent = match self.parent(node) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let children = &mut self.node_mut(parent).expect("Parents exist").children;
+        let index = children
+            .iter()
+            .position(|c| *c == node)
+            .expect("A node is a child of its parent") as isize;
+        let other = index + offset;
+        if other < 0 || other >= children.len() as isize {
+            return false;
+        }
+        children.swap(index as usize, other as usize);
+        true
+    }
+
+    /// Make the line through `node` the mainline, by making it and all of its ancestors the
+    /// first child of their parents.
+    pub fn promote_to_main(&mut self, node: NodeId) {
+        let mut node = node;
+        while let Some(parent) = self.parent(node) {
+            let children = &mut self.node_mut(parent).expect("Parents exist").children;
+            let index = children
+                .iter()
+                .position(|c| *c == node)
+                .expect("A node is a child of its parent");
+            let child = children.remove(index);
+            children.insert(0, child);
+            node = parent;
+        }
+    }
+
+    /// Delete `node` and everything after it.  If the current node is deleted, the parent of
+    /// `node` becomes the current node.  Returns false for the root, or a node that does not
+    /// exist.
+    pub fn delete(&mut self, node: NodeId) -> bool {
+        let parent = match self.parent(node) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        self.node_mut(parent)
+            .expect("Parents exist")
+            .children
+            .retain(|c| *c != node);
+
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if id == self.current {
+                self.current = parent;
+            }
+            if let Some(removed) = self.nodes[id.0].take() {
+                stack.extend(removed.children);
+            }
+        }
+        true
+    }
+
+    /// The comment on `node`.
+    pub fn comment(&self, node: NodeId) -> Option<&str> {
+        self.node(node).and_then(|n| n.comment.as_deref())
+    }
+
+    /// Set the comment on `node`, replacing any comment already there.
+    pub fn set_comment(&mut self, node: NodeId, comment: &str) {
+        if let Some(n) = self.node_mut(node) {
+            n.comment = Some(comment.to_string());
+        }
+    }
+
+    /// Remove the comment on `node`.
+    pub fn clear_comment(&mut self, node: NodeId) {
+        if let Some(n) = self.node_mut(node) {
+            n.comment = None;
+        }
+    }
+
+    /// The Numeric Annotation Glyphs on `node`, such as 1 for `!` or 2 for `?`.
+    pub fn nags(&self, node: NodeId) -> &[u8] {
+        self.node(node).map(|n| &n.nags[..]).unwrap_or(&[])
+    }
+
+    /// Add a Numeric Annotation Glyph to `node`, unless it is already there.
+    pub fn add_nag(&mut self, node: NodeId, nag: u8) {
+        if let Some(n) = self.node_mut(node) {
+            if !n.nags.contains(&nag) {
+                n.nags.push(nag);
+            }
+        }
+    }
+
+    /// Remove a Numeric Annotation Glyph from `node`.
+    pub fn remove_nag(&mut self, node: NodeId, nag: u8) {
+        if let Some(n) = self.node_mut(node) {
+            n.nags.retain(|x| *x != nag);
+        }
+    }
+
+    /// The arrows drawn on the position at `node`, as `(from, to, color)`.
+    pub fn arrows(&self, node: NodeId) -> &[(Square, Square, AnnotationColor)] {
+        self.node(node).map(|n| &n.arrows[..]).unwrap_or(&[])
+    }
+
+    /// Draw an arrow on the position at `node`, replacing any arrow between the same squares.
+    pub fn add_arrow(&mut self, node: NodeId, from: Square, to: Square, color: AnnotationColor) {
+        if let Some(n) = self.node_mut(node) {
+            n.arrows.retain(|(f, t, _)| (*f, *t) != (from, to));
+            n.arrows.push((from, to, color));
+        }
+    }
+
+    /// The squares highlighted on the position at `node`.
+    pub fn squares(&self, node: NodeId) -> &[(Square, AnnotationColor)] {
+        self.node(node).map(|n| &n.squares[..]).unwrap_or(&[])
+    }
+
+    /// Highlight a square on the position at `node`, replacing any highlight already on it.
+    pub fn add_square(&mut self, node: NodeId, square: Square, color: AnnotationColor) {
+        if let Some(n) = self.node_mut(node) {
+            n.squares.retain(|(s, _)| *s != square);
+            n.squares.push((square, color));
+        }
+    }
+
+    /// Remove all arrows and highlighted squares from the position at `node`.
+    pub fn clear_drawings(&mut self, node: NodeId) {
+        if let Some(n) = self.node_mut(node) {
+            n.arrows.clear();
+            n.squares.clear();
+        }
+    }
+
+    /// A `Game` of the mainline.
+    ///
+    /// Returns `Error::GameOver` if the mainline carries on after the game is over, such as after
+    /// a fivefold repetition, because `Game` does not accept those moves.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Game, GameTree, Square};
+    ///
+    /// let mut game = Game::new();
+    /// game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    ///
+    /// let tree = GameTree::from(&game);
+    /// let mainline = tree.mainline_game().expect("The game is not over");
+    /// assert_eq!(mainline.current_position(), game.current_position());
+    /// ```
+    pub fn mainline_game(&self) -> Result<Game, Error> {
+        let start = self.board_at(self.root()).expect("The root exists");
+        let mut game = Game::new_with_board(start);
+        for m in self.mainline() {
+            if !game.make_move(m) {
+                return Err(Error::GameOver);
+            }
+        }
+        Ok(game)
+    }
+
+    /// Add the moves of `line` after `node`, along with their variations and annotations.  The
+    /// comments before the first move of a variation go in front of the comment of that move.
+    fn add_pgn_line(&mut self, node: NodeId, line: &PgnVariation, variation: bool) {
+        let mut parent = node;
+        for (i, pgn_node) in line.nodes().iter().enumerate() {
+            let child = self
+                .add_move(parent, pgn_node.chess_move())
+                .expect("Moves in a PgnGame are legal");
+            if variation && i == 0 {
+                self.add_pgn_comments(child, line.comments(), line.commands());
+            }
+            self.add_pgn_comments(child, pgn_node.comments(), pgn_node.commands());
+            for nag in pgn_node.nags() {
+                self.add_nag(child, *nag);
+            }
+            for alternative in pgn_node.variations() {
+                self.add_pgn_line(parent, alternative, true);
+            }
+            parent = child;
+        }
+    }
+
+    /// Add PGN comments to `node`.  `%cal` and `%csl` commands become arrows and highlighted
+    /// squares, and any other command is kept in the comment.
+    fn add_pgn_comments(
+        &mut self,
+        node: NodeId,
+        comments: &[String],
+        commands: &[(String, String)],
+    ) {
+        let mut text: Vec<String> = self.comment(node).map(str::to_string).into_iter().collect();
+        text.extend(comments.iter().cloned());
+        for (name, value) in commands {
+            let drawn = match name.as_str() {
+                "cal" => self.add_pgn_arrows(node, value),
+                "csl" => self.add_pgn_squares(node, value),
+                _ => false,
+            };
+            if !drawn {
+                text.push(format!("[%{} {}]", name, value));
+            }
+        }
+        if !text.is_empty() {
+            self.set_comment(node, &text.join(" "));
+        }
+    }
+
+    /// Add the arrows of a `%cal` command, such as `Gd7d5,Re2e4`.  Returns false, without adding
+    /// any, if the command cannot be parsed.
+    fn add_pgn_arrows(&mut self, node: NodeId, value: &str) -> bool {
+        let arrows = value
+            .split(',')
+            .map(|arrow| {
+                let color = AnnotationColor::from_char(arrow.chars().next()?)?;
+                let from = Square::from_str(arrow.get(1..3)?).ok()?;
+                let to = Square::from_str(arrow.get(3..)?).ok()?;
+                Some((from, to, color))
+            })
+            .collect::<Option<Vec<_>>>();
+        match arrows {
+            Some(arrows) => {
+                for (from, to, color) in arrows {
+                    self.add_arrow(node, from, to, color);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add the squares of a `%csl` command, such as `Ye4,Rd5`.  Returns false, without adding any,
+    /// if the command cannot be parsed.
+    fn add_pgn_squares(&mut self, node: NodeId, value: &str) -> bool {
+        let squares = value
+            .split(',')
+            .map(|square| {
+                let color = AnnotationColor::from_char(square.chars().next()?)?;
+                Some((Square::from_str(square.get(1..)?).ok()?, color))
+            })
+            .collect::<Option<Vec<_>>>();
+        match squares {
+            Some(squares) => {
+                for (square, color) in squares {
+                    self.add_square(node, square, color);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The line starting at `node`, as PGN.  The other children of a node's parent become its
+    /// variations, unless `node` is itself a variation.
+    fn pgn_line(&self, node: NodeId) -> Vec<PgnNode> {
+        let mut nodes = vec![];
+        let mut next = Some(node);
+        while let Some(node) = next {
+            let parent = self.parent(node).expect("Only the root has no parent");
+            let board = self.board_at(parent).expect("The parent exists");
+            let chess_move = self.chess_move(node).expect("Only the root has no move");
+            let variations = match self.children(parent).split_first() {
+                Some((first, rest)) if *first == node => rest
+                    .iter()
+                    .map(|v| PgnVariation::new(vec![], vec![], self.pgn_line(*v)))
+                    .collect(),
+                _ => vec![],
+            };
+            let (comments, commands) = self.pgn_comments(node);
+            nodes.push(PgnNode::new(
+                chess_move,
+                chess_move.to_san(&board),
+                self.nags(node).to_vec(),
+                comments,
+                commands,
+                variations,
+            ));
+            next = self.children(node).first().copied();
+        }
+        nodes
+    }
+
+    /// The comment of `node` as PGN comments and commands, with the arrows and squares as `%cal`
+    /// and `%csl` commands.
+    fn pgn_comments(&self, node: NodeId) -> (Vec<String>, Vec<(String, String)>) {
+        let mut comments = vec![];
+        let mut commands = vec![];
+        if let Some(comment) = self.comment(node) {
+            split_comment(comment, &mut comments, &mut commands);
+        }
+        if !self.arrows(node).is_empty() {
+            let arrows: Vec<String> = self
+                .arrows(node)
+                .iter()
+                .map(|(from, to, color)| format!("{}{}{}", color.to_char(), from, to))
+                .collect();
+            commands.push(("cal".to_string(), arrows.join(",")));
+        }
+        if !self.squares(node).is_empty() {
+            let squares: Vec<String> = self
+                .squares(node)
+                .iter()
+                .map(|(square, color)| format!("{}{}", color.to_char(), square))
+                .collect();
+            commands.push(("csl".to_string(), squares.join(",")));
+        }
+        (comments, commands)
+    }
+}
+
+impl Default for GameTree {
+    fn default() -> GameTree {
+        GameTree::new()
+    }
+}
+
+/// The moves of the `Game` become the mainline, and the current node is the end of it.  Draw
+/// offers and resignations are not kept.
+impl From<&Game> for GameTree {
+    fn from(game: &Game) -> GameTree {
+        let mut tree = GameTree::new_with_board(game.start_position());
+        for action in game.actions().iter() {
+            if let Action::MakeMove(m) = action {
+                tree.make_move(*m).expect("Moves in a Game are legal");
+            }
+        }
+        tree
+    }
+}
+
+/// The movetext of the `PgnGame` becomes the tree, and the current node is the end of the
+/// mainline.  Comments and NAGs are kept, and `%cal` and `%csl` commands become arrows and
+/// highlighted squares.  Any other command, such as `%clk`, stays in the comment.  Comments before
+/// the first move of the game go on the root.
+///
+/// ```
+/// use chess::{AnnotationColor, GameTree, PgnGame, Square};
+/// use std::str::FromStr;
+///
+/// let pgn = PgnGame::from_str("1. e4 { [%cal Gd7d5] Best by test } e5 (1... c5 $1) *")
+///     .expect("Valid PGN");
+/// let tree = GameTree::from(&pgn);
+///
+/// let e4 = tree.children(tree.root())[0];
+/// assert_eq!(tree.comment(e4), Some("Best by test"));
+/// assert_eq!(tree.arrows(e4), &[(Square::D7, Square::D5, AnnotationColor::Green)]);
+/// let c5 = tree.children(e4)[1];
+/// assert_eq!(tree.nags(c5), &[1]);
+/// ```
+impl From<&PgnGame> for GameTree {
+    fn from(pgn: &PgnGame) -> GameTree {
+        let mut tree = GameTree::new_with_board(pgn.game().start_position());
+        let root = tree.root();
+        let mainline = pgn.mainline();
+        tree.add_pgn_comments(root, mainline.comments(), mainline.commands());
+        tree.add_pgn_line(root, mainline, false);
+        tree.current = tree.line_end(root);
+        tree
+    }
+}
+
+/// The tree becomes the movetext of a `PgnGame`, and its mainline the `Game`.  Arrows and
+/// highlighted squares become `%cal` and `%csl` commands, and the `Variant`, `SetUp` and `FEN` tags
+/// are set when needed.  Like `GameTree::mainline_game()`, this returns `Error::GameOver` if the
+/// mainline carries on after the game is over.
+///
+/// ```
+/// use chess::{ChessMove, GameTree, PgnGame, PgnWriter, Square};
+/// use std::convert::TryFrom;
+///
+/// let mut tree = GameTree::new();
+/// let e4 = tree.make_move(ChessMove::new(Square::E2, Square::E4, None)).expect("Legal move");
+/// tree.back();
+/// let d4 = tree.make_move(ChessMove::new(Square::D2, Square::D4, None)).expect("Legal move");
+/// tree.set_comment(d4, "Also good");
+///
+/// let pgn = PgnGame::try_from(&tree).expect("The game is not over");
+/// assert_eq!(pgn.mainline().nodes()[0].variations()[0].nodes()[0].san(), "d4");
+/// assert!(PgnWriter::from(&pgn).to_string().ends_with("\n1. e4 (1. d4 { Also good }) *\n"));
+/// ```
+impl TryFrom<&GameTree> for PgnGame {
+    type Error = Error;
+
+    fn try_from(tree: &GameTree) -> Result<PgnGame, Error> {
+        let game = tree.mainline_game()?;
+        let start = game.start_position();
+        let mut tags = vec![];
+        if start.is_chess960() {
+            tags.push(("Variant".to_string(), "Chess960".to_string()));
+        }
+        if start != Board::default() {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), start.to_string()));
+        }
+
+        let root = tree.root();
+        let (comments, commands) = tree.pgn_comments(root);
+        let nodes = match tree.children(root).first() {
+            Some(first) => tree.pgn_line(*first),
+            None => vec![],
+        };
+        let result = result_marker(game.result()).to_string();
+        Ok(PgnGame::new(
+            tags,
+            game,
+            PgnVariation::new(comments, commands, nodes),
+            Some(result),
+        ))
+    }
+}
+
+#[cfg(test)]
+use crate::game::GameResult;
+#[cfg(test)]
+use crate::pgn::PgnWriter;
+
+#[cfg(test)]
+fn uci(m: &str) -> ChessMove {
+    ChessMove::from_str(m).expect("Valid UCI move")
+}
+
+#[test]
+fn test_game_tree_variations() {
+    let mut tree = GameTree::new();
+    let e4 = tree.make_move(uci("e2e4")).unwrap();
+    let e5 = tree.make_move(uci("e7e5")).unwrap();
+    let nf3 = tree.make_move(uci("g1f3")).unwrap();
+
+    tree.go_to(e4);
+    let c5 = tree.make_move(uci("c7c5")).unwrap();
+    let c5_nf3 = tree.make_move(uci("g1f3")).unwrap();
+    tree.go_to(e4);
+    let e6 = tree.make_move(uci("e7e6")).unwrap();
+
+    assert_eq!(tree.children(e4), &[e5, c5, e6]);
+    assert_eq!(tree.mainline(), vec![uci("e2e4"), uci("e7e5"), uci("g1f3")]);
+    assert!(tree.is_mainline(nf3));
+    assert!(!tree.is_mainline(c5_nf3));
+    assert_eq!(
+        tree.moves_to(c5_nf3),
+        vec![uci("e2e4"), uci("c7c5"), uci("g1f3")]
+    );
+
+    // playing an existing move goes to its node
+    tree.go_to(e4);
+    assert_eq!(tree.make_move(uci("c7c5")), Some(c5));
+    assert_eq!(tree.make_move(uci("e2e4")), None);
+    assert_eq!(tree.current(), c5);
+
+    assert!(tree.promote(c5));
+    assert_eq!(tree.children(e4), &[c5, e5, e6]);
+    assert!(!tree.promote(c5));
+    assert!(tree.demote(c5));
+    assert!(tree.demote(c5));
+    assert!(!tree.demote(c5));
+    assert_eq!(tree.children(e4), &[e5, e6, c5]);
+
+    tree.promote_to_main(c5_nf3);
+    assert_eq!(tree.mainline(), vec![uci("e2e4"), uci("c7c5"), uci("g1f3")]);
+    assert_eq!(tree.children(e4), &[c5, e5, e6]);
+
+    // deleting the line the current node is on moves back to the branch point
+    tree.go_to(c5_nf3);
+    assert!(tree.delete(c5));
+    assert_eq!(tree.current(), e4);
+    assert!(!tree.contains(c5));
+    assert!(!tree.contains(c5_nf3));
+    assert!(!tree.go_to(c5_nf3));
+    assert!(!tree.delete(tree.root()));
+    assert_eq!(tree.mainline(), vec![uci("e2e4"), uci("e7e5"), uci("g1f3")]);
+}
+
+#[test]
+fn test_game_tree_navigation() {
+    let mut tree = GameTree::new();
+    assert!(!tree.back());
+    assert!(!tree.forward());
+
+    tree.make_move(uci("d2d4"));
+    tree.make_move(uci("d7d5"));
+    tree.make_move(uci("c2c4"));
+    let end = tree.current();
+    let board = tree.board();
+
+    tree.go_to_start();
+    assert_eq!(tree.board(), Board::default());
+    assert!(tree.forward());
+    assert_eq!(tree.chess_move(tree.current()), Some(uci("d2d4")));
+    tree.go_to_end();
+    assert_eq!(tree.current(), end);
+    assert_eq!(tree.board(), board);
+    assert!(tree.back());
+    assert_eq!(
+        tree.board_at(tree.current()),
+        tree.board_at(tree.parent(end).unwrap())
+    );
+
+    let game = tree.mainline_game().expect("The game is not over");
+    assert_eq!(game.current_position(), board);
+    let copy = GameTree::from(&game);
+    assert_eq!(copy.mainline(), tree.mainline());
+    assert_eq!(copy.current(), copy.line_end(copy.root()));
+}
+
+#[test]
+fn test_game_tree_annotations() {
+    let mut tree = GameTree::new();
+    let e4 = tree.make_move(uci("e2e4")).unwrap();
+
+    tree.set_comment(e4, "Best by test");
+    assert_eq!(tree.comment(e4), Some("Best by test"));
+    tree.clear_comment(e4);
+    assert_eq!(tree.comment(e4), None);
+
+    tree.add_nag(e4, 1);
+    tree.add_nag(e4, 1);
+    tree.add_nag(e4, 14);
+    assert_eq!(tree.nags(e4), &[1, 14]);
+    tree.remove_nag(e4, 1);
+    assert_eq!(tree.nags(e4), &[14]);
+
+    tree.add_arrow(e4, Square::D7, Square::D5, AnnotationColor::Green);
+    tree.add_arrow(e4, Square::D7, Square::D5, AnnotationColor::Red);
+    tree.add_square(e4, Square::E4, AnnotationColor::Yellow);
+    assert_eq!(
+        tree.arrows(e4),
+        &[(Square::D7, Square::D5, AnnotationColor::Red)]
+    );
+    assert_eq!(tree.squares(e4), &[(Square::E4, AnnotationColor::Yellow)]);
+    tree.clear_drawings(e4);
+    assert!(tree.arrows(e4).is_empty());
+    assert!(tree.squares(e4).is_empty());
+
+    tree.delete(e4);
+    assert_eq!(tree.comment(e4), None);
+    assert!(tree.nags(e4).is_empty());
+}
+
+#[test]
+fn test_game_tree_mainline_game_over() {
+    let mut tree = GameTree::new();
+    for _ in 0..4 {
+        for m in ["g1f3", "g8f6", "f3g1", "f6g8"].iter() {
+            tree.make_move(uci(m));
+        }
+    }
+    let game = tree
+        .mainline_game()
+        .expect("The fifth repetition ends the game");
+    assert_eq!(game.result(), Some(GameResult::FivefoldRepetition));
+
+    // the tree carries on, but the game cannot
+    tree.make_move(uci("e2e4"));
+    assert!(matches!(tree.mainline_game(), Err(Error::GameOver)));
+    assert!(matches!(PgnGame::try_from(&tree), Err(Error::GameOver)));
+}
+
+#[test]
+fn test_game_tree_pgn_conversion() {
+    let pgn = PgnGame::from_str(
+        "{ Opening } 1. e4 { [%clk 0:03:00] [%csl Ye4,Rd5] Best by test } e5 \
+         (1... c5 { Sicilian } 2. Nf3 (2. c3 $6) d6) 2. Nf3 $1 { [%cal Gf3e5,Bd7d6] } *",
+    )
+    .expect("Valid PGN");
+    let tree = GameTree::from(&pgn);
+
+    let root = tree.root();
+    assert_eq!(tree.comment(root), Some("Opening"));
+    let e4 = tree.children(root)[0];
+    assert_eq!(tree.comment(e4), Some("Best by test [%clk 0:03:00]"));
+    assert_eq!(
+        tree.squares(e4),
+        &[
+            (Square::E4, AnnotationColor::Yellow),
+            (Square::D5, AnnotationColor::Red)
+        ]
+    );
+    let (e5, c5) = (tree.children(e4)[0], tree.children(e4)[1]);
+    assert_eq!(tree.comment(c5), Some("Sicilian"));
+    let c5_c3 = tree.children(c5)[1];
+    assert_eq!(tree.chess_move(c5_c3), Some(uci("c2c3")));
+    assert_eq!(tree.nags(c5_c3), &[6]);
+    let nf3 = tree.children(e5)[0];
+    assert_eq!(tree.nags(nf3), &[1]);
+    assert_eq!(
+        tree.arrows(nf3),
+        &[
+            (Square::F3, Square::E5, AnnotationColor::Green),
+            (Square::D7, Square::D6, AnnotationColor::Blue)
+        ]
+    );
+    assert_eq!(tree.current(), nf3);
+    assert_eq!(
+        tree.mainline_game()
+            .expect("The game is not over")
+            .actions(),
+        pgn.game().actions()
+    );
+
+    // and back again
+    let converted = PgnGame::try_from(&tree).expect("The game is not over");
+    assert_eq!(converted.game().actions(), pgn.game().actions());
+    assert_eq!(converted.result(), Some("*"));
+    assert_eq!(converted.mainline().comments(), ["Opening"]);
+    let e4_node = &converted.mainline().nodes()[0];
+    assert_eq!(e4_node.comments(), ["Best by test"]);
+    assert_eq!(e4_node.command("csl"), Some("Ye4,Rd5"));
+    assert_eq!(e4_node.command("clk"), Some("0:03:00"));
+    let e5_node = &converted.mainline().nodes()[1];
+    assert_eq!(
+        e5_node.variations()[0].nodes()[1].variations()[0].nodes()[0].san(),
+        "c3"
+    );
+    assert_eq!(
+        converted.mainline().nodes()[2].command("cal"),
+        Some("Gf3e5,Bd7d6")
+    );
+
+    let written = PgnWriter::from(&converted).to_string();
+    let reread = PgnGame::from_str(&written).expect("Valid PGN");
+    assert_eq!(GameTree::from(&reread).mainline(), tree.mainline());
+    assert_eq!(
+        PgnGame::try_from(&GameTree::from(&reread))
+            .expect("The game is not over")
+            .mainline(),
+        converted.mainline()
+    );
+}
+
+#[test]
+fn test_game_tree_pgn_set_up() {
+    let board = Board::from_str("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40").expect("Valid FEN");
+    let mut tree = GameTree::new_with_board(board);
+    tree.make_move(uci("e8d7"));
+    tree.make_move(uci("e2e4"));
+
+    let pgn = PgnGame::try_from(&tree).expect("The game is not over");
+    assert_eq!(pgn.tag("FEN"), Some("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"));
+    assert!(PgnWriter::from(&pgn)
+        .to_string()
+        .ends_with("\n40... Kd7 41. e4 *\n"));
+    assert_eq!(GameTree::from(&pgn).board(), tree.board());
+}
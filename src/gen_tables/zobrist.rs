@@ -56,4 +56,18 @@ pub fn write_zobrist(f: &mut File) {
         }
     }
     write!(f, "]];\n\n").unwrap();
+
+    // checks left to give in Three-check, for 0, 1 or 2 checks.  All 3 left adds nothing.
+    write!(f, "const ZOBRIST_CHECKS: [[u64; 3]; NUM_COLORS] = [[\n").unwrap();
+    for i in 0..NUM_COLORS {
+        for _ in 0..3 {
+            write!(f, "    {},\n", rng.next_u64()).unwrap();
+        }
+        if i != NUM_COLORS - 1 {
+            write!(f, "], [\n").unwrap();
+        }
+    }
+    write!(f, "]];\n\n").unwrap();
+
+    write!(f, "const CHESS960: u64 = {};\n\n", rng.next_u64()).unwrap();
 }
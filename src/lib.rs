@@ -33,6 +33,9 @@ pub use crate::castle_rights::*;
 mod chess_move;
 pub use crate::chess_move::*;
 
+mod move16;
+pub use crate::move16::*;
+
 mod color;
 pub use crate::color::*;
 
@@ -64,13 +67,52 @@ pub use crate::square::*;
 mod movegen;
 pub use crate::movegen::MoveGen;
 
+mod see;
+pub use crate::see::*;
+
+mod perft;
+pub use crate::perft::*;
+
 mod zobrist;
 
 mod game;
 pub use crate::game::{Action, Game, GameResult};
 
+mod time_control;
+pub use crate::time_control::*;
+
+mod game_tree;
+pub use crate::game_tree::*;
+
+mod variant;
+pub use crate::variant::*;
+
+mod variant_board;
+pub use crate::variant_board::*;
+
+mod variant_game;
+pub use crate::variant_game::*;
+
+mod pgn;
+pub use crate::pgn::*;
+
+mod epd;
+pub use crate::epd::*;
+
+mod polyglot;
+pub use crate::polyglot::*;
+
+mod syzygy;
+pub use crate::syzygy::*;
+
+mod uci;
+pub use crate::uci::*;
+
 mod board_builder;
 pub use crate::board_builder::BoardBuilder;
 
 mod error;
 pub use crate::error::Error;
+
+#[cfg(feature = "serde")]
+mod serialization;
@@ -0,0 +1,130 @@
+use crate::chess_move::ChessMove;
+use crate::piece::{Piece, ALL_PIECES};
+use crate::square::Square;
+
+use std::fmt;
+
+/// A `ChessMove` packed into 16 bits, for when many moves need to be stored (in a transposition
+/// table, or an opening tree, for example).
+///
+/// The source square takes the top 6 bits, then the destination square takes 6 bits, and the
+/// promotion piece (if any) the bottom 4.  Because of this, `Move16`s sort in the same order as
+/// the `ChessMove`s they came from.
+///
+/// ```
+/// use chess::{ChessMove, Move16, Piece, Square};
+/// use std::mem;
+///
+/// let m = ChessMove::new(Square::E7, Square::E8, Some(Piece::Queen));
+/// let packed = Move16::from(m);
+///
+/// assert_eq!(mem::size_of::<Move16>(), 2);
+/// assert_eq!(ChessMove::from(packed), m);
+/// assert_eq!(Move16::from_bits(packed.to_bits()), Some(packed));
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Default, Debug, Hash)]
+pub struct Move16(u16);
+
+impl Move16 {
+    /// Get the source square.
+    #[inline]
+    pub fn get_source(&self) -> Square {
+        Square::new((self.0 >> 10) as u8)
+    }
+
+    /// Get the destination square.
+    #[inline]
+    pub fn get_dest(&self) -> Square {
+        Square::new(((self.0 >> 4) & 63) as u8)
+    }
+
+    /// Get the promotion piece (maybe).
+    #[inline]
+    pub fn get_promotion(&self) -> Option<Piece> {
+        match self.0 & 15 {
+            0 => None,
+            x => Some(ALL_PIECES[x as usize - 1]),
+        }
+    }
+
+    /// Get the raw 16 bits.
+    #[inline]
+    pub fn to_bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Rebuild a `Move16` from the bits given by `Move16::to_bits()`, or `None` if they could
+    /// not have come from one.
+    ///
+    /// ```
+    /// use chess::Move16;
+    ///
+    /// assert!(Move16::from_bits(0x0123).is_some());
+    /// assert!(Move16::from_bits(0x012f).is_none());
+    /// ```
+    #[inline]
+    pub fn from_bits(bits: u16) -> Option<Move16> {
+        if (bits & 15) as usize > ALL_PIECES.len() {
+            None
+        } else {
+            Some(Move16(bits))
+        }
+    }
+}
+
+impl From<ChessMove> for Move16 {
+    #[inline]
+    fn from(m: ChessMove) -> Move16 {
+        let promotion = match m.get_promotion() {
+            None => 0,
+            Some(piece) => piece.to_index() as u16 + 1,
+        };
+        Move16(
+            ((m.get_source().to_int() as u16) << 10)
+                | ((m.get_dest().to_int() as u16) << 4)
+                | promotion,
+        )
+    }
+}
+
+impl From<Move16> for ChessMove {
+    #[inline]
+    fn from(m: Move16) -> ChessMove {
+        ChessMove::new(m.get_source(), m.get_dest(), m.get_promotion())
+    }
+}
+
+impl fmt::Display for Move16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ChessMove::from(*self))
+    }
+}
+
+#[cfg(test)]
+use crate::square::ALL_SQUARES;
+
+#[test]
+fn test_move16_round_trip_and_order() {
+    let mut promotions = vec![None];
+    promotions.extend(ALL_PIECES.iter().map(|p| Some(*p)));
+
+    let mut moves = vec![];
+    for source in ALL_SQUARES.iter() {
+        for dest in ALL_SQUARES.iter() {
+            for promotion in promotions.iter() {
+                moves.push(ChessMove::new(*source, *dest, *promotion));
+            }
+        }
+    }
+
+    let packed = moves.iter().map(|m| Move16::from(*m)).collect::<Vec<_>>();
+    for (m, p) in moves.iter().zip(packed.iter()) {
+        assert_eq!(ChessMove::from(*p), *m);
+        assert_eq!(p.to_string(), m.to_string());
+        assert_eq!(Move16::from_bits(p.to_bits()), Some(*p));
+    }
+    for (a, b) in moves.iter().zip(moves.iter().skip(1)) {
+        assert_eq!(a.cmp(b), Move16::from(*a).cmp(&Move16::from(*b)));
+    }
+    assert_eq!(Move16::default(), Move16::from(ChessMove::default()));
+}
@@ -1,7 +1,7 @@
 use crate::bitboard::{BitBoard, EMPTY};
 use crate::board::Board;
 use crate::chess_move::ChessMove;
-use crate::magic::between;
+use crate::magic::{between, get_rank};
 use crate::movegen::piece_type::*;
 use crate::piece::{Piece, NUM_PROMOTION_PIECES, PROMOTION_PIECES};
 use crate::square::Square;
@@ -91,44 +91,179 @@ pub struct MoveGen {
 }
 
 impl MoveGen {
+    /// Generate the legal moves of the kind `G` that land on `mask`, or on `pawn_mask` for pawns.
     #[inline(always)]
-    fn enumerate_moves(board: &Board) -> MoveList {
+    fn enumerate_moves<G: GenType>(board: &Board, mask: BitBoard, pawn_mask: BitBoard) -> MoveList {
         let checkers = *board.checkers();
-        let mask = !board.color_combined(board.side_to_move());
         let mut movelist = NoDrop::new(ArrayVec::<SquareAndBitBoard, 18>::new());
 
         if checkers == EMPTY {
-            PawnType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-            KnightType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-            BishopType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-            RookType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-            QueenType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-            KingType::legals::<NotInCheckType>(&mut movelist, &board, mask);
-        } else if checkers.popcnt() == 1 {
-            PawnType::legals::<InCheckType>(&mut movelist, &board, mask);
-            KnightType::legals::<InCheckType>(&mut movelist, &board, mask);
-            BishopType::legals::<InCheckType>(&mut movelist, &board, mask);
-            RookType::legals::<InCheckType>(&mut movelist, &board, mask);
-            QueenType::legals::<InCheckType>(&mut movelist, &board, mask);
-            KingType::legals::<InCheckType>(&mut movelist, &board, mask);
+            PawnType::legals::<NotInCheckType, G>(&mut movelist, &board, pawn_mask);
+            if mask != EMPTY {
+                KnightType::legals::<NotInCheckType, G>(&mut movelist, &board, mask);
+                BishopType::legals::<NotInCheckType, G>(&mut movelist, &board, mask);
+                RookType::legals::<NotInCheckType, G>(&mut movelist, &board, mask);
+                QueenType::legals::<NotInCheckType, G>(&mut movelist, &board, mask);
+                KingType::legals::<NotInCheckType, G>(&mut movelist, &board, mask);
+            }
+        } else if checkers.popcnt() == 1 || !G::LEGAL {
+            PawnType::legals::<InCheckType, G>(&mut movelist, &board, pawn_mask);
+            if mask != EMPTY {
+                KnightType::legals::<InCheckType, G>(&mut movelist, &board, mask);
+                BishopType::legals::<InCheckType, G>(&mut movelist, &board, mask);
+                RookType::legals::<InCheckType, G>(&mut movelist, &board, mask);
+                QueenType::legals::<InCheckType, G>(&mut movelist, &board, mask);
+                KingType::legals::<InCheckType, G>(&mut movelist, &board, mask);
+            }
         } else {
-            KingType::legals::<InCheckType>(&mut movelist, &board, mask);
+            KingType::legals::<InCheckType, G>(&mut movelist, &board, mask);
         }
 
         movelist
     }
 
-    /// Create a new `MoveGen` structure, only generating legal moves
     #[inline(always)]
-    pub fn new_legal(board: &Board) -> MoveGen {
+    fn from_moves(moves: MoveList) -> MoveGen {
         MoveGen {
-            moves: MoveGen::enumerate_moves(board),
+            moves,
             promotion_index: 0,
             iterator_mask: !EMPTY,
             index: 0,
         }
     }
 
+    /// Create a new `MoveGen` structure, only generating legal moves
+    #[inline(always)]
+    pub fn new_legal(board: &Board) -> MoveGen {
+        let mask = !board.color_combined(board.side_to_move());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<AllMovesType>(board, mask, mask))
+    }
+
+    /// Create a new `MoveGen` structure, generating pseudo-legal moves.
+    ///
+    /// Pins and checks are ignored, so some of these moves may leave the king in check.  Check
+    /// each move with `Board::is_legal_after_pseudo()` before making it.  This skips the work of
+    /// proving moves legal until they are needed, which helps engines that often cut off a search
+    /// after looking at just a few moves.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, MoveGen, Square};
+    /// use std::str::FromStr;
+    ///
+    /// // the knight on e2 is pinned
+    /// let board = Board::from_str("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").expect("Valid FEN");
+    /// let knight_move = ChessMove::new(Square::E2, Square::C3, None);
+    ///
+    /// assert!(MoveGen::new_pseudo_legal(&board).any(|m| m == knight_move));
+    /// assert!(!board.is_legal_after_pseudo(knight_move));
+    ///
+    /// let legal = MoveGen::new_pseudo_legal(&board)
+    ///     .filter(|m| board.is_legal_after_pseudo(*m))
+    ///     .count();
+    /// assert_eq!(legal, MoveGen::new_legal(&board).len());
+    /// ```
+    #[inline(always)]
+    pub fn new_pseudo_legal(board: &Board) -> MoveGen {
+        let mask = !board.color_combined(board.side_to_move());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<PseudoLegalType>(
+            board, mask, mask,
+        ))
+    }
+
+    /// Create a new `MoveGen` structure, only generating legal captures (including en passant,
+    /// and promotions that capture).
+    ///
+    /// Together with `MoveGen::new_quiets()`, this generates every legal move exactly once.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, MoveGen, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").expect("Valid FEN");
+    ///
+    /// let captures = MoveGen::new_captures(&board).collect::<Vec<_>>();
+    /// assert_eq!(captures, vec![ChessMove::new(Square::E4, Square::D5, None)]);
+    /// assert_eq!(MoveGen::new_quiets(&board).len(), 6);
+    /// ```
+    #[inline(always)]
+    pub fn new_captures(board: &Board) -> MoveGen {
+        let mask = *board.color_combined(!board.side_to_move());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<CapturesType>(board, mask, mask))
+    }
+
+    /// Create a new `MoveGen` structure, only generating legal moves that do not capture
+    /// anything.  This includes castling, and promotions that do not capture.
+    #[inline(always)]
+    pub fn new_quiets(board: &Board) -> MoveGen {
+        let mask = !board.combined();
+        MoveGen::from_moves(MoveGen::enumerate_moves::<QuietsType>(board, mask, mask))
+    }
+
+    /// Create a new `MoveGen` structure, only generating the legal moves out of check.  If the
+    /// side to move is not in check, there are none.
+    ///
+    /// ```
+    /// use chess::{Board, MoveGen};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/8/8/8/8/3P1P2/r3K3 w - - 0 1").expect("Valid FEN");
+    /// assert_eq!(MoveGen::new_evasions(&board).len(), 1);
+    /// assert_eq!(MoveGen::new_evasions(&Board::default()).len(), 0);
+    /// ```
+    #[inline(always)]
+    pub fn new_evasions(board: &Board) -> MoveGen {
+        if *board.checkers() == EMPTY {
+            return MoveGen::from_moves(NoDrop::new(ArrayVec::new()));
+        }
+        let mask = !board.color_combined(board.side_to_move());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<AllMovesType>(board, mask, mask))
+    }
+
+    /// Create a new `MoveGen` structure, only generating legal moves that give check without
+    /// capturing or promoting.  This is handy for quiescence search.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, MoveGen, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1").expect("Valid FEN");
+    ///
+    /// let checks = MoveGen::new_quiet_checks(&board).collect::<Vec<_>>();
+    /// assert_eq!(checks.len(), 3);
+    /// assert!(checks.contains(&ChessMove::new(Square::A1, Square::A8, None)));
+    /// assert!(checks.contains(&ChessMove::new(Square::A1, Square::D1, None)));
+    /// assert!(checks.contains(&ChessMove::new(Square::E1, Square::C1, None)));
+    /// ```
+    #[inline(always)]
+    pub fn new_quiet_checks(board: &Board) -> MoveGen {
+        let mask = !board.combined();
+        let pawn_mask = mask & !get_rank(board.side_to_move().to_their_backrank());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<QuietChecksType>(
+            board, mask, pawn_mask,
+        ))
+    }
+
+    /// Create a new `MoveGen` structure, only generating legal promotions, whether or not they
+    /// capture.
+    ///
+    /// ```
+    /// use chess::{Board, MoveGen};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").expect("Valid FEN");
+    ///
+    /// // a7a8 and a7xb8, each to one of four pieces
+    /// assert_eq!(MoveGen::new_promotions(&board).len(), 8);
+    /// ```
+    #[inline(always)]
+    pub fn new_promotions(board: &Board) -> MoveGen {
+        let pawn_mask = !board.color_combined(board.side_to_move())
+            & get_rank(board.side_to_move().to_their_backrank());
+        MoveGen::from_moves(MoveGen::enumerate_moves::<PromotionsType>(
+            board, EMPTY, pawn_mask,
+        ))
+    }
+
     /// Never, ever, iterate any moves that land on the following squares
     pub fn remove_mask(&mut self, mask: BitBoard) {
         for x in 0..self.moves.len() {
@@ -183,7 +318,8 @@ impl MoveGen {
     /// incorrect results, and making that move on the `Board` will result in undefined behavior.
     /// This function may panic! if these rules are not followed.
     ///
-    /// If you are validating a move from a user, you should call the .legal() function.
+    /// If you are validating a move from a user, you should call the .legal() function.  For moves
+    /// from `MoveGen::new_pseudo_legal()`, call `Board::is_legal_after_pseudo()` instead.
     pub fn legal_quick(board: &Board, chess_move: ChessMove) -> bool {
         let piece = board.piece_on(chess_move.get_source()).unwrap();
         match piece {
@@ -203,7 +339,12 @@ impl MoveGen {
             }
             Piece::King => {
                 let bb = between(chess_move.get_source(), chess_move.get_dest());
-                if bb.popcnt() == 1 {
+                if board.is_chess960() {
+                    // castles are encoded as capturing my own rook, and are fully checked by
+                    // the move generator
+                    board.color_on(chess_move.get_dest()) == Some(board.side_to_move())
+                        || KingType::legal_king_move(board, chess_move.get_dest())
+                } else if bb.popcnt() == 1 {
                     // castles
                     if !KingType::legal_king_move(board, bb.to_square()) {
                         false
@@ -233,6 +374,22 @@ impl MoveGen {
         }
     }
 
+    #[cfg(test)]
+    /// Do a perft test with pseudo-legal moves, checking each one for legality
+    pub fn movegen_perft_test_pseudo_legal(board: &Board, depth: usize) -> usize {
+        let iterable = MoveGen::new_pseudo_legal(board).filter(|m| board.is_legal_after_pseudo(*m));
+
+        if depth == 1 {
+            iterable.count()
+        } else {
+            iterable
+                .map(|m| {
+                    MoveGen::movegen_perft_test_pseudo_legal(&board.make_move_new(m), depth - 1)
+                })
+                .sum()
+        }
+    }
+
     #[cfg(test)]
     /// Do a perft test after splitting the moves up into two groups
     pub fn movegen_perft_test_piecewise(board: &Board, depth: usize) -> usize {
@@ -353,6 +510,10 @@ fn movegen_perft_test(fen: String, depth: usize, result: usize) {
 
     assert_eq!(MoveGen::movegen_perft_test(&board, depth), result);
     assert_eq!(MoveGen::movegen_perft_test_piecewise(&board, depth), result);
+    assert_eq!(
+        MoveGen::movegen_perft_test_pseudo_legal(&board, depth),
+        result
+    );
 }
 
 #[test]
@@ -512,6 +673,42 @@ fn movegen_perft_26() {
     movegen_perft_test("8/5k2/8/5N2/5Q2/2K5/8/8 w - - 0 1".to_owned(), 4, 23527);
 }
 
+#[test]
+fn movegen_perft_chess960_1() {
+    movegen_perft_test(
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9".to_owned(),
+        4,
+        326672,
+    );
+}
+
+#[test]
+fn movegen_perft_chess960_2() {
+    movegen_perft_test(
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9".to_owned(),
+        4,
+        273318,
+    );
+}
+
+#[test]
+fn movegen_perft_chess960_3() {
+    movegen_perft_test(
+        "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9".to_owned(),
+        4,
+        732757,
+    );
+}
+
+#[test]
+fn movegen_perft_chess960_4() {
+    movegen_perft_test(
+        "qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9".to_owned(),
+        4,
+        465806,
+    );
+}
+
 #[test]
 fn movegen_issue_15() {
     let board =
@@ -567,3 +764,94 @@ fn test_masked_move_gen() {
         expected.into_iter().collect()
     );
 }
+
+#[cfg(test)]
+fn movegen_modes_perft(board: &Board, depth: usize) -> usize {
+    let collect = |movegen: MoveGen| {
+        let len = movegen.len();
+        let moves = movegen.collect::<HashSet<_>>();
+        assert_eq!(len, moves.len());
+        moves
+    };
+
+    let legal = collect(MoveGen::new_legal(board));
+    let captures = collect(MoveGen::new_captures(board));
+    let quiets = collect(MoveGen::new_quiets(board));
+    let evasions = collect(MoveGen::new_evasions(board));
+    let quiet_checks = collect(MoveGen::new_quiet_checks(board));
+    let promotions = collect(MoveGen::new_promotions(board));
+
+    let is_capture = |m: &ChessMove| {
+        board.color_on(m.get_dest()) == Some(!board.side_to_move())
+            || (board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                && m.get_source().get_file() != m.get_dest().get_file())
+    };
+    let gives_check = |m: &ChessMove| *board.make_move_new(*m).checkers() != EMPTY;
+
+    assert!(captures.is_disjoint(&quiets));
+    assert_eq!(&captures | &quiets, legal);
+    assert!(captures.iter().all(is_capture));
+    assert!(!quiets.iter().any(is_capture));
+    if *board.checkers() == EMPTY {
+        assert!(evasions.is_empty());
+    } else {
+        assert_eq!(evasions, legal);
+    }
+    assert_eq!(
+        quiet_checks,
+        quiets
+            .iter()
+            .filter(|m| m.get_promotion().is_none() && gives_check(m))
+            .cloned()
+            .collect()
+    );
+    assert_eq!(
+        promotions,
+        legal
+            .iter()
+            .filter(|m| m.get_promotion().is_some())
+            .cloned()
+            .collect()
+    );
+
+    if depth == 1 {
+        legal.len()
+    } else {
+        legal
+            .iter()
+            .map(|m| movegen_modes_perft(&board.make_move_new(*m), depth - 1))
+            .sum()
+    }
+}
+
+#[test]
+fn movegen_modes_partition_legal_moves() {
+    for (fen, depth, result) in [
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+            97862,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+            9467,
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            3,
+            62379,
+        ),
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            3,
+            12189,
+        ),
+    ]
+    .iter()
+    {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        assert_eq!(movegen_modes_perft(&board, *depth), *result);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::bitboard::{BitBoard, EMPTY};
 use crate::board::Board;
 use crate::color::Color;
+use crate::file::File;
 use crate::movegen::{MoveList, SquareAndBitBoard};
 use crate::piece::Piece;
 use crate::square::Square;
@@ -16,10 +17,18 @@ pub trait PieceType {
     fn into_piece() -> Piece;
     #[inline(always)]
     fn pseudo_legals(src: Square, color: Color, combined: BitBoard, mask: BitBoard) -> BitBoard;
+
+    /// The squares from which a piece of `color` would attack a king on `ksq`.
     #[inline(always)]
-    fn legals<T>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
+    fn check_squares(ksq: Square, color: Color, combined: BitBoard) -> BitBoard {
+        Self::pseudo_legals(ksq, color, combined, !EMPTY)
+    }
+
+    #[inline(always)]
+    fn legals<T, G>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
     where
         T: CheckType,
+        G: GenType,
     {
         let combined = board.combined();
         let color = board.side_to_move();
@@ -30,14 +39,17 @@ pub trait PieceType {
         let pinned = board.pinned();
         let checkers = board.checkers();
 
-        let check_mask = if T::IN_CHECK {
+        let check_mask = if T::IN_CHECK && G::LEGAL {
             between(checkers.to_square(), ksq) ^ checkers
         } else {
             !EMPTY
         };
+        let unpinned = if G::LEGAL { pieces & !pinned } else { pieces };
+        let checks = GivesCheck::new::<Self, G>(board);
 
-        for src in pieces & !pinned {
-            let moves = Self::pseudo_legals(src, color, *combined, mask) & check_mask;
+        for src in unpinned {
+            let moves =
+                Self::pseudo_legals(src, color, *combined, mask) & check_mask & checks.mask(src);
             if moves != EMPTY {
                 unsafe {
                     movelist.push_unchecked(SquareAndBitBoard::new(src, moves, false));
@@ -45,9 +57,11 @@ pub trait PieceType {
             }
         }
 
-        if !T::IN_CHECK {
+        if !T::IN_CHECK && G::LEGAL {
             for src in pieces & pinned {
-                let moves = Self::pseudo_legals(src, color, *combined, mask) & line(src, ksq);
+                let moves = Self::pseudo_legals(src, color, *combined, mask)
+                    & line(src, ksq)
+                    & checks.mask(src);
                 if moves != EMPTY {
                     unsafe {
                         movelist.push_unchecked(SquareAndBitBoard::new(src, moves, false));
@@ -80,6 +94,128 @@ impl CheckType for NotInCheckType {
     const IN_CHECK: bool = false;
 }
 
+/// Which kinds of moves should `PieceType::legals` generate, on top of the destination mask it
+/// is given?
+pub trait GenType {
+    /// Only generate legal moves.  Otherwise, pins and checks are ignored, except when castling.
+    const LEGAL: bool;
+    /// Generate en passant captures.
+    const EN_PASSANT: bool;
+    /// Generate castling moves.
+    const CASTLES: bool;
+    /// Only generate moves that give check.
+    const CHECKS: bool;
+}
+
+/// Every move.
+pub struct AllMovesType;
+/// Captures, including en passant.
+pub struct CapturesType;
+/// Moves that do not capture, including castling.
+pub struct QuietsType;
+/// Moves that do not capture, but do give check.
+pub struct QuietChecksType;
+/// Pawn moves to the last rank.
+pub struct PromotionsType;
+/// Every pseudo-legal move.
+pub struct PseudoLegalType;
+
+impl GenType for AllMovesType {
+    const LEGAL: bool = true;
+    const EN_PASSANT: bool = true;
+    const CASTLES: bool = true;
+    const CHECKS: bool = false;
+}
+
+impl GenType for CapturesType {
+    const LEGAL: bool = true;
+    const EN_PASSANT: bool = true;
+    const CASTLES: bool = false;
+    const CHECKS: bool = false;
+}
+
+impl GenType for QuietsType {
+    const LEGAL: bool = true;
+    const EN_PASSANT: bool = false;
+    const CASTLES: bool = true;
+    const CHECKS: bool = false;
+}
+
+impl GenType for QuietChecksType {
+    const LEGAL: bool = true;
+    const EN_PASSANT: bool = false;
+    const CASTLES: bool = true;
+    const CHECKS: bool = true;
+}
+
+impl GenType for PromotionsType {
+    const LEGAL: bool = true;
+    const EN_PASSANT: bool = false;
+    const CASTLES: bool = false;
+    const CHECKS: bool = false;
+}
+
+impl GenType for PseudoLegalType {
+    const LEGAL: bool = false;
+    const EN_PASSANT: bool = true;
+    const CASTLES: bool = true;
+    const CHECKS: bool = false;
+}
+
+/// The destinations that give check for each of my pieces of one type, when only generating
+/// checks.  Otherwise, every destination is allowed.
+pub struct GivesCheck {
+    their_ksq: Square,
+    direct: BitBoard,
+    discovered: BitBoard,
+}
+
+impl GivesCheck {
+    #[inline(always)]
+    pub fn new<P: PieceType + ?Sized, G: GenType>(board: &Board) -> GivesCheck {
+        let color = board.side_to_move();
+        let their_ksq = board.king_square(!color);
+        if !G::CHECKS {
+            return GivesCheck {
+                their_ksq,
+                direct: !EMPTY,
+                discovered: EMPTY,
+            };
+        }
+
+        // my pieces that are the only thing between one of my sliders and their king
+        let my_pieces = board.color_combined(color);
+        let snipers = my_pieces
+            & ((get_bishop_rays(their_ksq)
+                & (board.pieces(Piece::Bishop) | board.pieces(Piece::Queen)))
+                | (get_rook_rays(their_ksq)
+                    & (board.pieces(Piece::Rook) | board.pieces(Piece::Queen))));
+        let mut discovered = EMPTY;
+        for sq in snipers {
+            let between = between(sq, their_ksq) & board.combined();
+            if between.popcnt() == 1 {
+                discovered |= between & my_pieces;
+            }
+        }
+
+        GivesCheck {
+            their_ksq,
+            direct: P::check_squares(their_ksq, color, *board.combined()),
+            discovered,
+        }
+    }
+
+    /// Which destinations of the piece on `src` give check?
+    #[inline(always)]
+    pub fn mask(&self, src: Square) -> BitBoard {
+        if self.discovered & BitBoard::from_square(src) != EMPTY {
+            self.direct | !line(src, self.their_ksq)
+        } else {
+            self.direct
+        }
+    }
+}
+
 impl PawnType {
     /// Is a particular en-passant capture legal?
     pub fn legal_ep_move(board: &Board, source: Square, dest: Square) -> bool {
@@ -128,9 +264,15 @@ impl PieceType for PawnType {
     }
 
     #[inline(always)]
-    fn legals<T>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
+    fn check_squares(ksq: Square, color: Color, _combined: BitBoard) -> BitBoard {
+        get_pawn_attacks(ksq, !color, !EMPTY)
+    }
+
+    #[inline(always)]
+    fn legals<T, G>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
     where
         T: CheckType,
+        G: GenType,
     {
         let combined = board.combined();
         let color = board.side_to_move();
@@ -141,14 +283,17 @@ impl PieceType for PawnType {
         let pinned = board.pinned();
         let checkers = board.checkers();
 
-        let check_mask = if T::IN_CHECK {
+        let check_mask = if T::IN_CHECK && G::LEGAL {
             between(checkers.to_square(), ksq) ^ checkers
         } else {
             !EMPTY
         };
+        let unpinned = if G::LEGAL { pieces & !pinned } else { pieces };
+        let checks = GivesCheck::new::<Self, G>(board);
 
-        for src in pieces & !pinned {
-            let moves = Self::pseudo_legals(src, color, *combined, mask) & check_mask;
+        for src in unpinned {
+            let moves =
+                Self::pseudo_legals(src, color, *combined, mask) & check_mask & checks.mask(src);
             if moves != EMPTY {
                 unsafe {
                     movelist.push_unchecked(SquareAndBitBoard::new(
@@ -160,9 +305,11 @@ impl PieceType for PawnType {
             }
         }
 
-        if !T::IN_CHECK {
+        if !T::IN_CHECK && G::LEGAL {
             for src in pieces & pinned {
-                let moves = Self::pseudo_legals(src, color, *combined, mask) & line(ksq, src);
+                let moves = Self::pseudo_legals(src, color, *combined, mask)
+                    & line(ksq, src)
+                    & checks.mask(src);
                 if moves != EMPTY {
                     unsafe {
                         movelist.push_unchecked(SquareAndBitBoard::new(
@@ -175,13 +322,13 @@ impl PieceType for PawnType {
             }
         }
 
-        if board.en_passant().is_some() {
+        if G::EN_PASSANT && board.en_passant().is_some() {
             let ep_sq = board.en_passant().unwrap();
             let rank = get_rank(ep_sq.get_rank());
             let files = get_adjacent_files(ep_sq.get_file());
             for src in rank & files & pieces {
                 let dest = ep_sq.uforward(color);
-                if PawnType::legal_ep_move(board, src, dest) {
+                if !G::LEGAL || PawnType::legal_ep_move(board, src, dest) {
                     unsafe {
                         movelist.push_unchecked(SquareAndBitBoard::new(
                             src,
@@ -225,9 +372,10 @@ impl PieceType for KnightType {
     }
 
     #[inline(always)]
-    fn legals<T>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
+    fn legals<T, G>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
     where
         T: CheckType,
+        G: GenType,
     {
         let combined = board.combined();
         let color = board.side_to_move();
@@ -237,12 +385,27 @@ impl PieceType for KnightType {
         let pieces = board.pieces(Self::into_piece()) & my_pieces;
         let pinned = board.pinned();
         let checkers = board.checkers();
+        let checks = GivesCheck::new::<Self, G>(board);
 
-        if T::IN_CHECK {
+        if !G::LEGAL {
+            for src in pieces {
+                let moves = Self::pseudo_legals(src, color, *combined, mask);
+                if moves != EMPTY {
+                    unsafe {
+                        movelist.push_unchecked(SquareAndBitBoard::new(src, moves, false));
+                    }
+                }
+            }
+        } else if T::IN_CHECK {
             let check_mask = between(checkers.to_square(), ksq) ^ checkers;
 
             for src in pieces & !pinned {
-                let moves = Self::pseudo_legals(src, color, *combined, mask & check_mask);
+                let moves = Self::pseudo_legals(
+                    src,
+                    color,
+                    *combined,
+                    mask & check_mask & checks.mask(src),
+                );
                 if moves != EMPTY {
                     unsafe {
                         movelist.push_unchecked(SquareAndBitBoard::new(src, moves, false));
@@ -251,7 +414,7 @@ impl PieceType for KnightType {
             }
         } else {
             for src in pieces & !pinned {
-                let moves = Self::pseudo_legals(src, color, *combined, mask);
+                let moves = Self::pseudo_legals(src, color, *combined, mask & checks.mask(src));
                 if moves != EMPTY {
                     unsafe {
                         movelist.push_unchecked(SquareAndBitBoard::new(src, moves, false));
@@ -300,6 +463,12 @@ impl KingType {
             ^ (board.pieces(Piece::King) & board.color_combined(board.side_to_move()))
             | BitBoard::from_square(dest);
 
+        KingType::safe_square(board, dest, combined)
+    }
+
+    /// Is `dest` free from attack by my opponent, given the occupied squares `combined`?
+    #[inline(always)]
+    fn safe_square(board: &Board, dest: Square, combined: BitBoard) -> bool {
         let mut attackers = EMPTY;
 
         let rooks = (board.pieces(Piece::Rook) | board.pieces(Piece::Queen))
@@ -328,6 +497,70 @@ impl KingType {
 
         return attackers == EMPTY;
     }
+
+    /// Can I castle with the rook on `rook_file`, in Chess960?  The king ends up on `king_dest`,
+    /// and the rook on `rook_dest`.
+    ///
+    /// Every square the king or rook travel through must be empty (other than the king and rook
+    /// themselves), and every square the king travels through must be safe once both the king
+    /// and rook are lifted off the board.
+    pub fn legal_chess960_castle(
+        board: &Board,
+        rook_file: File,
+        king_dest: File,
+        rook_dest: File,
+    ) -> bool {
+        let color = board.side_to_move();
+        let backrank = color.to_my_backrank();
+        let ksq = board.king_square(color);
+        let rsq = Square::make_square(backrank, rook_file);
+        let kdest = Square::make_square(backrank, king_dest);
+        let rdest = Square::make_square(backrank, rook_dest);
+
+        let combined = board.combined() ^ BitBoard::from_square(ksq) ^ BitBoard::from_square(rsq);
+
+        let king_path = between(ksq, kdest) | BitBoard::from_square(kdest);
+        let rook_path = between(rsq, rdest) | BitBoard::from_square(rdest);
+        if (king_path | rook_path) & combined != EMPTY {
+            return false;
+        }
+
+        (king_path | BitBoard::from_square(ksq))
+            .into_iter()
+            .all(|sq| KingType::safe_square(board, sq, combined))
+    }
+
+    /// Does castling with the rook on `rook_file` give check?  The king ends up on `king_dest`,
+    /// and the rook on `rook_dest`.
+    fn castle_gives_check(
+        board: &Board,
+        rook_file: File,
+        king_dest: File,
+        rook_dest: File,
+    ) -> bool {
+        let color = board.side_to_move();
+        let backrank = color.to_my_backrank();
+        let ksq = board.king_square(color);
+        let rsq = Square::make_square(backrank, rook_file);
+        let rdest = Square::make_square(backrank, rook_dest);
+
+        let combined = board.combined() ^ BitBoard::from_square(ksq) ^ BitBoard::from_square(rsq)
+            | BitBoard::set(backrank, king_dest)
+            | BitBoard::from_square(rdest);
+
+        get_rook_moves(rdest, combined) & BitBoard::from_square(board.king_square(!color)) != EMPTY
+    }
+
+    /// May I castle with the rook on `rook_file`, given which moves I am generating?
+    #[inline(always)]
+    fn wanted_castle<G: GenType>(
+        board: &Board,
+        rook_file: File,
+        king_dest: File,
+        rook_dest: File,
+    ) -> bool {
+        !G::CHECKS || KingType::castle_gives_check(board, rook_file, king_dest, rook_dest)
+    }
 }
 
 impl PieceType for KingType {
@@ -345,20 +578,29 @@ impl PieceType for KingType {
     }
 
     #[inline(always)]
-    fn legals<T>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
+    fn check_squares(_ksq: Square, _color: Color, _combined: BitBoard) -> BitBoard {
+        EMPTY
+    }
+
+    #[inline(always)]
+    fn legals<T, G>(movelist: &mut MoveList, board: &Board, mask: BitBoard)
     where
         T: CheckType,
+        G: GenType,
     {
         let combined = board.combined();
         let color = board.side_to_move();
         let ksq = board.king_square(color);
 
-        let mut moves = Self::pseudo_legals(ksq, color, *combined, mask);
+        let checks = GivesCheck::new::<Self, G>(board);
+        let mut moves = Self::pseudo_legals(ksq, color, *combined, mask & checks.mask(ksq));
 
-        let copy = moves;
-        for dest in copy {
-            if !KingType::legal_king_move(board, dest) {
-                moves ^= BitBoard::from_square(dest);
+        if G::LEGAL {
+            let copy = moves;
+            for dest in copy {
+                if !KingType::legal_king_move(board, dest) {
+                    moves ^= BitBoard::from_square(dest);
+                }
             }
         }
 
@@ -370,7 +612,25 @@ impl PieceType for KingType {
         //    destination square.
         //  ** This is determined by going to the left or right, and calling
         //     'legal_king_move' for that square.
-        if !T::IN_CHECK {
+        if G::CASTLES && !T::IN_CHECK && board.is_chess960() {
+            // In Chess960, castling is encoded as the king capturing its own rook.
+            let backrank = color.to_my_backrank();
+            let (kingside, queenside) = board.castle_rook_files(color);
+            if board.my_castle_rights().has_kingside()
+                && KingType::legal_chess960_castle(board, kingside, File::G, File::F)
+                && KingType::wanted_castle::<G>(board, kingside, File::G, File::F)
+            {
+                moves |= BitBoard::set(backrank, kingside);
+            }
+
+            if board.my_castle_rights().has_queenside()
+                && KingType::legal_chess960_castle(board, queenside, File::C, File::D)
+                && KingType::wanted_castle::<G>(board, queenside, File::C, File::D)
+            {
+                moves |= BitBoard::set(backrank, queenside);
+            }
+        } else if G::CASTLES && !T::IN_CHECK {
+            let (kingside, queenside) = board.castle_rook_files(color);
             if board.my_castle_rights().has_kingside()
                 && (combined & board.my_castle_rights().kingside_squares(color)) == EMPTY
             {
@@ -378,6 +638,7 @@ impl PieceType for KingType {
                 let right = middle.uright();
                 if KingType::legal_king_move(board, middle)
                     && KingType::legal_king_move(board, right)
+                    && KingType::wanted_castle::<G>(board, kingside, File::G, File::F)
                 {
                     moves ^= BitBoard::from_square(right);
                 }
@@ -390,6 +651,7 @@ impl PieceType for KingType {
                 let left = middle.uleft();
                 if KingType::legal_king_move(board, middle)
                     && KingType::legal_king_move(board, left)
+                    && KingType::wanted_castle::<G>(board, queenside, File::C, File::D)
                 {
                     moves ^= BitBoard::from_square(left);
                 }
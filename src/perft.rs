@@ -0,0 +1,518 @@
+use crate::board::Board;
+use crate::cache_table::CacheTable;
+use crate::chess_move::ChessMove;
+use crate::epd::{Epd, EpdValue};
+use crate::error::Error;
+use crate::movegen::MoveGen;
+use crate::variant_board::VariantBoard;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+/// Count the leaf nodes of the legal move tree `depth` plies deep.
+///
+/// ```
+/// use chess::{perft, Board};
+///
+/// assert_eq!(perft(&Board::default(), 0), 1);
+/// assert_eq!(perft(&Board::default(), 3), 8902);
+/// ```
+pub fn perft(board: &Board, depth: usize) -> usize {
+    if depth == 0 {
+        1
+    } else {
+        MoveGen::movegen_perft_test(board, depth)
+    }
+}
+
+/// Count the leaf nodes under each legal move, `depth` plies deep (including the move itself).
+///
+/// Comparing this against another move generator quickly points at the move that is being
+/// generated wrongly.
+///
+/// ```
+/// use chess::{perft_divide, Board, ChessMove, Square};
+///
+/// let divide = perft_divide(&Board::default(), 3);
+///
+/// assert_eq!(divide.len(), 20);
+/// assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<usize>(), 8902);
+/// assert!(divide.contains(&(ChessMove::new(Square::E2, Square::E4, None), 600)));
+/// ```
+pub fn perft_divide(board: &Board, depth: usize) -> Vec<(ChessMove, usize)> {
+    if depth == 0 {
+        return vec![];
+    }
+    MoveGen::new_legal(board)
+        .map(|m| (m, perft(&board.make_move_new(m), depth - 1)))
+        .collect()
+}
+
+/// Like `perft()`, but remember the node count of every position visited in `cache`, keyed by
+/// `Board::get_hash()` and the remaining depth, so that transpositions are only counted once.
+///
+/// ```
+/// use chess::{perft_hashed, Board, CacheTable};
+///
+/// let mut cache = CacheTable::new(1 << 16, 0);
+/// assert_eq!(perft_hashed(&Board::default(), 4, &mut cache), 197281);
+/// ```
+pub fn perft_hashed(board: &Board, depth: usize, cache: &mut CacheTable<usize>) -> usize {
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+
+    // mix the depth in, so the same position at different depths has different keys
+    let key = board.get_hash() ^ (depth as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    if let Some(nodes) = cache.get(key) {
+        return nodes;
+    }
+
+    let nodes = MoveGen::new_legal(board)
+        .map(|m| perft_hashed(&board.make_move_new(m), depth - 1, cache))
+        .sum();
+    cache.add(key, nodes);
+    nodes
+}
+
+/// Like `perft()`, but split the legal moves of `board` across `threads` threads.
+///
+/// ```
+/// use chess::{perft_parallel, Board};
+///
+/// assert_eq!(perft_parallel(&Board::default(), 4, 4), 197281);
+/// ```
+pub fn perft_parallel(board: &Board, depth: usize, threads: usize) -> usize {
+    if depth <= 1 || threads <= 1 {
+        return perft(board, depth);
+    }
+
+    let moves = MoveGen::new_legal(board).collect::<Vec<_>>();
+    thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|thread| {
+                let moves = &moves;
+                scope.spawn(move || {
+                    moves
+                        .iter()
+                        .skip(thread)
+                        .step_by(threads)
+                        .map(|m| perft(&board.make_move_new(*m), depth - 1))
+                        .sum::<usize>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Perft thread panicked"))
+            .sum()
+    })
+}
+
+/// Like `perft()`, but following the rules of the variant of `board`.  Positions where the game
+/// has ended have no moves, and are not counted below `depth`.
+///
+/// ```
+/// use chess::{perft_variant, Variant, VariantBoard};
+///
+/// assert_eq!(perft_variant(&VariantBoard::new(Variant::Standard), 3), 8902);
+/// assert_eq!(perft_variant(&VariantBoard::new(Variant::Antichess), 3), 8067);
+/// ```
+pub fn perft_variant(board: &VariantBoard, depth: usize) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves();
+    if depth == 1 {
+        moves.len()
+    } else {
+        moves
+            .iter()
+            .map(|m| perft_variant(&board.make_move_new(*m), depth - 1))
+            .sum()
+    }
+}
+
+/// One position of a `PerftSuite`, with the expected node counts at each depth.
+#[derive(Clone, Debug)]
+pub struct PerftPosition {
+    fen: String,
+    board: Board,
+    expected: Vec<(usize, usize)>,
+}
+
+impl PerftPosition {
+    /// The position, as it was written in the suite.
+    pub fn fen(&self) -> &str {
+        &self.fen
+    }
+
+    /// The position.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The expected node counts, as `(depth, nodes)` pairs in the order they were written.
+    pub fn expected(&self) -> &[(usize, usize)] {
+        &self.expected
+    }
+}
+
+/// A node count from a `PerftSuite` that did not match the move generator.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PerftMismatch {
+    fen: String,
+    depth: usize,
+    expected: usize,
+    actual: usize,
+}
+
+impl PerftMismatch {
+    /// The position, as it was written in the suite.
+    pub fn fen(&self) -> &str {
+        &self.fen
+    }
+
+    /// The depth of the count.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The count the suite expected.
+    pub fn expected(&self) -> usize {
+        self.expected
+    }
+
+    /// The count the move generator gave.
+    pub fn actual(&self) -> usize {
+        self.actual
+    }
+}
+
+impl fmt::Display for PerftMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {} nodes at depth {}, found {}",
+            self.fen, self.expected, self.depth, self.actual
+        )
+    }
+}
+
+/// A perft test suite in the usual EPD style, with one position per line followed by the
+/// expected node counts:
+///
+/// ```text
+/// rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400 ;D3 8902
+/// ```
+///
+/// Blank lines, and lines starting with `#`, are skipped.
+///
+/// ```
+/// use chess::PerftSuite;
+/// use std::str::FromStr;
+///
+/// let suite = PerftSuite::from_str(
+///     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400 ;D3 8902\n\
+///      4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D2 66",
+/// )
+/// .expect("Valid suite");
+///
+/// let mismatches = suite.run(3);
+/// assert_eq!(mismatches.len(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PerftSuite {
+    positions: Vec<PerftPosition>,
+}
+
+impl PerftSuite {
+    /// The positions in the suite.
+    pub fn positions(&self) -> &[PerftPosition] {
+        &self.positions
+    }
+
+    /// Check every expected count up to `max_depth` plies deep, and report each one that the
+    /// move generator disagrees with.
+    pub fn run(&self, max_depth: usize) -> Vec<PerftMismatch> {
+        let mut mismatches = vec![];
+        for position in self.positions.iter() {
+            for &(depth, expected) in position.expected.iter() {
+                if depth > max_depth {
+                    continue;
+                }
+                let actual = perft(&position.board, depth);
+                if actual != expected {
+                    mismatches.push(PerftMismatch {
+                        fen: position.fen.clone(),
+                        depth,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+impl FromStr for PerftSuite {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut positions = vec![];
+        for (index, line) in value.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: String| Error::InvalidPerftSuite {
+                line: index + 1,
+                message,
+            };
+
+            let epd = match Epd::from_str(line) {
+                Ok(epd) => epd,
+                Err(Error::InvalidEpd { message, .. }) => return Err(invalid(message)),
+                Err(e) => return Err(e),
+            };
+
+            let mut expected = vec![];
+            for (opcode, value) in epd.operations().iter() {
+                let depth = opcode
+                    .strip_prefix('D')
+                    .and_then(|depth| depth.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        invalid(format!("expected a depth like D1, found {}", opcode))
+                    })?;
+                let nodes = match value {
+                    EpdValue::Integer(nodes) => usize::try_from(*nodes).ok(),
+                    _ => None,
+                }
+                .ok_or_else(|| invalid(format!("expected a node count for {}", opcode)))?;
+                expected.push((depth, nodes));
+            }
+
+            let fen = line.split(';').next().unwrap_or("").trim();
+            positions.push(PerftPosition {
+                fen: fen.to_string(),
+                board: epd.board(),
+                expected,
+            });
+        }
+        Ok(PerftSuite { positions })
+    }
+}
+
+#[test]
+fn test_perft_hashed_and_parallel() {
+    let board =
+        Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .expect("Valid FEN");
+    let mut cache = CacheTable::new(1 << 16, 0);
+
+    assert_eq!(perft(&board, 4), 4085603);
+    assert_eq!(perft_hashed(&board, 4, &mut cache), 4085603);
+    // a second run comes straight from the cache
+    assert_eq!(perft_hashed(&board, 4, &mut cache), 4085603);
+    assert_eq!(perft_parallel(&board, 4, 3), 4085603);
+    assert_eq!(perft_parallel(&board, 4, 100), 4085603);
+    assert_eq!(
+        perft_divide(&board, 4)
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum::<usize>(),
+        4085603
+    );
+}
+
+#[test]
+fn test_perft_suite() {
+    let suite = PerftSuite::from_str(
+        "# a comment\n\
+         \n\
+         r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ;D1 48 ;D2 2039 ;D3 97862\n\
+         8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - ;D1 14 ;D2 191 ;D3 2813 ;D4 43238\n\
+         bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9 ;D1 21 ;D2 528 ;D3 12189\n",
+    )
+    .expect("Valid suite");
+
+    assert_eq!(suite.positions().len(), 3);
+    assert_eq!(suite.positions()[1].expected().len(), 4);
+
+    let mismatches = suite.run(3);
+    assert_eq!(
+        mismatches,
+        vec![PerftMismatch {
+            fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -".to_string(),
+            depth: 3,
+            expected: 2813,
+            actual: 2812,
+        }]
+    );
+    assert_eq!(
+        mismatches[0].to_string(),
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -: expected 2813 nodes at depth 3, found 2812"
+    );
+}
+
+#[test]
+fn test_perft_suite_invalid() {
+    match PerftSuite::from_str("# header\n4k3/8/8/8/8/8/8/4K3 w - - ;D1 5 ;2 10") {
+        Err(Error::InvalidPerftSuite { line, .. }) => assert_eq!(line, 2),
+        _ => panic!("Expected an invalid suite"),
+    }
+    assert!(PerftSuite::from_str("not a fen ;D1 20").is_err());
+    match PerftSuite::from_str("4k3/8/8/8/8/8/8/4K3 w - - ;D1 five") {
+        Err(Error::InvalidPerftSuite { line, message }) => {
+            assert_eq!(line, 1);
+            assert_eq!(message, "expected an integer for D1");
+        }
+        _ => panic!("Expected an invalid suite"),
+    }
+}
+
+#[cfg(test)]
+use crate::variant::Variant;
+
+#[test]
+fn test_perft_variant_start() {
+    let expected: [(Variant, &[usize]); 6] = [
+        (Variant::Standard, &[20, 400, 8902, 197281]),
+        (Variant::Atomic, &[20, 400, 8902, 197326]),
+        (Variant::KingOfTheHill, &[20, 400, 8902, 197281]),
+        (Variant::ThreeCheck, &[20, 400, 8902, 197281]),
+        (Variant::Antichess, &[20, 400, 8067, 153299]),
+        (Variant::Horde, &[8, 128, 1274, 23310, 265223]),
+    ];
+    for (variant, counts) in expected.iter() {
+        let board = VariantBoard::new(*variant);
+        for (depth, count) in counts.iter().enumerate() {
+            assert_eq!(
+                perft_variant(&board, depth + 1),
+                *count,
+                "{} {}",
+                variant,
+                depth + 1
+            );
+        }
+    }
+}
+
+#[test]
+fn test_perft_variant_reference() {
+    // from the variant perft suites of python-chess
+    let published: [(Variant, &str, &[usize]); 13] = [
+        (
+            Variant::Atomic,
+            "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1",
+            &[40, 1238, 45237, 1434825],
+        ),
+        (
+            Variant::Atomic,
+            "rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1",
+            &[28, 833, 23353, 714499],
+        ),
+        (
+            Variant::ThreeCheck,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1 0 1",
+            &[48, 2039, 97848],
+        ),
+        (
+            Variant::ThreeCheck,
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 1+1 0 1",
+            &[26, 562, 13410],
+        ),
+        // neither king can reach the hill, nor either side give three checks, within four plies,
+        // so these are the published counts of standard chess
+        (
+            Variant::KingOfTheHill,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197281],
+        ),
+        (
+            Variant::KingOfTheHill,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862, 4085603],
+        ),
+        (
+            Variant::ThreeCheck,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            &[20, 400, 8902, 197281],
+        ),
+        (
+            Variant::ThreeCheck,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3+3 0 1",
+            &[48, 2039, 97862, 4085603],
+        ),
+        (
+            Variant::KingOfTheHill,
+            "8/2k5/3q4/3K4/8/8/8/8 w - - 0 1",
+            &[0],
+        ),
+        (
+            Variant::Antichess,
+            "8/1p6/8/8/8/8/P7/8 w - - 0 1",
+            &[2, 4, 4, 3, 1, 0],
+        ),
+        (
+            Variant::Antichess,
+            "8/2p5/8/8/8/8/P7/8 w - - 0 1",
+            &[2, 4, 4, 4, 4, 4, 4, 4, 12, 36, 312, 2557, 30873],
+        ),
+        (
+            Variant::Horde,
+            "4k3/pp4q1/3P2p1/8/P3PP2/PPP2r2/PPP5/PPPP4 b - - 0 1",
+            &[30, 241, 6633, 56539],
+        ),
+        (
+            Variant::Horde,
+            "k7/5p2/4p2P/3p2P1/2p2P2/1p2P2P/p2P2P1/2P2P2 w - - 0 1",
+            &[13, 172, 2205, 33781],
+        ),
+    ];
+
+    // counted by hand, as a check on the rules the positions above do not reach
+    let counted: [(Variant, &str, &[usize]); 4] = [
+        // Kd4 and Ke4 reach the hill, so black has no replies to them
+        (
+            Variant::KingOfTheHill,
+            "4k3/8/8/8/8/4K3/8/8 w - - 0 1",
+            &[8, 30],
+        ),
+        // the same with black to move: Kd5 and Ke5 win
+        (
+            Variant::KingOfTheHill,
+            "8/8/4k3/8/8/8/8/4K3 b - - 0 1",
+            &[8, 30],
+        ),
+        // white has one check left, so Rh8+ wins, and its three replies are not counted
+        (
+            Variant::ThreeCheck,
+            "4k3/8/8/8/8/8/8/4K2R w K - 1+3 0 1",
+            &[15, 63],
+        ),
+        // black has given two checks already, and Ra1+ or Re2+ would be the third
+        (
+            Variant::ThreeCheck,
+            "8/8/8/8/8/2k5/r7/4K3 b - - 3+1 0 1",
+            &[21, 53],
+        ),
+    ];
+
+    for (variant, fen, counts) in published.iter().chain(counted.iter()) {
+        let board = VariantBoard::from_fen(*variant, fen).expect("Valid position");
+        for (depth, count) in counts.iter().enumerate() {
+            assert_eq!(
+                perft_variant(&board, depth + 1),
+                *count,
+                "{} {}",
+                fen,
+                depth + 1
+            );
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod reader;
+pub use self::reader::*;
+
+mod writer;
+pub use self::writer::*;
@@ -0,0 +1,908 @@
+use crate::board::Board;
+use crate::board_builder::BoardBuilder;
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+use crate::game::Game;
+
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// A single move from the movetext of a PGN game, along with everything attached to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnNode {
+    chess_move: ChessMove,
+    san: String,
+    nags: Vec<u8>,
+    comments: Vec<String>,
+    commands: Vec<(String, String)>,
+    variations: Vec<PgnVariation>,
+}
+
+impl PgnNode {
+    pub(crate) fn new(
+        chess_move: ChessMove,
+        san: String,
+        nags: Vec<u8>,
+        comments: Vec<String>,
+        commands: Vec<(String, String)>,
+        variations: Vec<PgnVariation>,
+    ) -> PgnNode {
+        PgnNode {
+            chess_move,
+            san,
+            nags,
+            comments,
+            commands,
+            variations,
+        }
+    }
+
+    /// The move that was played.
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// The move exactly as it was written in the PGN, without any `!` or `?` suffix.
+    pub fn san(&self) -> &str {
+        &self.san
+    }
+
+    /// The numeric annotation glyphs attached to this move.  Suffix annotations are converted to
+    /// their NAG equivalent, so `!` is `1`, `?` is `2`, `!!` is `3`, `??` is `4`, `!?` is `5` and
+    /// `?!` is `6`.
+    pub fn nags(&self) -> &[u8] {
+        &self.nags
+    }
+
+    /// The comments following this move, with any `[%command value]` annotations removed.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// The `[%command value]` annotations found in the comments following this move, such as
+    /// `("clk", "0:03:21")`.
+    pub fn commands(&self) -> &[(String, String)] {
+        &self.commands
+    }
+
+    /// Give me the value of the first `[%command value]` annotation named `name`.
+    ///
+    /// ```
+    /// use chess::PgnGame;
+    /// use std::str::FromStr;
+    ///
+    /// let pgn = PgnGame::from_str("1. e4 { [%clk 0:03:00] Best by test } e5 *").expect("Valid PGN");
+    /// let e4 = &pgn.mainline().nodes()[0];
+    /// assert_eq!(e4.command("clk"), Some("0:03:00"));
+    /// assert_eq!(e4.comments(), ["Best by test"]);
+    /// assert_eq!(e4.command("eval"), None);
+    /// ```
+    pub fn command(&self, name: &str) -> Option<&str> {
+        self.commands
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The alternatives to this move, in the order they appeared in the PGN.
+    pub fn variations(&self) -> &[PgnVariation] {
+        &self.variations
+    }
+
+    fn add_comment(&mut self, text: &str) {
+        split_comment(text, &mut self.comments, &mut self.commands);
+    }
+}
+
+/// A line of play.  This is either the mainline of a game, or one of the alternatives found
+/// inside `( )`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PgnVariation {
+    comments: Vec<String>,
+    commands: Vec<(String, String)>,
+    nodes: Vec<PgnNode>,
+}
+
+impl PgnVariation {
+    pub(crate) fn new(
+        comments: Vec<String>,
+        commands: Vec<(String, String)>,
+        nodes: Vec<PgnNode>,
+    ) -> PgnVariation {
+        PgnVariation {
+            comments,
+            commands,
+            nodes,
+        }
+    }
+
+    /// The comments that appear before the first move of this line.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// The `[%command value]` annotations that appear before the first move of this line.
+    pub fn commands(&self) -> &[(String, String)] {
+        &self.commands
+    }
+
+    /// The moves in this line.
+    pub fn nodes(&self) -> &[PgnNode] {
+        &self.nodes
+    }
+
+    fn add_comment(&mut self, text: &str) {
+        split_comment(text, &mut self.comments, &mut self.commands);
+    }
+}
+
+/// A game read from PGN.
+///
+/// The mainline is available as a `Game`, while the full movetext, including comments, NAGs and
+/// variations, is available through `mainline()`.
+///
+/// ```
+/// use chess::{PgnGame, Square};
+/// use std::str::FromStr;
+///
+/// let pgn = PgnGame::from_str(r#"
+/// [Event "Casual Game"]
+/// [White "Anderssen"]
+///
+/// 1. e4 e5 (1... c5 2. Nf3) 2. f4 $1 exf4 *
+/// "#).expect("Valid PGN");
+///
+/// assert_eq!(pgn.tag("White"), Some("Anderssen"));
+/// assert_eq!(pgn.result(), Some("*"));
+/// assert_eq!(pgn.game().actions().len(), 4);
+///
+/// let e5 = &pgn.mainline().nodes()[1];
+/// assert_eq!(e5.variations()[0].nodes()[0].san(), "c5");
+/// assert_eq!(pgn.mainline().nodes()[2].nags(), [1]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    tags: Vec<(String, String)>,
+    game: Game,
+    mainline: PgnVariation,
+    result: Option<String>,
+}
+
+impl PgnGame {
+    pub(crate) fn new(
+        tags: Vec<(String, String)>,
+        game: Game,
+        mainline: PgnVariation,
+        result: Option<String>,
+    ) -> PgnGame {
+        PgnGame {
+            tags,
+            game,
+            mainline,
+            result,
+        }
+    }
+
+    /// All tag pairs, in the order they appeared in the PGN.
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Give me the value of the tag named `name`, if it exists.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The mainline of this game as a `Game`.  If a `FEN` tag was present, the game starts from
+    /// that position.  Games with a `Variant "Chess960"` tag are played in Chess960 mode.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The movetext of this game, including comments, NAGs and variations.
+    pub fn mainline(&self) -> &PgnVariation {
+        &self.mainline
+    }
+
+    /// The game termination marker (`1-0`, `0-1`, `1/2-1/2` or `*`), if there was one.
+    pub fn result(&self) -> Option<&str> {
+        self.result.as_deref()
+    }
+}
+
+/// Parse a single game from a PGN string.
+impl FromStr for PgnGame {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PgnReader::new(s.as_bytes()).next().unwrap_or_else(|| {
+            Err(Error::InvalidPgn {
+                line: 1,
+                column: 1,
+                message: "no game found".to_string(),
+            })
+        })
+    }
+}
+
+/// Read games from a stream of PGN text, one at a time.
+///
+/// `PgnReader` is an `Iterator` over the games in the stream.  When a game cannot be parsed, an
+/// `Error::InvalidPgn` with the line and column of the problem is returned, and reading resumes at
+/// the next game.
+///
+/// ```
+/// use chess::{Error, PgnReader};
+///
+/// let pgn = r#"
+/// [Event "First"]
+///
+/// 1. e4 e5 1-0
+///
+/// [Event "Second"]
+///
+/// 1. e4 e4 0-1
+///
+/// [Event "Third"]
+///
+/// 1. d4 d5 1/2-1/2
+/// "#;
+///
+/// let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+/// assert_eq!(games.len(), 3);
+/// assert_eq!(games[0].as_ref().unwrap().tag("Event"), Some("First"));
+/// match games[1] {
+///     Err(Error::InvalidPgn { line, column, .. }) => assert_eq!((line, column), (8, 7)),
+///     _ => panic!("The second game contains an illegal move"),
+/// }
+/// assert_eq!(games[2].as_ref().unwrap().result(), Some("1/2-1/2"));
+/// ```
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    line: String,
+    pos: usize,
+    line_number: usize,
+    column: usize,
+    eof: bool,
+    in_movetext: bool,
+    recover: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    /// Create a new `PgnReader` reading from `reader`.
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            line: String::new(),
+            pos: 0,
+            line_number: 0,
+            column: 1,
+            eof: false,
+            in_movetext: false,
+            recover: false,
+        }
+    }
+
+    fn location(&self) -> (usize, usize) {
+        (self.line_number, self.column)
+    }
+
+    fn error_at(position: (usize, usize), message: &str) -> Error {
+        Error::InvalidPgn {
+            line: position.0,
+            column: position.1,
+            message: message.to_string(),
+        }
+    }
+
+    /// Make sure there is something left in the current line, reading more lines as needed.
+    /// Returns false at the end of the stream.
+    fn fill(&mut self) -> Result<bool, Error> {
+        while self.pos >= self.line.len() {
+            if self.eof {
+                return Ok(false);
+            }
+
+            let mut buf = vec![];
+            match self.reader.read_until(b'\n', &mut buf) {
+                Ok(0) => {
+                    self.eof = true;
+                    return Ok(false);
+                }
+                Ok(_) => {
+                    self.line = String::from_utf8_lossy(&buf).into_owned();
+                    self.pos = 0;
+                    self.line_number += 1;
+                    self.column = 1;
+                    if self.line_number == 1 && self.line.starts_with('\u{feff}') {
+                        self.pos = '\u{feff}'.len_utf8();
+                    }
+                    // Lines starting with '%' are escaped, and must be ignored.
+                    if self.line.starts_with('%') {
+                        self.pos = self.line.len();
+                    }
+                }
+                Err(e) => {
+                    self.eof = true;
+                    return Err(PgnReader::<R>::error_at(
+                        (self.line_number + 1, 1),
+                        &format!("unable to read input: {}", e),
+                    ));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, Error> {
+        if self.fill()? {
+            Ok(self.line[self.pos..].chars().next())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.line[self.pos..].chars().next() {
+            self.pos += c.len_utf8();
+            self.column += 1;
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), Error> {
+        while let Some(c) = self.peek()? {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+        Ok(())
+    }
+
+    /// After an error, throw away input until something that looks like the start of the next
+    /// game: a tag following movetext.
+    fn skip_to_next_game(&mut self) -> Result<(), Error> {
+        let mut seen_movetext = self.in_movetext;
+
+        // The error may have been caused by the tags of the next game.
+        if seen_movetext
+            && self.line[..self.pos].trim().is_empty()
+            && self.line[self.pos..].starts_with('[')
+        {
+            return Ok(());
+        }
+
+        self.pos = self.line.len();
+        while self.fill()? {
+            if self.line[self.pos..].trim_start().starts_with('[') {
+                if seen_movetext {
+                    return Ok(());
+                }
+            } else {
+                seen_movetext = true;
+            }
+            self.pos = self.line.len();
+        }
+        Ok(())
+    }
+
+    fn read_game(&mut self) -> Result<Option<PgnGame>, Error> {
+        self.in_movetext = false;
+        self.skip_whitespace()?;
+        if self.peek()?.is_none() {
+            return Ok(None);
+        }
+
+        let mut tags = vec![];
+        let mut fen = None;
+        let mut chess960 = false;
+        while self.peek()? == Some('[') {
+            let position = self.location();
+            let (name, value) = self.parse_tag()?;
+            if name == "FEN" {
+                fen = Some((position, value.clone()));
+            } else if name == "Variant" {
+                chess960 = is_chess960_variant(&value);
+            }
+            tags.push((name, value));
+            self.skip_whitespace()?;
+        }
+
+        self.in_movetext = true;
+        let start = match fen {
+            Some((position, fen)) => BoardBuilder::from_str(&fen)
+                .and_then(|mut builder| {
+                    if chess960 {
+                        builder.chess960(true);
+                    }
+                    Board::try_from(builder)
+                })
+                .map_err(|_| PgnReader::<R>::error_at(position, "invalid FEN tag"))?,
+            None if chess960 => Board::try_from(BoardBuilder::default().chess960(true))?,
+            None => Board::default(),
+        };
+
+        let (mainline, result) = self.parse_variation(start, true)?;
+
+        let mut game = Game::new_with_board(start);
+        for node in mainline.nodes.iter() {
+            game.make_move(node.chess_move);
+        }
+
+        Ok(Some(PgnGame {
+            tags,
+            game,
+            mainline,
+            result,
+        }))
+    }
+
+    fn parse_tag(&mut self) -> Result<(String, String), Error> {
+        self.bump(); // '['
+        self.skip_whitespace()?;
+
+        let mut name = String::new();
+        while let Some(c) = self.peek()? {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+        if name.is_empty() {
+            return Err(PgnReader::<R>::error_at(
+                self.location(),
+                "expected a tag name",
+            ));
+        }
+
+        self.skip_whitespace()?;
+        if self.peek()? != Some('"') {
+            return Err(PgnReader::<R>::error_at(
+                self.location(),
+                "expected a quoted tag value",
+            ));
+        }
+        self.bump();
+
+        let mut value = String::new();
+        loop {
+            match self.peek()? {
+                Some('"') => {
+                    self.bump();
+                    break;
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.peek()? {
+                        Some(c) if c == '"' || c == '\\' => {
+                            value.push(c);
+                            self.bump();
+                        }
+                        _ => value.push('\\'),
+                    }
+                }
+                Some('\n') | Some('\r') | None => {
+                    return Err(PgnReader::<R>::error_at(
+                        self.location(),
+                        "unterminated tag value",
+                    ));
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.bump();
+                }
+            }
+        }
+
+        self.skip_whitespace()?;
+        if self.peek()? != Some(']') {
+            return Err(PgnReader::<R>::error_at(
+                self.location(),
+                "expected ']' to close the tag",
+            ));
+        }
+        self.bump();
+
+        Ok((name, value))
+    }
+
+    fn parse_brace_comment(&mut self) -> Result<String, Error> {
+        let start = self.location();
+        self.bump(); // '{'
+        let mut text = String::new();
+        loop {
+            match self.peek()? {
+                Some('}') => {
+                    self.bump();
+                    return Ok(text);
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.bump();
+                }
+                None => return Err(PgnReader::<R>::error_at(start, "unterminated comment")),
+            }
+        }
+    }
+
+    fn parse_line_comment(&mut self) -> Result<String, Error> {
+        self.bump(); // ';'
+        let mut text = String::new();
+        while let Some(c) = self.peek()? {
+            if c == '\n' {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        Ok(text)
+    }
+
+    fn parse_nag(&mut self) -> Result<u8, Error> {
+        let start = self.location();
+        self.bump(); // '$'
+        let mut digits = String::new();
+        while let Some(c) = self.peek()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.bump();
+        }
+        u8::from_str(&digits).map_err(|_| PgnReader::<R>::error_at(start, "invalid NAG"))
+    }
+
+    fn parse_suffix_annotation(&mut self) -> Result<u8, Error> {
+        let start = self.location();
+        let mut suffix = String::new();
+        while let Some(c) = self.peek()? {
+            if c != '!' && c != '?' {
+                break;
+            }
+            suffix.push(c);
+            self.bump();
+        }
+        match suffix.as_str() {
+            "!" => Ok(1),
+            "?" => Ok(2),
+            "!!" => Ok(3),
+            "??" => Ok(4),
+            "!?" => Ok(5),
+            "?!" => Ok(6),
+            _ => Err(PgnReader::<R>::error_at(start, "invalid suffix annotation")),
+        }
+    }
+
+    fn parse_symbol(&mut self) -> Result<String, Error> {
+        let mut symbol = String::new();
+        while let Some(c) = self.peek()? {
+            if !(c.is_ascii_alphanumeric() || "-+#=:/*.".contains(c)) {
+                break;
+            }
+            symbol.push(c);
+            self.bump();
+        }
+        Ok(symbol)
+    }
+
+    /// Parse a line of moves starting at `board`.  The mainline ends with a game termination
+    /// marker, the tags of the next game or the end of the stream, and returns the marker if
+    /// there was one.  A variation ends with ')'.
+    fn parse_variation(
+        &mut self,
+        mut board: Board,
+        top_level: bool,
+    ) -> Result<(PgnVariation, Option<String>), Error> {
+        let mut variation = PgnVariation::default();
+        let mut previous = board;
+
+        loop {
+            self.skip_whitespace()?;
+            let position = self.location();
+            let c = match self.peek()? {
+                Some(c) => c,
+                None if top_level => return Ok((variation, None)),
+                None => return Err(PgnReader::<R>::error_at(position, "unterminated variation")),
+            };
+
+            match c {
+                '{' | ';' => {
+                    let text = if c == '{' {
+                        self.parse_brace_comment()?
+                    } else {
+                        self.parse_line_comment()?
+                    };
+                    match variation.nodes.last_mut() {
+                        Some(node) => node.add_comment(&text),
+                        None => variation.add_comment(&text),
+                    }
+                }
+                '(' => {
+                    self.bump();
+                    if variation.nodes.is_empty() {
+                        return Err(PgnReader::<R>::error_at(
+                            position,
+                            "variation before any move",
+                        ));
+                    }
+                    let (alternative, _) = self.parse_variation(previous, false)?;
+                    if let Some(node) = variation.nodes.last_mut() {
+                        node.variations.push(alternative);
+                    }
+                }
+                ')' => {
+                    if top_level {
+                        return Err(PgnReader::<R>::error_at(position, "unexpected ')'"));
+                    }
+                    self.bump();
+                    return Ok((variation, None));
+                }
+                '[' if top_level => return Ok((variation, None)),
+                '$' | '!' | '?' => {
+                    let nag = if c == '$' {
+                        self.parse_nag()?
+                    } else {
+                        self.parse_suffix_annotation()?
+                    };
+                    match variation.nodes.last_mut() {
+                        Some(node) => node.nags.push(nag),
+                        None => {
+                            return Err(PgnReader::<R>::error_at(
+                                position,
+                                "annotation before any move",
+                            ))
+                        }
+                    }
+                }
+                _ => {
+                    let symbol = self.parse_symbol()?;
+                    match symbol.as_str() {
+                        "" => {
+                            return Err(PgnReader::<R>::error_at(
+                                position,
+                                &format!("unexpected character '{}'", c),
+                            ))
+                        }
+                        "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                            if top_level {
+                                return Ok((variation, Some(symbol)));
+                            }
+                            return Err(PgnReader::<R>::error_at(
+                                position,
+                                "game termination marker inside a variation",
+                            ));
+                        }
+                        _ => {}
+                    }
+
+                    // Strip off any move number, such as "12." or "12...".
+                    let san = match symbol.as_str() {
+                        "0-0" => "O-O",
+                        "0-0-0" => "O-O-O",
+                        s => s
+                            .trim_start_matches(|c: char| c.is_ascii_digit())
+                            .trim_start_matches('.'),
+                    };
+                    if san.is_empty() {
+                        continue;
+                    }
+
+                    let chess_move = ChessMove::from_san(&board, san).map_err(|_| {
+                        PgnReader::<R>::error_at(
+                            position,
+                            &format!("illegal or ambiguous move '{}'", san),
+                        )
+                    })?;
+
+                    variation.nodes.push(PgnNode {
+                        chess_move,
+                        san: san.to_string(),
+                        nags: vec![],
+                        comments: vec![],
+                        commands: vec![],
+                        variations: vec![],
+                    });
+                    previous = board;
+                    board = board.make_move_new(chess_move);
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.recover {
+            self.recover = false;
+            if let Err(e) = self.skip_to_next_game() {
+                return Some(Err(e));
+            }
+        }
+
+        match self.read_game() {
+            Ok(Some(game)) => Some(Ok(game)),
+            Ok(None) => None,
+            Err(e) => {
+                self.recover = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Does the value of a `Variant` tag mean Chess960?
+fn is_chess960_variant(variant: &str) -> bool {
+    const NAMES: [&str; 6] = [
+        "chess960",
+        "chess 960",
+        "960",
+        "fischerandom",
+        "fischer random",
+        "fischer random chess",
+    ];
+    NAMES.contains(&variant.to_lowercase().as_str())
+}
+
+/// Split a comment into its plain text and any `[%command value]` annotations it contains.
+pub(crate) fn split_comment(
+    text: &str,
+    comments: &mut Vec<String>,
+    commands: &mut Vec<(String, String)>,
+) {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[%") {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        plain.push_str(&rest[..start]);
+
+        let command = rest[(start + 2)..end].trim();
+        let (name, value) = match command.find(char::is_whitespace) {
+            Some(split) => (&command[..split], command[split..].trim()),
+            None => (command, ""),
+        };
+        commands.push((name.to_string(), value.to_string()));
+
+        rest = &rest[(end + 1)..];
+    }
+    plain.push_str(rest);
+
+    let plain = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !plain.is_empty() {
+        comments.push(plain);
+    }
+}
+
+#[cfg(test)]
+use crate::square::Square;
+
+#[test]
+fn test_pgn_reader_full_game() {
+    let pgn = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcdefgh"]
+[White "Alice \"The Rook\""]
+[Black "Bob"]
+[Result "1-0"]
+[WhiteElo "1850"]
+
+{ A quick game. } 1. e4 { [%clk 0:03:00] } 1... e5 { [%clk 0:03:00] [%eval 0.2] }
+2. Nf3 Nc6 3. Bc4 Nd4?! (3... Bc5 4. c3 (4. b4!? Bxb4) 4... Nf6) ; the Blackburne trap
+4. Nxe5 $2 Qg5 5. Nxf7?? Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 1-0
+"#;
+
+    let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+    assert_eq!(games.len(), 1);
+    let pgn = games[0].as_ref().expect("Valid PGN");
+
+    assert_eq!(pgn.tags().len(), 6);
+    assert_eq!(pgn.tag("White"), Some("Alice \"The Rook\""));
+    assert_eq!(pgn.tag("WhiteElo"), Some("1850"));
+    assert_eq!(pgn.tag("Round"), None);
+    assert_eq!(pgn.result(), Some("1-0"));
+    assert_eq!(pgn.game().actions().len(), 14);
+    assert!(pgn.game().current_position().checkers().popcnt() > 0);
+
+    let mainline = pgn.mainline();
+    assert_eq!(mainline.comments(), ["A quick game."]);
+
+    let nodes = mainline.nodes();
+    assert_eq!(
+        nodes[0].chess_move(),
+        ChessMove::new(Square::E2, Square::E4, None)
+    );
+    assert_eq!(nodes[0].command("clk"), Some("0:03:00"));
+    assert!(nodes[0].comments().is_empty());
+    assert_eq!(
+        nodes[1].commands(),
+        [
+            ("clk".to_string(), "0:03:00".to_string()),
+            ("eval".to_string(), "0.2".to_string())
+        ]
+    );
+
+    let nd4 = &nodes[5];
+    assert_eq!(nd4.san(), "Nd4");
+    assert_eq!(nd4.nags(), [6]);
+    assert_eq!(nd4.comments(), ["the Blackburne trap"]);
+    assert_eq!(nd4.variations().len(), 1);
+
+    let bc5 = &nd4.variations()[0];
+    assert_eq!(bc5.nodes().len(), 3);
+    assert_eq!(bc5.nodes()[1].san(), "c3");
+    assert_eq!(bc5.nodes()[1].variations()[0].nodes()[0].nags(), [5]);
+    assert_eq!(
+        bc5.nodes()[1].variations()[0].nodes()[1].chess_move(),
+        ChessMove::new(Square::C5, Square::B4, None)
+    );
+
+    assert_eq!(nodes[6].nags(), [2]);
+    assert_eq!(nodes[8].nags(), [4]);
+    assert_eq!(nodes[13].san(), "Nf3#");
+}
+
+#[test]
+fn test_pgn_reader_fen_tag() {
+    let pgn = PgnGame::from_str(
+        r#"[SetUp "1"]
+[FEN "4k3/1P6/8/8/8/8/8/4K2R w K - 0 40"]
+
+40. b8=Q+ Kd7 41. O-O *"#,
+    )
+    .expect("Valid PGN");
+
+    assert_eq!(
+        pgn.game().current_position(),
+        Board::from_str("1Q6/3k4/8/8/8/8/8/5RK1 b - - 2 41").expect("Valid FEN")
+    );
+}
+
+#[test]
+fn test_pgn_reader_errors() {
+    let pgn = "[Event \"Broken tag]\n\n1. e4 *\n\n[Event \"Unbalanced\"]\n\n1. e4 (1. d4 *\n\n[Event \"Good\"]\n\n1. e4 c5 *\n\n[Event \"Trailing\"]\n\n1. e4 e5 2. Nf3 )";
+
+    let results: Vec<_> = PgnReader::new(pgn.as_bytes())
+        .map(|r| match r {
+            Ok(game) => Ok(game.tag("Event").map(|e| e.to_string())),
+            Err(Error::InvalidPgn { line, column, .. }) => Err((line, column)),
+            Err(_) => panic!("Unexpected error type"),
+        })
+        .collect();
+
+    assert_eq!(
+        results,
+        vec![
+            Err((1, 20)),
+            Err((7, 14)),
+            Ok(Some("Good".to_string())),
+            Err((15, 17)),
+        ]
+    );
+}
+
+#[test]
+fn test_pgn_reader_chess960() {
+    let pgn = PgnGame::from_str(
+        r#"[Variant "Chess960"]
+[SetUp "1"]
+[FEN "rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w KQkq - 0 1"]
+
+1. O-O O-O-O *"#,
+    )
+    .expect("Valid PGN");
+
+    let board = pgn.game().current_position();
+    assert!(board.is_chess960());
+    assert_eq!(
+        board.to_string(),
+        "2kr2r1/pp4pp/8/8/8/8/PP4PP/R4RK1 w - - 2 2"
+    );
+}
@@ -0,0 +1,467 @@
+use crate::board::Board;
+use crate::color::Color;
+use crate::game::{Action, Game, GameResult};
+use crate::pgn::{PgnGame, PgnVariation};
+use crate::variant_board::VariantBoard;
+use crate::variant_game::VariantGame;
+
+use std::fmt;
+
+/// The tags that must appear, in this order, at the start of every exported game.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Export format PGN lines must be shorter than 80 characters.
+const MAX_LINE_LENGTH: usize = 79;
+
+/// Write a `Game` as export format PGN.
+///
+/// The seven tag roster is always written first, with `?` for any tag that was not given.  The
+/// `Result` tag and the game termination marker come from `Game::result()`.  Chess960 games get
+/// the `Variant` tag next, games that do not start from the initial position get the `SetUp` and
+/// `FEN` tags, and any other tags follow in ASCII order.
+///
+/// A `PgnWriter` made from a `PgnGame` writes its tags and its full movetext, including comments,
+/// NAGs and variations.  When the `Game` has no result, the result read from the PGN is used.
+///
+/// A `PgnWriter` made from a `VariantGame` writes the `Variant` tag with the name of the variant,
+/// and the `SetUp` and `FEN` tags unless the game starts from the variant's initial position.
+///
+/// ```
+/// use chess::{ChessMove, Game, PgnWriter, Square};
+///
+/// let mut game = Game::new();
+/// game.make_move(ChessMove::new(Square::F2, Square::F3, None));
+/// game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+/// game.make_move(ChessMove::new(Square::G2, Square::G4, None));
+/// game.make_move(ChessMove::new(Square::D8, Square::H4, None));
+///
+/// let pgn = PgnWriter::new(&game)
+///     .tag("White", "Fool")
+///     .tag("Black", "Scholar")
+///     .to_string();
+///
+/// assert_eq!(
+///     pgn,
+///     r#"[Event "?"]
+/// [Site "?"]
+/// [Date "????.??.??"]
+/// [Round "?"]
+/// [White "Fool"]
+/// [Black "Scholar"]
+/// [Result "0-1"]
+///
+/// 1. f3 e5 2. g4 Qh4# 0-1
+/// "#
+/// );
+/// ```
+pub struct PgnWriter<'a> {
+    game: &'a Game,
+    tags: Vec<(String, String)>,
+    mainline: Option<&'a PgnVariation>,
+    result: Option<&'a str>,
+}
+
+impl<'a> PgnWriter<'a> {
+    /// Create a new `PgnWriter` for `game`, without any tags.
+    pub fn new(game: &'a Game) -> PgnWriter<'a> {
+        PgnWriter {
+            game,
+            tags: vec![],
+            mainline: None,
+            result: None,
+        }
+    }
+
+    /// Set the tag `name` to `value`, replacing any previous value.  The `Result`, `SetUp` and
+    /// `FEN` tags (and the `Variant` tag for Chess960 and variant games) are derived from the
+    /// game, and will be ignored.
+    pub fn tag(&mut self, name: &str, value: &str) -> &mut Self {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    fn result(&self) -> &str {
+        match (self.game.result(), self.result) {
+            (None, Some(result)) => result,
+            (result, _) => result_marker(result),
+        }
+    }
+
+    fn tag_value(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn movetext(&self) -> Vec<String> {
+        if let Some(mainline) = self.mainline {
+            let mut tokens = vec![];
+            write_line(mainline, self.game.start_position(), &mut tokens);
+            tokens.push(self.result().to_string());
+            return tokens;
+        }
+
+        if let Some(start) = self.game.start_variant_position() {
+            return self.variant_movetext(start);
+        }
+
+        let mut board = self.game.start_position();
+        let mut number = board.fullmove_number();
+        let mut tokens = vec![];
+
+        for action in self.game.actions().iter() {
+            if let Action::MakeMove(m) = *action {
+                if board.side_to_move() == Color::White {
+                    tokens.push(format!("{}.", number));
+                } else if tokens.is_empty() {
+                    tokens.push(format!("{}...", number));
+                }
+                tokens.push(m.to_san(&board));
+
+                if board.side_to_move() == Color::Black {
+                    number += 1;
+                }
+                board = board.make_move_new(m);
+            }
+        }
+
+        tokens.push(self.result().to_string());
+        tokens
+    }
+
+    /// The movetext of a `VariantGame`, played from `board`.
+    fn variant_movetext(&self, mut board: VariantBoard) -> Vec<String> {
+        let mut tokens = vec![];
+        for action in self.game.actions().iter() {
+            if let Action::MakeMove(m) = *action {
+                if board.side_to_move() == Color::White {
+                    tokens.push(format!("{}.", board.fullmove_number()));
+                } else if tokens.is_empty() {
+                    tokens.push(format!("{}...", board.fullmove_number()));
+                }
+                tokens.push(m.to_variant_san(&board));
+                board = board.make_move_new(m);
+            }
+        }
+
+        tokens.push(self.result().to_string());
+        tokens
+    }
+
+    /// The `Variant`, `SetUp` and `FEN` tags that come from the game itself.
+    fn position_tags(&self) -> Vec<(&'static str, String)> {
+        let mut tags = vec![];
+        if let Some(start) = self.game.start_variant_position() {
+            tags.push(("Variant", start.variant().to_string()));
+            if start != VariantBoard::new(start.variant()) {
+                tags.push(("SetUp", "1".to_string()));
+                tags.push(("FEN", start.to_string()));
+            }
+            return tags;
+        }
+
+        let start = self.game.start_position();
+        if start.is_chess960() {
+            tags.push(("Variant", "Chess960".to_string()));
+        }
+        if start != Board::default() {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", start.to_string()));
+        }
+        tags
+    }
+}
+
+impl<'a> From<&'a VariantGame> for PgnWriter<'a> {
+    fn from(game: &'a VariantGame) -> PgnWriter<'a> {
+        PgnWriter::new(game.game())
+    }
+}
+
+impl<'a> From<&'a PgnGame> for PgnWriter<'a> {
+    fn from(pgn: &'a PgnGame) -> PgnWriter<'a> {
+        PgnWriter {
+            game: pgn.game(),
+            tags: pgn.tags().to_vec(),
+            mainline: Some(pgn.mainline()),
+            result: pgn.result(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for PgnWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = self.result();
+        for (name, default) in SEVEN_TAG_ROSTER.iter() {
+            let value = if *name == "Result" {
+                result
+            } else {
+                self.tag_value(name).unwrap_or(default)
+            };
+            write_tag(f, name, value)?;
+        }
+
+        let position_tags = self.position_tags();
+        for (name, value) in position_tags.iter() {
+            write_tag(f, name, value)?;
+        }
+
+        let mut tags: Vec<&(String, String)> = self
+            .tags
+            .iter()
+            .filter(|(n, _)| {
+                n != "SetUp"
+                    && n != "FEN"
+                    && !position_tags.iter().any(|(p, _)| p == n)
+                    && !SEVEN_TAG_ROSTER.iter().any(|(r, _)| r == n)
+            })
+            .collect();
+        tags.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, value) in tags {
+            write_tag(f, name, value)?;
+        }
+
+        writeln!(f)?;
+
+        let mut line_length = 0;
+        for token in self.movetext() {
+            if line_length == 0 {
+                line_length = token.len();
+            } else if line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f)?;
+                line_length = token.len();
+            } else {
+                write!(f, " ")?;
+                line_length += 1 + token.len();
+            }
+            write!(f, "{}", token)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The export format result marker for `result`.
+pub(crate) fn result_marker(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteCheckmates)
+        | Some(GameResult::BlackResigns)
+        | Some(GameResult::BlackLosesOnTime)
+        | Some(GameResult::WhiteVariantWin) => "1-0",
+        Some(GameResult::BlackCheckmates)
+        | Some(GameResult::WhiteResigns)
+        | Some(GameResult::WhiteLosesOnTime)
+        | Some(GameResult::BlackVariantWin) => "0-1",
+        Some(GameResult::Stalemate)
+        | Some(GameResult::DrawAccepted)
+        | Some(GameResult::DrawDeclared)
+        | Some(GameResult::FivefoldRepetition)
+        | Some(GameResult::SeventyFiveMoveRule)
+        | Some(GameResult::InsufficientMaterial)
+        | Some(GameResult::TimeoutVsInsufficientMaterial)
+        | Some(GameResult::ThreefoldRepetition)
+        | Some(GameResult::FiftyMoveRule) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Add the tokens for `line`, played from `board`, to `tokens`.  Variations are wrapped in
+/// parentheses, and the move number is repeated for Black after a comment or variation.
+fn write_line(line: &PgnVariation, mut board: Board, tokens: &mut Vec<String>) {
+    let mut show_number = true;
+    write_comment(line.comments(), line.commands(), tokens);
+    for node in line.nodes() {
+        if board.side_to_move() == Color::White {
+            tokens.push(format!("{}.", board.fullmove_number()));
+        } else if show_number {
+            tokens.push(format!("{}...", board.fullmove_number()));
+        }
+        tokens.push(node.chess_move().to_san(&board));
+        for nag in node.nags() {
+            tokens.push(format!("${}", nag));
+        }
+        show_number = write_comment(node.comments(), node.commands(), tokens);
+
+        for variation in node.variations() {
+            let mut variation_tokens = vec![];
+            write_line(variation, board, &mut variation_tokens);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+                show_number = true;
+            }
+            tokens.extend(variation_tokens);
+        }
+        board = board.make_move_new(node.chess_move());
+    }
+}
+
+/// Add a `{ }` comment holding `comments` and `commands` to `tokens`, if there is anything to say.
+/// Returns true if a comment was added.
+fn write_comment(
+    comments: &[String],
+    commands: &[(String, String)],
+    tokens: &mut Vec<String>,
+) -> bool {
+    if comments.is_empty() && commands.is_empty() {
+        return false;
+    }
+    tokens.push("{".to_string());
+    for (name, value) in commands {
+        if value.is_empty() {
+            tokens.push(format!("[%{}]", name));
+        } else {
+            tokens.push(format!("[%{}", name));
+            tokens.extend(value.split_whitespace().map(str::to_string));
+            if let Some(last) = tokens.last_mut() {
+                last.push(']');
+            }
+        }
+    }
+    for comment in comments {
+        tokens.extend(
+            comment
+                .replace('}', "")
+                .split_whitespace()
+                .map(str::to_string),
+        );
+    }
+    tokens.push("}".to_string());
+    true
+}
+
+fn write_tag(f: &mut fmt::Formatter, name: &str, value: &str) -> fmt::Result {
+    writeln!(
+        f,
+        "[{} \"{}\"]",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+#[cfg(test)]
+use crate::chess_move::ChessMove;
+#[cfg(test)]
+use crate::pgn::PgnReader;
+#[cfg(test)]
+use crate::piece::Piece;
+#[cfg(test)]
+use crate::square::Square;
+#[cfg(test)]
+use crate::variant::Variant;
+#[cfg(test)]
+use std::str::FromStr;
+
+#[test]
+fn test_pgn_writer_round_trip() {
+    let pgn = "1. d4 Nf6 2. c4 g6 3. Nc3 Bg7 4. e4 d6 5. Nf3 O-O 6. Be2 e5 7. O-O Nc6 8. d5 Ne7 9. Nd2 a5 10. Rb1 Nd7 11. a3 f5 12. b4 Kh8 13. f3 Ng8 14. Qc2 Ngf6 15. Nb5 axb4 16. axb4 Nh5 17. g3 Ndf6 18. c5 Bd7 19. Rb3 Nxg3 20. hxg3 Nh5 21. f4 exf4 22. c6 bxc6 23. dxc6 Nxg3 24. Rxg3 fxg3 25. cxd7 g2 26. Rf3 Qxd7 27. Bb2 fxe4 28. Rxf8+ Rxf8 29. Bxg7+ Qxg7 30. Qxe4 Qf6 31. Nf3 Qf4 32. Qe7 Rf7 33. Qe6 Rf6 34. Qe8+ Rf8 35. Qe7 Rf7 36. Qe6 Rf6 37. Qb3 g5 38. Nxc7 g4 39. Nd5 Qc1+ 40. Qd1 Qxd1+ 41. Bxd1 Rf5 42. Ne3 Rf4 43. Ne1 Rxb4 44. Bxg4 h5 45. Bf3 d5 46. N3xg2 h4 47. Nd3 Ra4 48. Ngf4 Kg7 49. Kg2 Kf6 50. Bxd5 Ra5 *";
+    let game = PgnReader::new(pgn.as_bytes())
+        .next()
+        .expect("One game")
+        .expect("Valid PGN");
+
+    let written = PgnWriter::new(game.game())
+        .tag("Event", "Round \"Trip\"")
+        .tag("Annotator", "Nobody")
+        .tag("Result", "1-0")
+        .to_string();
+
+    assert!(written.lines().all(|l| l.len() < 80));
+    assert!(written.contains("[Event \"Round \\\"Trip\\\"\"]\n"));
+    assert!(written.contains("[Result \"*\"]\n[Annotator \"Nobody\"]\n\n1. d4 Nf6"));
+    assert!(written.split_whitespace().any(|t| t == "N3xg2"));
+    assert!(written.split_whitespace().any(|t| t == "Ngf4"));
+    assert!(written.ends_with(" *\n"));
+
+    let reread = PgnReader::new(written.as_bytes())
+        .next()
+        .expect("One game")
+        .expect("Valid PGN");
+    assert_eq!(reread.tag("Event"), Some("Round \"Trip\""));
+    assert_eq!(reread.game().actions(), game.game().actions());
+}
+
+#[test]
+fn test_pgn_writer_set_up() {
+    let mut game = Game::from_str("r3k3/1P6/8/8/8/8/8/4K2R b Kq - 0 30").expect("Valid FEN");
+    game.make_move(ChessMove::new(Square::E8, Square::D7, None));
+    game.make_move(ChessMove::new(Square::B7, Square::A8, Some(Piece::Knight)));
+    game.make_move(ChessMove::new(Square::D7, Square::D8, None));
+    game.make_move(ChessMove::new(Square::E1, Square::G1, None));
+    game.resign(Color::Black);
+
+    assert_eq!(
+        PgnWriter::new(&game).to_string(),
+        r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "1-0"]
+[SetUp "1"]
+[FEN "r3k3/1P6/8/8/8/8/8/4K2R b Kq - 0 30"]
+
+30... Kd7 31. bxa8=N Kd8 32. O-O 1-0
+"#
+    );
+}
+
+#[test]
+fn test_pgn_writer_chess960() {
+    let mut game =
+        Game::from_str("rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w GAga - 0 1").expect("Valid FEN");
+    game.make_move(ChessMove::new(Square::B1, Square::G1, None));
+    game.make_move(ChessMove::new(Square::B8, Square::A8, None));
+
+    let written = PgnWriter::new(&game).tag("Variant", "Standard").to_string();
+    assert!(written.contains(
+        "[Result \"*\"]\n[Variant \"Chess960\"]\n[SetUp \"1\"]\n[FEN \"rk4r1/pp4pp/8/8/8/8/PP4PP/RK4R1 w KQkq - 0 1\"]\n\n1. O-O O-O-O *\n"
+    ));
+
+    let reread = PgnReader::new(written.as_bytes())
+        .next()
+        .expect("One game")
+        .expect("Valid PGN");
+    assert_eq!(reread.game().current_position(), game.current_position());
+}
+
+#[test]
+fn test_pgn_writer_variant() {
+    let mut game = VariantGame::new(VariantBoard::new(Variant::Horde));
+    game.make_move(ChessMove::new(Square::F5, Square::F6, None));
+    game.make_move(ChessMove::new(Square::G7, Square::F6, None));
+    let written = PgnWriter::from(&game)
+        .tag("Variant", "Chess960")
+        .to_string();
+    assert!(written.contains("[Result \"*\"]\n[Variant \"Horde\"]\n\n1. f6 gxf6 *\n"));
+    assert!(!written.contains("FEN"));
+
+    let board = VariantBoard::from_fen(Variant::ThreeCheck, "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1")
+        .expect("Valid FEN");
+    let mut game = VariantGame::new(board);
+    game.make_move(ChessMove::new(Square::A1, Square::A8, None));
+    assert_eq!(game.result(), Some(GameResult::WhiteVariantWin));
+    assert!(PgnWriter::from(&game).to_string().ends_with(
+        r#"[Result "1-0"]
+[Variant "Three-check"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1"]
+
+1. Ra8+ 1-0
+"#
+    ));
+}
@@ -0,0 +1,347 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+use crate::file::File;
+use crate::piece::Piece;
+use crate::polyglot::polyglot_key;
+use crate::rank::Rank;
+use crate::square::Square;
+
+use rand::Rng;
+use std::fs;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Every Polyglot book entry is exactly 16 bytes long.
+const ENTRY_SIZE: u64 = 16;
+
+/// A single move from a Polyglot opening book.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PolyglotEntry {
+    key: u64,
+    chess_move: ChessMove,
+    weight: u16,
+    learn: u32,
+}
+
+impl PolyglotEntry {
+    /// The Polyglot key of the position this entry belongs to.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// The book move.  Castling has already been converted from Polyglot's king-takes-rook form
+    /// into the move the king makes on this `Board`.
+    pub fn chess_move(&self) -> ChessMove {
+        self.chess_move
+    }
+
+    /// How good the book thinks this move is, relative to the other moves in the same position.
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// The learning data stored alongside the move.
+    pub fn learn(&self) -> u32 {
+        self.learn
+    }
+}
+
+/// A Polyglot `.bin` opening book.
+///
+/// The book is never read into memory.  Instead, the entries for a position are found with a
+/// binary search over the (sorted) file.
+///
+/// ```
+/// use chess::{Board, ChessMove, PolyglotBook, Square};
+/// use std::io::Cursor;
+///
+/// // A book with a single entry: 1. e4 from the starting position
+/// let mut bytes = vec![];
+/// bytes.extend_from_slice(&0x463b96181691fc9cu64.to_be_bytes());
+/// bytes.extend_from_slice(&0x031cu16.to_be_bytes());
+/// bytes.extend_from_slice(&1u16.to_be_bytes());
+/// bytes.extend_from_slice(&0u32.to_be_bytes());
+///
+/// let mut book = PolyglotBook::new(Cursor::new(bytes)).expect("Valid book");
+/// assert_eq!(
+///     book.best_move(&Board::default()).expect("Readable book"),
+///     Some(ChessMove::new(Square::E2, Square::E4, None))
+/// );
+/// ```
+pub struct PolyglotBook<R: Read + Seek> {
+    reader: R,
+    len: u64,
+}
+
+impl PolyglotBook<BufReader<fs::File>> {
+    /// Open the Polyglot book at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = fs::File::open(path).map_err(io_error)?;
+        PolyglotBook::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> PolyglotBook<R> {
+    /// Create a `PolyglotBook` reading from `reader`.
+    ///
+    /// Fails if the book is not a whole number of entries long.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let size = reader.seek(SeekFrom::End(0)).map_err(io_error)?;
+        if size % ENTRY_SIZE != 0 {
+            return Err(Error::InvalidPolyglotBook);
+        }
+        Ok(PolyglotBook {
+            reader,
+            len: size / ENTRY_SIZE,
+        })
+    }
+
+    /// Find every legal book move for `board`, in the order they appear in the book.
+    ///
+    /// Entries whose move is not legal on `board` (for example because of a key collision) are
+    /// skipped.
+    pub fn entries(&mut self, board: &Board) -> Result<Vec<PolyglotEntry>, Error> {
+        let key = polyglot_key(board);
+
+        // find the first entry with a key of at least `key`
+        let mut low = 0;
+        let mut high = self.len;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.read_entry(mid)?.0 < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut result = vec![];
+        let mut index = low;
+        while index < self.len {
+            let (entry_key, raw_move, weight, learn) = self.read_entry(index)?;
+            if entry_key != key {
+                break;
+            }
+            if let Some(chess_move) = decode_move(board, raw_move) {
+                result.push(PolyglotEntry {
+                    key,
+                    chess_move,
+                    weight,
+                    learn,
+                });
+            }
+            index += 1;
+        }
+        Ok(result)
+    }
+
+    /// Get the book move with the highest weight for `board`, if there is one.
+    pub fn best_move(&mut self, board: &Board) -> Result<Option<ChessMove>, Error> {
+        Ok(self
+            .entries(board)?
+            .iter()
+            .rev()
+            .max_by_key(|e| e.weight())
+            .map(|e| e.chess_move()))
+    }
+
+    /// Pick a book move for `board` at random, with each move's chance of being picked
+    /// proportional to its weight.  Moves with a weight of zero are never picked.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, PolyglotBook, Square};
+    /// use rand::rngs::SmallRng;
+    /// use rand::SeedableRng;
+    /// use std::io::Cursor;
+    ///
+    /// // 1. e4 with weight 1, and 1. d4 with weight 0
+    /// let mut bytes = vec![];
+    /// for (raw_move, weight) in [(0x031cu16, 1u16), (0x02dbu16, 0u16)].iter() {
+    ///     bytes.extend_from_slice(&0x463b96181691fc9cu64.to_be_bytes());
+    ///     bytes.extend_from_slice(&raw_move.to_be_bytes());
+    ///     bytes.extend_from_slice(&weight.to_be_bytes());
+    ///     bytes.extend_from_slice(&0u32.to_be_bytes());
+    /// }
+    ///
+    /// let mut book = PolyglotBook::new(Cursor::new(bytes)).expect("Valid book");
+    /// let mut rng = SmallRng::seed_from_u64(1);
+    /// for _ in 0..10 {
+    ///     assert_eq!(
+    ///         book.weighted_move(&Board::default(), &mut rng).expect("Readable book"),
+    ///         Some(ChessMove::new(Square::E2, Square::E4, None))
+    ///     );
+    /// }
+    /// ```
+    pub fn weighted_move<G: Rng>(
+        &mut self,
+        board: &Board,
+        rng: &mut G,
+    ) -> Result<Option<ChessMove>, Error> {
+        let entries = self.entries(board)?;
+        let total: u32 = entries.iter().map(|e| e.weight() as u32).sum();
+        if total == 0 {
+            return Ok(None);
+        }
+
+        let mut choice = rng.gen_range(0, total);
+        for entry in entries.iter() {
+            if choice < entry.weight() as u32 {
+                return Ok(Some(entry.chess_move()));
+            }
+            choice -= entry.weight() as u32;
+        }
+        unreachable!()
+    }
+
+    /// Read the entry at `index` as its key, move, weight and learn fields.
+    fn read_entry(&mut self, index: u64) -> Result<(u64, u16, u16, u32), Error> {
+        let mut buf = [0u8; ENTRY_SIZE as usize];
+        self.reader
+            .seek(SeekFrom::Start(index * ENTRY_SIZE))
+            .map_err(io_error)?;
+        self.reader.read_exact(&mut buf).map_err(io_error)?;
+
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&buf[0..8]);
+        Ok((
+            u64::from_be_bytes(key),
+            u16::from_be_bytes([buf[8], buf[9]]),
+            u16::from_be_bytes([buf[10], buf[11]]),
+            u32::from_be_bytes([buf[12], buf[13], buf[14], buf[15]]),
+        ))
+    }
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::Io {
+        message: error.to_string(),
+    }
+}
+
+/// Decode a Polyglot move for `board`, or `None` if it is not legal there.
+fn decode_move(board: &Board, raw_move: u16) -> Option<ChessMove> {
+    let square = |bits: u16| {
+        Square::make_square(
+            Rank::from_index(((bits >> 3) & 7) as usize),
+            File::from_index((bits & 7) as usize),
+        )
+    };
+    let source = square(raw_move >> 6);
+    let mut dest = square(raw_move);
+    let promotion = match (raw_move >> 12) & 7 {
+        0 => None,
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => return None,
+    };
+
+    // Polyglot writes castling as the king taking its own rook, which is also how Chess960
+    // boards expect it.  On a standard board, the king moves two squares instead.
+    if !board.is_chess960()
+        && board.piece_on(source) == Some(Piece::King)
+        && board.piece_on(dest) == Some(Piece::Rook)
+        && board.color_on(dest) == Some(board.side_to_move())
+    {
+        let file = if dest.get_file() > source.get_file() {
+            File::G
+        } else {
+            File::C
+        };
+        dest = Square::make_square(source.get_rank(), file);
+    }
+
+    let chess_move = ChessMove::new(source, dest, promotion);
+    if board.legal(chess_move) {
+        Some(chess_move)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+use std::io::Cursor;
+#[cfg(test)]
+use std::str::FromStr;
+
+#[cfg(test)]
+fn make_book(entries: &[(u64, u16, u16)]) -> PolyglotBook<Cursor<Vec<u8>>> {
+    let mut entries = entries.to_vec();
+    entries.sort();
+
+    let mut bytes = vec![];
+    for (key, raw_move, weight) in entries {
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&raw_move.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+    }
+    PolyglotBook::new(Cursor::new(bytes)).expect("Valid book")
+}
+
+#[test]
+fn test_polyglot_book_entries() {
+    let castles = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").expect("Valid FEN");
+    let castles_key = polyglot_key(&castles);
+    let start_key = polyglot_key(&Board::default());
+
+    let mut book = make_book(&[
+        (start_key - 1, 0x031c, 7),
+        // e1h1, e1a1, a7a8=Q (illegal here)
+        (castles_key, 0x0107, 3),
+        (castles_key, 0x0100, 5),
+        (castles_key, 0x4c38, 9),
+        (start_key, 0x031c, 2),
+        (start_key, 0x02db, 0),
+        (start_key + 1, 0x031c, 7),
+    ]);
+
+    let entries = book.entries(&castles).expect("Readable book");
+    assert_eq!(
+        entries.iter().map(|e| e.chess_move()).collect::<Vec<_>>(),
+        vec![
+            ChessMove::new(Square::E1, Square::C1, None),
+            ChessMove::new(Square::E1, Square::G1, None),
+        ]
+    );
+    assert_eq!(
+        book.best_move(&castles).expect("Readable book"),
+        Some(ChessMove::new(Square::E1, Square::C1, None))
+    );
+
+    let entries = book.entries(&Board::default()).expect("Readable book");
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|e| e.key() == start_key));
+
+    let empty = Board::from_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1").expect("Valid FEN");
+    assert_eq!(book.entries(&empty).expect("Readable book"), vec![]);
+    assert_eq!(book.best_move(&empty).expect("Readable book"), None);
+}
+
+#[test]
+fn test_polyglot_book_weighted_move() {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    let key = polyglot_key(&Board::default());
+    // e2e4 with weight 3, d2d4 with weight 1
+    let mut book = make_book(&[(key, 0x031c, 3), (key, 0x02db, 1)]);
+    let mut rng = SmallRng::seed_from_u64(0);
+
+    let e4 = ChessMove::new(Square::E2, Square::E4, None);
+    let picks = (0..1000)
+        .filter(|_| {
+            book.weighted_move(&Board::default(), &mut rng)
+                .expect("Readable book")
+                == Some(e4)
+        })
+        .count();
+    assert!(picks > 650 && picks < 850);
+}
+
+#[test]
+fn test_polyglot_book_invalid() {
+    assert!(PolyglotBook::new(Cursor::new(vec![0u8; 17])).is_err());
+}
@@ -0,0 +1,5 @@
+mod book;
+pub use self::book::*;
+
+mod key;
+pub use self::key::*;
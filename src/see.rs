@@ -0,0 +1,536 @@
+use crate::bitboard::{BitBoard, EMPTY};
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::{Color, NUM_COLORS};
+use crate::magic::{between, get_bishop_rays, get_rook_rays, line};
+use crate::piece::{Piece, ALL_PIECES, NUM_PIECES};
+use crate::square::Square;
+
+use std::cmp::max;
+
+/// The piece values used by `Board::see()` and `Board::see_ge()`, indexed by `Piece::to_index()`.
+///
+/// The king can never be captured, so its value is never used.
+pub const SEE_PIECE_VALUES: [i32; NUM_PIECES] = [100, 300, 300, 500, 900, 0];
+
+impl Board {
+    /// Statically evaluate the exchange of pieces that `m` starts on its destination square,
+    /// using `SEE_PIECE_VALUES`.
+    ///
+    /// Both sides keep recapturing with their least valuable piece for as long as it pays, and
+    /// the result is the material the side to move wins (or loses, if negative).  Sliding pieces
+    /// lined up behind one another join in as the pieces in front of them capture, pinned pieces
+    /// only capture along the pin, and the king only captures a piece that is no longer defended.
+    ///
+    /// `m` is assumed to be legal.  Castling is always worth `0`.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").expect("Valid FEN");
+    ///
+    /// // The queen wins a pawn, but is taken back by the other pawn
+    /// assert_eq!(board.see(ChessMove::new(Square::D1, Square::D5, None)), 100 - 900);
+    /// ```
+    pub fn see(&self, m: ChessMove) -> i32 {
+        self.see_with_values(m, &SEE_PIECE_VALUES)
+    }
+
+    /// Check whether the static exchange evaluation of `m` is at least `threshold`, using
+    /// `SEE_PIECE_VALUES`.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").expect("Valid FEN");
+    /// let capture = ChessMove::new(Square::D1, Square::D5, None);
+    ///
+    /// assert!(board.see_ge(capture, -800));
+    /// assert!(!board.see_ge(capture, 0));
+    /// ```
+    pub fn see_ge(&self, m: ChessMove, threshold: i32) -> bool {
+        self.see_ge_with_values(m, threshold, &SEE_PIECE_VALUES)
+    }
+
+    /// Like `Board::see()`, but with the piece values given by `values`, indexed by
+    /// `Piece::to_index()`.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square};
+    /// use std::str::FromStr;
+    ///
+    /// let board = Board::from_str("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").expect("Valid FEN");
+    /// let values = [100, 325, 325, 500, 1000, 0];
+    ///
+    /// assert_eq!(
+    ///     board.see_with_values(ChessMove::new(Square::D1, Square::D5, None), &values),
+    ///     100 - 1000
+    /// );
+    /// ```
+    pub fn see_with_values(&self, m: ChessMove, values: &[i32; NUM_PIECES]) -> i32 {
+        let (mut exchange, first) = match Exchange::new(self, m, values) {
+            Some(exchange) => exchange,
+            None => return 0,
+        };
+
+        let mut gain = [0i32; 32];
+        gain[0] = first;
+        let mut depth = 1;
+        while let Some(won) = exchange.capture() {
+            gain[depth] = won - gain[depth - 1];
+            depth += 1;
+        }
+
+        // Either side may stop capturing whenever continuing would lose material.
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -max(-gain[depth - 1], gain[depth]);
+        }
+        gain[0]
+    }
+
+    /// Like `Board::see_ge()`, but with the piece values given by `values`, indexed by
+    /// `Piece::to_index()`.
+    pub fn see_ge_with_values(
+        &self,
+        m: ChessMove,
+        threshold: i32,
+        values: &[i32; NUM_PIECES],
+    ) -> bool {
+        let (mut exchange, first) = match Exchange::new(self, m, values) {
+            Some(exchange) => exchange,
+            None => return 0 >= threshold,
+        };
+
+        // What the side to move has won so far, over the threshold.  Whoever captures next
+        // stops as soon as that is good enough for them, so the rest of the exchange is only
+        // looked at while it can still change the answer.
+        let mut balance = first - threshold;
+        let mut ours = false;
+        loop {
+            if ours == (balance >= 0) {
+                return balance >= 0;
+            }
+            match exchange.capture() {
+                Some(won) if ours => balance += won,
+                Some(won) => balance -= won,
+                None => return balance >= 0,
+            }
+            ours = !ours;
+        }
+    }
+
+    /// The pieces of `side` that are pinned to their king, given the pieces left on `occupied`
+    /// and that `on_dest` of `on_dest_color` has captured on `dest`.
+    fn see_pinned(
+        &self,
+        side: Color,
+        kings: [Square; NUM_COLORS],
+        dest: Square,
+        on_dest: Piece,
+        on_dest_color: Color,
+        occupied: BitBoard,
+    ) -> BitBoard {
+        let king = kings[side.to_index()];
+        let dest_bb = BitBoard::from_square(dest);
+        let enemy = self.color_combined(!side) & occupied & !dest_bb;
+
+        let mut snipers = ((get_bishop_rays(king)
+            & (self.pieces(Piece::Bishop) | self.pieces(Piece::Queen)))
+            | (get_rook_rays(king) & (self.pieces(Piece::Rook) | self.pieces(Piece::Queen))))
+            & enemy;
+        if on_dest_color != side {
+            let rays = match on_dest {
+                Piece::Bishop => get_bishop_rays(king),
+                Piece::Rook => get_rook_rays(king),
+                Piece::Queen => get_bishop_rays(king) | get_rook_rays(king),
+                _ => EMPTY,
+            };
+            snipers |= rays & dest_bb;
+        }
+
+        let mut pinned = EMPTY;
+        for sniper in snipers {
+            let blockers = between(king, sniper) & occupied;
+            if blockers.popcnt() == 1 {
+                pinned |= blockers & self.color_combined(side) & !dest_bb;
+            }
+        }
+        pinned
+    }
+}
+
+/// The captures on the destination square of a move, each made by the least valuable piece that
+/// can make it.
+struct Exchange<'a> {
+    board: &'a Board,
+    values: &'a [i32; NUM_PIECES],
+    dest: Square,
+    occupied: BitBoard,
+    kings: [Square; NUM_COLORS],
+    /// The piece on `dest`, and its color.
+    on_dest: Piece,
+    on_dest_color: Color,
+    /// The side to capture next.
+    side: Color,
+}
+
+impl<'a> Exchange<'a> {
+    /// Start the exchange with `m`, and return it with the material `m` wins.  Returns `None` if
+    /// there is no piece to move, or `m` castles.
+    fn new(
+        board: &'a Board,
+        m: ChessMove,
+        values: &'a [i32; NUM_PIECES],
+    ) -> Option<(Exchange<'a>, i32)> {
+        let source = m.get_source();
+        let dest = m.get_dest();
+        let us = board.side_to_move();
+        let mover = board.piece_on(source)?;
+
+        let castles = mover == Piece::King
+            && (board.color_on(dest) == Some(us)
+                || (source.get_file().to_index() as i8 - dest.get_file().to_index() as i8).abs()
+                    == 2);
+        if castles {
+            return None;
+        }
+
+        let mut occupied = *board.combined() ^ BitBoard::from_square(source);
+        let mut won = match board.piece_on(dest) {
+            Some(piece) => values[piece.to_index()],
+            None if mover == Piece::Pawn && source.get_file() != dest.get_file() => {
+                // en passant, which also clears the square of the captured pawn
+                occupied ^=
+                    BitBoard::from_square(Square::make_square(source.get_rank(), dest.get_file()));
+                values[Piece::Pawn.to_index()]
+            }
+            None => 0,
+        };
+
+        let mut on_dest = mover;
+        if let Some(promotion) = m.get_promotion() {
+            won += values[promotion.to_index()] - values[Piece::Pawn.to_index()];
+            on_dest = promotion;
+        }
+
+        let mut kings = [
+            board.king_square(Color::White),
+            board.king_square(Color::Black),
+        ];
+        if mover == Piece::King {
+            kings[us.to_index()] = dest;
+        }
+
+        let exchange = Exchange {
+            board,
+            values,
+            dest,
+            occupied,
+            kings,
+            on_dest,
+            on_dest_color: us,
+            side: !us,
+        };
+        Some((exchange, won))
+    }
+
+    /// Make the next capture, and return the material it wins for the side that made it.
+    /// Returns `None` if that side has no piece left that can capture.
+    fn capture(&mut self) -> Option<i32> {
+        let board = self.board;
+        let dest = self.dest;
+        let side = self.side;
+        let attackers = board.attackers_to(dest, self.occupied);
+        let pinned = board.see_pinned(
+            side,
+            self.kings,
+            dest,
+            self.on_dest,
+            self.on_dest_color,
+            self.occupied,
+        );
+
+        let mine = attackers & board.color_combined(side);
+        let king = self.kings[side.to_index()];
+        let (piece, from) = ALL_PIECES.iter().find_map(|&piece| {
+            let mut candidates = mine & board.pieces(piece);
+            for pinned_square in candidates & pinned {
+                if line(king, pinned_square) & BitBoard::from_square(dest) == EMPTY {
+                    candidates ^= BitBoard::from_square(pinned_square);
+                }
+            }
+            if candidates == EMPTY {
+                None
+            } else {
+                Some((piece, candidates.to_square()))
+            }
+        })?;
+
+        // the king may only capture if the other side can not take it back
+        if piece == Piece::King
+            && board.attackers_to(dest, self.occupied ^ BitBoard::from_square(from))
+                & board.color_combined(!side)
+                != EMPTY
+        {
+            return None;
+        }
+
+        let mut won = self.values[self.on_dest.to_index()];
+        self.on_dest = piece;
+        if piece == Piece::Pawn && dest.get_rank() == side.to_their_backrank() {
+            won += self.values[Piece::Queen.to_index()] - self.values[Piece::Pawn.to_index()];
+            self.on_dest = Piece::Queen;
+        }
+        if piece == Piece::King {
+            self.kings[side.to_index()] = dest;
+        }
+
+        self.occupied ^= BitBoard::from_square(from);
+        self.on_dest_color = side;
+        self.side = !side;
+        Some(won)
+    }
+}
+
+#[cfg(test)]
+use crate::movegen::MoveGen;
+#[cfg(test)]
+use std::str::FromStr;
+
+#[cfg(test)]
+fn see_of(fen: &str, source: Square, dest: Square, promotion: Option<Piece>) -> i32 {
+    let board = Board::from_str(fen).expect("Valid FEN");
+    let m = ChessMove::new(source, dest, promotion);
+    assert!(board.legal(m));
+    board.see(m)
+}
+
+#[test]
+fn test_see_simple_captures() {
+    // an undefended pawn
+    assert_eq!(
+        see_of(
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            Square::E1,
+            Square::E5,
+            None
+        ),
+        100
+    );
+    // a pawn defended by a pawn
+    assert_eq!(
+        see_of(
+            "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1",
+            Square::D1,
+            Square::D5,
+            None
+        ),
+        -800
+    );
+    // a quiet move onto an attacked square
+    assert_eq!(
+        see_of(
+            "4k3/8/8/2p5/8/8/8/3RK3 w - - 0 1",
+            Square::D1,
+            Square::D4,
+            None
+        ),
+        -500
+    );
+    // a quiet move onto a safe square
+    assert_eq!(
+        see_of(
+            "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+            Square::D1,
+            Square::D4,
+            None
+        ),
+        0
+    );
+    // castling
+    assert_eq!(
+        see_of(
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            Square::E1,
+            Square::G1,
+            None
+        ),
+        0
+    );
+}
+
+#[test]
+fn test_see_x_rays() {
+    // the rook behind the first one recaptures
+    assert_eq!(
+        see_of(
+            "4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1",
+            Square::E2,
+            Square::E5,
+            None
+        ),
+        100
+    );
+    // the knight takes a defended pawn, and the exchange goes on behind it
+    assert_eq!(
+        see_of(
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            Square::D3,
+            Square::E5,
+            None
+        ),
+        -200
+    );
+    // a queen behind a bishop
+    assert_eq!(
+        see_of(
+            "4k3/8/5p2/4p3/3B4/2Q5/8/4K3 w - - 0 1",
+            Square::D4,
+            Square::E5,
+            None
+        ),
+        -100
+    );
+}
+
+#[test]
+fn test_see_pins() {
+    // the knight on f6 is pinned to its king, so it can not defend d5
+    assert_eq!(
+        see_of(
+            "7k/8/5n2/3p4/8/2B5/8/3RK3 w - - 0 1",
+            Square::D1,
+            Square::D5,
+            None
+        ),
+        100
+    );
+    // the rook on e7 is pinned by the queen, but can still take it
+    assert_eq!(
+        see_of(
+            "4k3/4r3/8/8/4p3/8/8/4QK2 w - - 0 1",
+            Square::E1,
+            Square::E4,
+            None
+        ),
+        -800
+    );
+    // the king can not take a defended rook
+    assert_eq!(
+        see_of(
+            "3k4/3p4/8/8/8/8/3R4/3RK3 w - - 0 1",
+            Square::D2,
+            Square::D7,
+            None
+        ),
+        100
+    );
+    // the king can take an undefended rook
+    assert_eq!(
+        see_of(
+            "3k4/3p4/8/8/8/8/3R4/4K3 w - - 0 1",
+            Square::D2,
+            Square::D7,
+            None
+        ),
+        -400
+    );
+}
+
+#[test]
+fn test_see_en_passant_and_promotions() {
+    assert_eq!(
+        see_of(
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            Square::E5,
+            Square::D6,
+            None
+        ),
+        100
+    );
+    // taking en passant opens the d-file for the rook behind
+    assert_eq!(
+        see_of(
+            "3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 1",
+            Square::E5,
+            Square::D6,
+            None
+        ),
+        100
+    );
+    assert_eq!(
+        see_of(
+            "4k3/4b3/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            Square::E5,
+            Square::D6,
+            None
+        ),
+        0
+    );
+    // promoting while taking an undefended rook
+    assert_eq!(
+        see_of(
+            "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1",
+            Square::E7,
+            Square::D8,
+            Some(Piece::Queen)
+        ),
+        1300
+    );
+    // the king takes the new queen back
+    assert_eq!(
+        see_of(
+            "2kr4/4P3/8/8/8/8/8/4K3 w - - 0 1",
+            Square::E7,
+            Square::D8,
+            Some(Piece::Queen)
+        ),
+        400
+    );
+    // a pawn recaptures and promotes
+    assert_eq!(
+        see_of(
+            "1R6/7k/8/8/8/8/p7/1n2K3 w - - 0 1",
+            Square::B8,
+            Square::B1,
+            None
+        ),
+        -1000
+    );
+}
+
+#[test]
+fn test_see_ge() {
+    let board = Board::from_str("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1").expect("Valid FEN");
+    let capture = ChessMove::new(Square::E2, Square::E5, None);
+    assert!(board.see_ge(capture, 100));
+    assert!(!board.see_ge(capture, 101));
+
+    let values = [100, 300, 300, 50, 900, 0];
+    assert_eq!(board.see_with_values(capture, &values), 100);
+    assert!(board.see_ge_with_values(capture, 100, &values));
+}
+
+#[test]
+fn test_see_ge_agrees_with_see() {
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+        "3r2k1/1b3ppp/p7/1p1nB3/8/1P4P1/P4PBP/3R2K1 b - - 0 1",
+        "2r4k/1P6/8/8/8/8/6B1/4K1R1 w - - 0 1",
+        "8/8/8/3k4/3pP3/8/8/3RK3 b - e3 0 1",
+    ];
+    for fen in fens.iter() {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        for m in MoveGen::new_legal(&board) {
+            let see = board.see(m);
+            for threshold in [-1000, -300, -100, -1, 0, 1, 100, 300, 1000].iter() {
+                assert_eq!(board.see_ge(m, see + threshold), *threshold <= 0);
+            }
+        }
+    }
+}
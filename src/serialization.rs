@@ -0,0 +1,470 @@
+use crate::board::Board;
+use crate::board_builder::BoardBuilder;
+use crate::castle_rights::CastleRights;
+use crate::chess_move::ChessMove;
+use crate::color::{Color, ALL_COLORS};
+use crate::file::File;
+use crate::game::{Action, Game};
+use crate::move16::Move16;
+use crate::piece::ALL_PIECES;
+use crate::square::{Square, ALL_SQUARES};
+use crate::time_control::{ManualClock, TimeControl, TimePeriod};
+
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Human readable formats (JSON, TOML, ...) get the notation a person would write: algebraic
+// squares, UCI moves and FEN boards.  Binary formats get a compact encoding instead.
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(self.to_int())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            Square::from_str(&s).map_err(de::Error::custom)
+        } else {
+            let index = u8::deserialize(deserializer)?;
+            if (index as usize) < ALL_SQUARES.len() {
+                Ok(Square::new(index))
+            } else {
+                Err(de::Error::custom("square index out of range"))
+            }
+        }
+    }
+}
+
+impl Serialize for ChessMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u16(Move16::from(*self).to_bits())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            ChessMove::from_str(&s).map_err(de::Error::custom)
+        } else {
+            let bits = u16::deserialize(deserializer)?;
+            Move16::from_bits(bits)
+                .map(ChessMove::from)
+                .ok_or_else(|| de::Error::custom("invalid promotion piece"))
+        }
+    }
+}
+
+// A `Move16` is its bits in every format, and only bits that `Move16::from_bits` accepts can be
+// read back.
+
+impl Serialize for Move16 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.to_bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Move16 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u16::deserialize(deserializer)?;
+        Move16::from_bits(bits).ok_or_else(|| de::Error::custom("invalid promotion piece"))
+    }
+}
+
+/// The compact form of a `Board`:
+///
+/// * the occupied squares,
+/// * one nibble per occupied square (from a1 upwards) holding `color << 3 | piece`,
+/// * bit 0 set if black is to move, bit 1 set for chess960,
+/// * the castle rights of each color in the bottom 4 bits, then the king side and queen side
+///   rook files of each color in 3 bits each,
+/// * the en passant file, or 0xff,
+/// * the halfmove clock and fullmove number.
+type CompactBoard = (u64, [u8; 16], u8, u16, u8, u16, u16);
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.collect_str(self);
+        }
+
+        if self.combined().popcnt() > 32 {
+            return Err(ser::Error::custom("too many pieces on the board"));
+        }
+
+        let builder = BoardBuilder::from(self);
+
+        let mut pieces = [0u8; 16];
+        for (i, square) in self.combined().enumerate() {
+            let (piece, color) = builder[square].expect("Occupied square");
+            let nibble = (color.to_index() << 3 | piece.to_index()) as u8;
+            pieces[i / 2] |= nibble << (4 * (i % 2));
+        }
+
+        let mut flags = 0u8;
+        if builder.get_side_to_move() == Color::Black {
+            flags |= 1;
+        }
+        if builder.is_chess960() {
+            flags |= 2;
+        }
+
+        let mut castling = 0u16;
+        for color in ALL_COLORS.iter() {
+            let (king_side, queen_side) = builder.get_castle_rook_files(*color);
+            castling |=
+                (builder.get_castle_rights(*color).to_index() as u16) << (2 * color.to_index());
+            castling |= (king_side.to_index() as u16) << (4 + 6 * color.to_index());
+            castling |= (queen_side.to_index() as u16) << (7 + 6 * color.to_index());
+        }
+
+        let en_passant = match builder.get_en_passant() {
+            Some(square) => square.get_file().to_index() as u8,
+            None => 0xff,
+        };
+
+        let compact: CompactBoard = (
+            self.combined().0,
+            pieces,
+            flags,
+            castling,
+            en_passant,
+            builder.get_halfmove_clock(),
+            builder.get_fullmove_number(),
+        );
+        compact.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            return Board::from_str(&s).map_err(de::Error::custom);
+        }
+
+        let (combined, pieces, flags, castling, en_passant, halfmove_clock, fullmove_number) =
+            CompactBoard::deserialize(deserializer)?;
+
+        if combined.count_ones() > 32 {
+            return Err(de::Error::custom("too many pieces on the board"));
+        }
+
+        let mut builder = BoardBuilder::new();
+        let mut i = 0;
+        for square in ALL_SQUARES.iter() {
+            if combined & (1u64 << square.to_int()) == 0 {
+                continue;
+            }
+            let nibble = (pieces[i / 2] >> (4 * (i % 2))) & 15;
+            let piece = ALL_PIECES
+                .get((nibble & 7) as usize)
+                .ok_or_else(|| de::Error::custom("invalid piece"))?;
+            builder.piece(*square, *piece, ALL_COLORS[(nibble >> 3) as usize]);
+            i += 1;
+        }
+
+        builder.side_to_move(if flags & 1 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        });
+        builder.chess960(flags & 2 != 0);
+
+        for color in ALL_COLORS.iter() {
+            let rights = (castling >> (2 * color.to_index())) & 3;
+            let king_side = (castling >> (4 + 6 * color.to_index())) & 7;
+            let queen_side = (castling >> (7 + 6 * color.to_index())) & 7;
+            builder.castle_rights(*color, CastleRights::from_index(rights as usize));
+            builder.castle_rook_files(
+                *color,
+                File::from_index(king_side as usize),
+                File::from_index(queen_side as usize),
+            );
+        }
+
+        builder.en_passant(match en_passant {
+            0xff => None,
+            file if file < 8 => Some(File::from_index(file as usize)),
+            _ => return Err(de::Error::custom("invalid en passant file")),
+        });
+        builder.halfmove_clock(halfmove_clock);
+        builder.fullmove_number(fullmove_number);
+
+        Board::try_from(&builder).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+struct TimeControlData {
+    periods: Vec<TimePeriod>,
+}
+
+impl<'de> Deserialize<'de> for TimeControl {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TimeControlData::deserialize(deserializer)?;
+        TimeControl::from_periods(data.periods)
+            .ok_or_else(|| de::Error::custom("invalid time control periods"))
+    }
+}
+
+#[derive(Serialize)]
+struct GameRef<'a> {
+    start_position: Board,
+    actions: &'a [Action],
+    time_control: Option<&'a TimeControl>,
+    elapsed: &'a [Option<Duration>],
+}
+
+#[derive(Deserialize)]
+struct GameData {
+    start_position: Board,
+    actions: Vec<Action>,
+    #[serde(default)]
+    time_control: Option<TimeControl>,
+    #[serde(default)]
+    elapsed: Vec<Option<Duration>>,
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameRef {
+            start_position: self.start_position(),
+            actions: self.actions(),
+            time_control: self.time_control(),
+            elapsed: self.elapsed_times(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    /// Replay every action on the start position, so a `Game` can only be deserialized if each
+    /// action was allowed when it was made.
+    ///
+    /// A timed game is replayed on a `ManualClock`, moved on by the time each move took, so the
+    /// clocks come back as they were after the last move.  The clock stays stopped until
+    /// `Game::set_time_control()` is called again with a running `Clock`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = GameData::deserialize(deserializer)?;
+        let mut elapsed = data.elapsed;
+        if elapsed.is_empty() {
+            elapsed = vec![None; data.actions.len()];
+        } else if elapsed.len() != data.actions.len() {
+            return Err(de::Error::custom("elapsed times do not match the actions"));
+        }
+        if data.time_control.is_none() && elapsed.iter().any(Option::is_some) {
+            return Err(de::Error::custom("elapsed times without a time control"));
+        }
+
+        let clock = ManualClock::new();
+        let mut time_control = data.time_control;
+        let mut game = Game::new_with_board(data.start_position);
+        for (action, elapsed) in data.actions.into_iter().zip(elapsed) {
+            // the clocks started with the first timed move
+            if let Some(elapsed) = elapsed {
+                if let Some(control) = time_control.take() {
+                    game.set_time_control(control, clock.clone());
+                }
+                clock.advance(elapsed);
+            }
+
+            let accepted = match action {
+                Action::MakeMove(m) => game.make_move(m),
+                Action::OfferDraw(color) => game.offer_draw(color),
+                Action::AcceptDraw => game.accept_draw(),
+                Action::DeclineDraw => game.decline_draw(),
+                Action::DeclareDraw => game.declare_draw(),
+                Action::ClaimDraw => game.claim_draw(),
+                Action::Resign(color) => game.resign(color),
+                Action::Timeout(color) => game.timeout(color),
+                Action::TakebackRequest(color) => game.takeback_request(color),
+            };
+            if !accepted {
+                return Err(de::Error::custom(format!("illegal action {:?}", action)));
+            }
+        }
+        if let Some(control) = time_control {
+            game.set_time_control(control, clock);
+        }
+        Ok(game)
+    }
+}
+
+#[test]
+fn test_serde_square_and_move() {
+    assert_eq!(serde_json::to_string(&Square::E4).unwrap(), "\"e4\"");
+    assert_eq!(bincode::serialize(&Square::E4).unwrap(), vec![28]);
+    assert_eq!(bincode::deserialize::<Square>(&[28]).unwrap(), Square::E4);
+    assert!(bincode::deserialize::<Square>(&[64]).is_err());
+    assert!(serde_json::from_str::<Square>("\"i9\"").is_err());
+
+    let m = ChessMove::new(Square::E7, Square::E8, Some(crate::piece::Piece::Knight));
+    assert_eq!(serde_json::to_string(&m).unwrap(), "\"e7e8n\"");
+    assert_eq!(serde_json::from_str::<ChessMove>("\"e7e8n\"").unwrap(), m);
+    assert_eq!(bincode::serialize(&m).unwrap().len(), 2);
+    assert_eq!(
+        bincode::deserialize::<ChessMove>(&bincode::serialize(&m).unwrap()).unwrap(),
+        m
+    );
+    assert!(bincode::deserialize::<ChessMove>(&0x012fu16.to_le_bytes()).is_err());
+
+    let packed = Move16::from(m);
+    assert_eq!(
+        serde_json::from_str::<Move16>(&serde_json::to_string(&packed).unwrap()).unwrap(),
+        packed
+    );
+    assert_eq!(
+        bincode::deserialize::<Move16>(&bincode::serialize(&packed).unwrap()).unwrap(),
+        packed
+    );
+    assert!(serde_json::from_str::<Move16>("15").is_err());
+    assert!(bincode::deserialize::<Move16>(&0x012fu16.to_le_bytes()).is_err());
+}
+
+#[test]
+fn test_serde_board() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 17 42",
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+    ]
+    .iter()
+    {
+        let board = Board::from_str(fen).expect("Valid FEN");
+
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, format!("\"{}\"", board));
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+
+        let bytes = bincode::serialize(&board).unwrap();
+        assert!(bytes.len() < 40);
+        let decoded = bincode::deserialize::<Board>(&bytes).unwrap();
+        assert_eq!(decoded, board);
+        assert_eq!(decoded.to_string(), board.to_string());
+    }
+
+    // both kings missing
+    assert!(serde_json::from_str::<Board>("\"8/8/8/8/8/8/8/8 w - - 0 1\"").is_err());
+    let mut bytes = bincode::serialize(&Board::default()).unwrap();
+    bytes[0] = 0;
+    bytes[1] = 0;
+    assert!(bincode::deserialize::<Board>(&bytes).is_err());
+}
+
+#[test]
+fn test_serde_game() {
+    let mut game = Game::new();
+    game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    game.offer_draw(Color::White);
+    game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+    game.resign(Color::White);
+
+    let json = serde_json::to_string(&game).unwrap();
+    let decoded = serde_json::from_str::<Game>(&json).unwrap();
+    assert_eq!(decoded.actions(), game.actions());
+    assert_eq!(decoded.result(), game.result());
+
+    let bytes = bincode::serialize(&game).unwrap();
+    let decoded = bincode::deserialize::<Game>(&bytes).unwrap();
+    assert_eq!(decoded.actions(), game.actions());
+
+    // e2e4 twice in a row is not a legal game
+    let bad = json.replace("\"e7e5\"", "\"e2e4\"");
+    assert!(serde_json::from_str::<Game>(&bad).is_err());
+}
+
+#[test]
+fn test_serde_timed_game() {
+    let secs = Duration::from_secs;
+    let clock = ManualClock::new();
+    let mut game = Game::new();
+    game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    game.set_time_control(TimeControl::from_str("40/60+2:30").unwrap(), clock.clone());
+    clock.advance(secs(5));
+    game.make_move(ChessMove::new(Square::E7, Square::E5, None));
+    clock.advance(secs(7));
+    game.make_move(ChessMove::new(Square::G1, Square::F3, None));
+
+    let json = serde_json::to_string(&game).unwrap();
+    let bytes = bincode::serialize(&game).unwrap();
+    for decoded in [
+        serde_json::from_str::<Game>(&json).unwrap(),
+        bincode::deserialize::<Game>(&bytes).unwrap(),
+    ]
+    .iter()
+    {
+        assert_eq!(decoded.actions(), game.actions());
+        assert_eq!(decoded.time_control(), game.time_control());
+        assert_eq!(
+            decoded.elapsed_times(),
+            &[None, Some(secs(5)), Some(secs(7))]
+        );
+        assert_eq!(decoded.remaining_time(Color::White), Some(secs(55)));
+        assert_eq!(decoded.remaining_time(Color::Black), Some(secs(57)));
+    }
+
+    // black runs out of time, and is still out of time after a round trip
+    clock.advance(secs(60));
+    assert!(game.check_timeout());
+    let decoded = serde_json::from_str::<Game>(&serde_json::to_string(&game).unwrap()).unwrap();
+    assert_eq!(decoded.result(), game.result());
+    assert_eq!(decoded.remaining_time(Color::Black), Some(secs(0)));
+
+    // a game written before clocks were serialized has no time control
+    let untimed = json[..json.find(",\"time_control\"").unwrap()].to_string() + "}";
+    let decoded = serde_json::from_str::<Game>(&untimed).unwrap();
+    assert_eq!(decoded.time_control(), None);
+    assert_eq!(decoded.actions(), &game.actions()[..3]);
+
+    let mismatched = json.replace("[null,", "[");
+    assert!(serde_json::from_str::<Game>(&mismatched).is_err());
+
+    // a time control needs periods, and only the last can be for the rest of the game
+    let period = |moves: &str| {
+        format!(
+            "{{\"moves\":{},\"time\":{{\"secs\":60,\"nanos\":0}},\
+             \"increment\":{{\"secs\":0,\"nanos\":0}},\"delay\":{{\"secs\":0,\"nanos\":0}}}}",
+            moves
+        )
+    };
+    let control = format!("{{\"periods\":[{}]}}", period("40"));
+    assert!(serde_json::from_str::<TimeControl>(&control).is_ok());
+    for periods in [
+        String::new(),
+        period("0"),
+        format!("{},{}", period("null"), period("40")),
+    ]
+    .iter()
+    {
+        let control = format!("{{\"periods\":[{}]}}", periods);
+        assert!(
+            serde_json::from_str::<TimeControl>(&control).is_err(),
+            "{}",
+            control
+        );
+        let game = format!(
+            "{{\"start_position\":\"{}\",\"actions\":[{{\"MakeMove\":\"e2e4\"}}],\
+             \"time_control\":{},\"elapsed\":[{{\"secs\":1,\"nanos\":0}}]}}",
+            Board::default(),
+            control
+        );
+        assert!(serde_json::from_str::<Game>(&game).is_err(), "{}", game);
+    }
+}
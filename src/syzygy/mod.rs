@@ -0,0 +1,4 @@
+mod table;
+
+mod tablebase;
+pub use self::tablebase::*;
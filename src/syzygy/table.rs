@@ -0,0 +1,946 @@
+use crate::bitboard::BitBoard;
+use crate::board::Board;
+use crate::color::Color;
+use crate::error::Error;
+use crate::magic::get_king_moves;
+use crate::piece::Piece;
+use crate::square::Square;
+use crate::syzygy::Wdl;
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The most pieces, kings included, that a Syzygy table can hold.
+pub const MAX_TABLE_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags stored with every sub-table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Is this a WDL (`.rtbw`) or DTZ (`.rtbz`) table?
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/// The result of looking a position up in a single table.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TableValue {
+    /// The value stored for the position.
+    Value(i32),
+    /// DTZ tables only store one side to move, and this position has the other one.
+    ChangeStm,
+}
+
+/// The lookup tables used to turn a position into an index into a table.  They only depend on
+/// the board geometry, so they are computed once.
+pub struct Encoding {
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [u64; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    map_pawns: [u64; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+/// How far above (positive) or below (negative) the a1-h8 diagonal is this square?
+fn off_a1h8(sq: usize) -> i32 {
+    (sq >> 3) as i32 - (sq & 7) as i32
+}
+
+fn flip_file(sq: usize) -> usize {
+    sq ^ 7
+}
+
+fn flip_rank(sq: usize) -> usize {
+    sq ^ 56
+}
+
+impl Encoding {
+    pub fn new() -> Encoding {
+        let mut result = Encoding {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        // squares below the a1-h8 diagonal map to 0..28
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_a1h8(sq) < 0 {
+                result.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        // squares in the a1-d1-d4 triangle map to 0..10, with the diagonal last
+        let mut diagonal = vec![];
+        code = 0;
+        for sq in 0..28 {
+            if off_a1h8(sq) < 0 && (sq & 7) <= 3 {
+                result.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && (sq & 7) <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            result.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // the 462 legal ways to place two kings with the first one in the a1-d1-d4 triangle.
+        // if the first king is on the diagonal, the second one may not be above it.
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if result.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                let touching = get_king_moves(Square::new(s1 as u8))
+                    | BitBoard::from_square(Square::new(s1 as u8));
+                for s2 in 0..64 {
+                    let illegal =
+                        touching & BitBoard::from_square(Square::new(s2 as u8)) != BitBoard::new(0);
+                    if illegal || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    } else if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx as usize, s2));
+                    } else {
+                        result.map_kk[idx as usize][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            result.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        // binomial[k][n] is the number of ways to choose k things from n
+        result.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6 {
+                if k > n {
+                    break;
+                }
+                result.binomial[k][n] = if k > 0 {
+                    result.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { result.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // map_pawns numbers a2-h7 so that the pawn with the highest number is the leading one:
+        // closest to the edge, and lowest on the board
+        let mut available = 48;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        available -= 1;
+                        result.map_pawns[sq] = available;
+                        available -= 1;
+                        result.map_pawns[flip_file(sq)] = available;
+                    }
+                    result.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += result.binomial[lead_pawns - 1][result.map_pawns[sq] as usize];
+                }
+                result.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        result
+    }
+}
+
+/// Everything needed to decompress one sub-table: one side to move, and for tables with pawns,
+/// one file of the leading pawn.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: u64,
+    span: u64,
+    num_blocks: u64,
+    min_sym_len: u8,
+    single_value: u8,
+    lowest_sym: Vec<u16>,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: Vec<(u16, u16)>,
+    sparse_index: u64,
+    sparse_index_size: u64,
+    block_lengths: u64,
+    block_length_size: u64,
+    data: u64,
+    pieces: [u8; MAX_TABLE_PIECES],
+    group_idx: [u64; MAX_TABLE_PIECES + 1],
+    group_len: [usize; MAX_TABLE_PIECES + 1],
+    /// For DTZ tables, where the value maps for a win, loss, cursed win and blessed loss start.
+    map_idx: [u64; 4],
+}
+
+/// A single Syzygy table file.
+pub struct Table {
+    name: String,
+    file: fs::File,
+    kind: TableKind,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    piece_count: usize,
+    pawn_count: [usize; 2],
+    symmetric: bool,
+    /// The sub-tables, indexed by side to move and then by the file of the leading pawn.
+    items: Vec<Vec<PairsData>>,
+}
+
+/// Reads a table file front to back while it is being opened.
+struct HeaderReader {
+    file: fs::File,
+    offset: u64,
+    name: String,
+}
+
+impl HeaderReader {
+    fn invalid(&self) -> Error {
+        Error::InvalidTablebase {
+            name: self.name.clone(),
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0; len];
+        read_at(&mut self.file, self.offset, &mut buf)?;
+        self.offset += len as u64;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let buf = self.bytes(2)?;
+        Ok(u16::from_le_bytes([buf[0], buf[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let buf = self.bytes(4)?;
+        Ok(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+    }
+
+    fn align(&mut self, to: u64) {
+        self.offset += (to - self.offset % to) % to;
+    }
+}
+
+fn read_at(file: &mut fs::File, offset: u64, buf: &mut [u8]) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(buf))
+        .map_err(io_error)
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::Io {
+        message: error.to_string(),
+    }
+}
+
+/// Count the pieces of each kind, for one side of a table name like `KRPvKR`.
+fn count_pieces(side: &str) -> [usize; 6] {
+    let mut result = [0; 6];
+    for c in side.chars() {
+        let piece = match c {
+            'P' => Piece::Pawn,
+            'N' => Piece::Knight,
+            'B' => Piece::Bishop,
+            'R' => Piece::Rook,
+            'Q' => Piece::Queen,
+            _ => Piece::King,
+        };
+        result[piece.to_index()] += 1;
+    }
+    result
+}
+
+/// The piece code used in table files: 1 to 6 for a white pawn to king, and 9 to 14 for black.
+fn piece_code(piece: Piece, color: Color) -> u8 {
+    piece.to_index() as u8 + 1 + if color == Color::Black { 8 } else { 0 }
+}
+
+impl Table {
+    /// Open the table at `path`, for the material in `name` (like `KRvK`).
+    pub fn open(
+        path: &Path,
+        name: &str,
+        kind: TableKind,
+        encoding: &Encoding,
+    ) -> Result<Table, Error> {
+        let file = fs::File::open(path).map_err(io_error)?;
+        let invalid = || Error::InvalidTablebase {
+            name: name.to_string(),
+        };
+
+        let mut sides = name.split('v');
+        let white = count_pieces(sides.next().ok_or_else(invalid)?);
+        let black = count_pieces(sides.next().ok_or_else(invalid)?);
+
+        let white_pawns = white[Piece::Pawn.to_index()];
+        let black_pawns = black[Piece::Pawn.to_index()];
+        // the side with fewer pawns leads, because that compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        let mut table = Table {
+            name: name.to_string(),
+            file,
+            kind,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: white[..5].iter().chain(black[..5].iter()).any(|&n| n == 1),
+            piece_count: white.iter().chain(black.iter()).sum(),
+            pawn_count: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+            items: vec![],
+        };
+        if table.piece_count > MAX_TABLE_PIECES {
+            return Err(invalid());
+        }
+
+        let mut magic = [0; 4];
+        read_at(&mut table.file, 0, &mut magic)?;
+        let expected = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if magic != expected {
+            return Err(invalid());
+        }
+
+        table.init(encoding)?;
+        Ok(table)
+    }
+
+    fn invalid(&self) -> Error {
+        Error::InvalidTablebase {
+            name: self.name.clone(),
+        }
+    }
+
+    /// Read the layout of the table from its header.
+    fn init(&mut self, encoding: &Encoding) -> Result<(), Error> {
+        let mut reader = HeaderReader {
+            file: self.file.try_clone().map_err(io_error)?,
+            offset: 4,
+            name: self.name.clone(),
+        };
+
+        let flags = reader.u8()?;
+        let split = flags & 1 != 0;
+        if (flags & 2 != 0) != self.has_pawns {
+            return Err(self.invalid());
+        }
+
+        let sides = if self.kind == TableKind::Wdl && split {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let first = reader.u8()?;
+            let second = if both_pawns { reader.u8()? } else { 0xff };
+            let order = [
+                [first & 0xf, if both_pawns { second & 0xf } else { 0xf }],
+                [first >> 4, if both_pawns { second >> 4 } else { 0xf }],
+            ];
+
+            let pieces = reader.bytes(self.piece_count)?;
+            for (side, item) in items.iter_mut().enumerate() {
+                let d = &mut item[file];
+                for (k, p) in pieces.iter().enumerate() {
+                    d.pieces[k] = if side == 0 { p & 0xf } else { p >> 4 };
+                }
+                self.set_groups(encoding, d, order[side], file);
+            }
+        }
+        reader.align(2);
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                set_sizes(&mut reader, &mut item[file])?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            for d in items[0].iter_mut() {
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    reader.align(2);
+                    for i in 0..4 {
+                        d.map_idx[i] = reader.offset + 2;
+                        let len = reader.u16()? as u64;
+                        reader.offset += 2 * len;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = reader.offset + 1;
+                        let len = reader.u8()? as u64;
+                        reader.offset += len;
+                    }
+                }
+            }
+            reader.align(2);
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].sparse_index = reader.offset;
+                reader.offset += item[file].sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].block_lengths = reader.offset;
+                reader.offset += item[file].block_length_size * 2;
+            }
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                reader.align(64);
+                item[file].data = reader.offset;
+                reader.offset += item[file].num_blocks * item[file].block_size;
+            }
+        }
+
+        self.items = items;
+        Ok(())
+    }
+
+    /// Work out how the pieces are grouped, and how big each group's part of the index is.
+    fn set_groups(&self, encoding: &Encoding, d: &mut PairsData, order: [u8; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[n] = 1;
+
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    encoding.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= encoding.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= encoding.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+    }
+
+    /// Look up `board` in this table.  `flip` says whether the colors of the position have to be
+    /// swapped to match the table, which is always stored with the side named first as white.
+    pub fn probe(
+        &mut self,
+        encoding: &Encoding,
+        board: &Board,
+        flip: bool,
+        wdl: Wdl,
+    ) -> Result<TableValue, Error> {
+        let mut squares = [0usize; MAX_TABLE_PIECES];
+        let mut pieces = [0u8; MAX_TABLE_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_count = 0;
+        let mut lead_pawns = BitBoard::new(0);
+        let mut tb_file = 0;
+
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (board.side_to_move() == Color::Black) != flip;
+
+        if self.has_pawns {
+            // the pawns of the leading color always come first
+            let color = if (self.items[0][0].pieces[0] ^ flip_color) & 8 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            lead_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+            for sq in lead_pawns {
+                squares[size] = sq.to_index() ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns_count = size;
+
+            let mut lead = 0;
+            for i in 1..lead_pawns_count {
+                if encoding.map_pawns[squares[i]] > encoding.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+
+            let file = squares[0] & 7;
+            tb_file = if file > 3 { 7 - file } else { file };
+        }
+
+        let side = stm as usize % self.items.len();
+        let d = &self.items[side][tb_file];
+
+        if self.kind == TableKind::Dtz
+            && (d.flags & FLAG_STM != 0) != stm
+            && (self.has_pawns || !self.symmetric)
+        {
+            return Ok(TableValue::ChangeStm);
+        }
+
+        for sq in *board.combined() ^ lead_pawns {
+            squares[size] = sq.to_index() ^ flip_squares;
+            pieces[size] =
+                piece_code(board.piece_on(sq).unwrap(), board.color_on(sq).unwrap()) ^ flip_color;
+            size += 1;
+        }
+
+        // put the pieces in the same order as the table
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in (i + 1)..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // the leading piece always goes on files a to d
+        if squares[0] & 7 > 3 {
+            for sq in squares[..size].iter_mut() {
+                *sq = flip_file(*sq);
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = encoding.lead_pawn_idx[lead_pawns_count][squares[0]];
+            squares[1..lead_pawns_count].sort_by_key(|&sq| encoding.map_pawns[sq]);
+            for (i, &sq) in squares[..lead_pawns_count].iter().enumerate().skip(1) {
+                idx += encoding.binomial[i][encoding.map_pawns[sq] as usize];
+            }
+        } else {
+            // without pawns, the leading piece also goes on ranks 1 to 4
+            if squares[0] >> 3 > 3 {
+                for sq in squares[..size].iter_mut() {
+                    *sq = flip_rank(*sq);
+                }
+            }
+
+            // and the first piece of the leading group off the diagonal goes below it
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for sq in squares[i..size].iter_mut() {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 =
+                    (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+                let rank = |sq: usize| (sq >> 3) as u64;
+
+                idx = if off_a1h8(squares[0]) != 0 {
+                    (encoding.map_a1d1d4[squares[0]] * 63 + (squares[1] - adjust1) as u64) * 62
+                        + (squares[2] - adjust2) as u64
+                } else if off_a1h8(squares[1]) != 0 {
+                    (6 * 63 + rank(squares[0]) * 28 + encoding.map_b1h1h7[squares[1]]) * 62
+                        + (squares[2] - adjust2) as u64
+                } else if off_a1h8(squares[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(squares[0]) * 7 * 28
+                        + (rank(squares[1]) - adjust1 as u64) * 28
+                        + encoding.map_b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(squares[0]) * 7 * 6
+                        + (rank(squares[1]) - adjust1 as u64) * 6
+                        + (rank(squares[2]) - adjust2 as u64)
+                };
+            } else {
+                idx = encoding.map_kk[encoding.map_a1d1d4[squares[0]] as usize][squares[1]];
+            }
+        }
+
+        // now the remaining groups, each in ascending order
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[group_start..group_start + len].sort();
+
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| sq > s).count();
+                let below = if remaining_pawns { 8 } else { 0 };
+                n += encoding.binomial[i + 1][sq - adjust - below];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(side, tb_file, idx)?;
+        Ok(TableValue::Value(self.map_score(tb_file, value, wdl)?))
+    }
+
+    /// Find the value stored at `idx`.
+    fn decompress_pairs(&mut self, side: usize, tb_file: usize, idx: u64) -> Result<i32, Error> {
+        let d = &self.items[side][tb_file];
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(d.single_value as i32);
+        }
+
+        // the sparse index tells us roughly which block holds idx
+        let k = idx / d.span;
+        if k >= d.sparse_index_size {
+            return Err(self.invalid());
+        }
+        let mut entry = [0; 6];
+        read_at(&mut self.file, d.sparse_index + 6 * k, &mut entry)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+        let mut offset = u16::from_le_bytes([entry[4], entry[5]]) as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        // corrupt tables can point anywhere, so every block and byte is checked before use
+        let name = &self.name;
+        let invalid = || Error::InvalidTablebase { name: name.clone() };
+        let block_length = |file: &mut fs::File, block: u64| -> Result<i64, Error> {
+            if block >= d.num_blocks {
+                return Err(invalid());
+            }
+            let mut buf = [0; 2];
+            read_at(file, d.block_lengths + 2 * block, &mut buf)?;
+            Ok(u16::from_le_bytes(buf) as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(invalid)?;
+            offset += block_length(&mut self.file, block)? + 1;
+        }
+        loop {
+            let length = block_length(&mut self.file, block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+
+        // read the whole block, with a little padding for the bit buffer
+        let mut data = vec![0; d.block_size as usize + 8];
+        let start = d.data + block * d.block_size;
+        let available = self
+            .file
+            .metadata()
+            .map(|m| m.len().saturating_sub(start))
+            .unwrap_or(0)
+            .min(data.len() as u64) as usize;
+        if available < d.block_size as usize {
+            return Err(invalid());
+        }
+        read_at(&mut self.file, start, &mut data[..available])?;
+
+        let mut buf64 = u64::from_be_bytes([
+            data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+        ]);
+        let mut ptr = 8;
+        let mut buf64_size = 64;
+        let min_sym_len = d.min_sym_len as usize;
+        let mut sym;
+
+        loop {
+            let mut len = 0;
+            while buf64 < *d.base64.get(len).ok_or_else(invalid)? {
+                len += 1;
+            }
+
+            sym = ((buf64 - d.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            sym += d.lowest_sym[len] as usize;
+            if sym >= d.symlen.len() {
+                return Err(invalid());
+            }
+
+            if offset < d.symlen[sym] as i64 + 1 {
+                break;
+            }
+
+            offset -= d.symlen[sym] as i64 + 1;
+            len += min_sym_len;
+            buf64 <<= len;
+            buf64_size -= len;
+
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                let word = match data.get(ptr..ptr + 4) {
+                    Some(word) => u32::from_be_bytes([word[0], word[1], word[2], word[3]]),
+                    None => return Err(invalid()),
+                };
+                buf64 |= (word as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // expand the symbol until we reach the single value we want
+        while d.symlen[sym] != 0 {
+            let (left, right) = d.btree[sym];
+            if offset < d.symlen[left as usize] as i64 + 1 {
+                sym = left as usize;
+            } else {
+                offset -= d.symlen[left as usize] as i64 + 1;
+                sym = right as usize;
+            }
+        }
+
+        Ok(d.btree[sym].0 as i32)
+    }
+
+    /// Turn a raw table value into a WDL value, or a DTZ value in plies.
+    fn map_score(&mut self, tb_file: usize, value: i32, wdl: Wdl) -> Result<i32, Error> {
+        if self.kind == TableKind::Wdl {
+            return Ok(value - 2);
+        }
+
+        let d = &self.items[0][tb_file];
+        let flags = d.flags;
+        let mut value = value;
+        if flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Win | Wdl::Draw => d.map_idx[0],
+                Wdl::Loss => d.map_idx[1],
+                Wdl::CursedWin => d.map_idx[2],
+                Wdl::BlessedLoss => d.map_idx[3],
+            };
+            value = if flags & FLAG_WIDE != 0 {
+                let mut buf = [0; 2];
+                read_at(&mut self.file, map + 2 * value as u64, &mut buf)?;
+                u16::from_le_bytes(buf) as i32
+            } else {
+                let mut buf = [0; 1];
+                read_at(&mut self.file, map + value as u64, &mut buf)?;
+                buf[0] as i32
+            };
+        }
+
+        // some values are stored in moves rather than plies
+        if (wdl == Wdl::Win && flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        Ok(value + 1)
+    }
+}
+
+/// Read the Huffman code and block layout of a sub-table.
+fn set_sizes(reader: &mut HeaderReader, d: &mut PairsData) -> Result<(), Error> {
+    d.flags = reader.u8()?;
+
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        d.single_value = reader.u8()?;
+        return Ok(());
+    }
+
+    // group_len is zero terminated, and the last group_idx is the size of the table
+    let groups = d.group_len.iter().position(|&l| l == 0).unwrap_or(0);
+    let tb_size = d.group_idx[groups];
+
+    d.block_size = 1 << reader.u8()?;
+    let span_bits = reader.u8()?;
+    d.span = 1 << span_bits;
+    // one sparse index entry for every span values, rounding up
+    d.sparse_index_size = (tb_size + d.span - 1) >> span_bits;
+    let padding = reader.u8()? as u64;
+    d.num_blocks = reader.u32()? as u64;
+    d.block_length_size = d.num_blocks + padding;
+
+    let max_sym_len = reader.u8()? as usize;
+    d.min_sym_len = reader.u8()?;
+    let min_sym_len = d.min_sym_len as usize;
+    if max_sym_len < min_sym_len || max_sym_len > 32 {
+        return Err(reader.invalid());
+    }
+
+    let count = max_sym_len - min_sym_len + 1;
+    d.lowest_sym = (0..count)
+        .map(|_| reader.u16())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // canonical Huffman codes: longer symbols have lower values.  base64[l] is the lowest
+    // symbol of length l + min_sym_len, padded to 64 bits.
+    d.base64 = vec![0; count];
+    for i in (0..count - 1).rev() {
+        d.base64[i] = d.base64[i + 1]
+            .wrapping_add(d.lowest_sym[i] as u64)
+            .wrapping_sub(d.lowest_sym[i + 1] as u64)
+            / 2;
+    }
+    for (i, base) in d.base64.iter_mut().enumerate() {
+        *base <<= 64 - i - min_sym_len;
+    }
+
+    let symbols = reader.u16()? as usize;
+    let tree = reader.bytes(symbols * 3)?;
+    d.btree = tree
+        .chunks(3)
+        .map(|lr| {
+            (
+                ((lr[1] as u16 & 0xf) << 8) | lr[0] as u16,
+                ((lr[2] as u16) << 4) | (lr[1] as u16 >> 4),
+            )
+        })
+        .collect();
+    reader.offset += (symbols & 1) as u64;
+
+    // the symbols were built by recursive pairing, so each one stands for a run of values
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] && !set_symlen(d, sym, &mut visited) {
+            return Err(reader.invalid());
+        }
+    }
+
+    Ok(())
+}
+
+/// Work out how many values `sym` stands for, and the same for every symbol it expands into.
+/// Returns false if the tree is broken.
+fn set_symlen(d: &mut PairsData, sym: usize, visited: &mut Vec<bool>) -> bool {
+    visited[sym] = true;
+    let (left, right) = d.btree[sym];
+    if right == 0xfff {
+        d.symlen[sym] = 0;
+        return true;
+    }
+
+    let (left, right) = (left as usize, right as usize);
+    if left >= d.symlen.len() || right >= d.symlen.len() {
+        return false;
+    }
+    if !visited[left] && !set_symlen(d, left, visited) {
+        return false;
+    }
+    if !visited[right] && !set_symlen(d, right, visited) {
+        return false;
+    }
+    d.symlen[sym] = d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1);
+    true
+}
+
+#[test]
+fn test_encoding_tables() {
+    let encoding = Encoding::new();
+
+    let mut kk = vec![];
+    for idx in 0..10 {
+        for sq in 0..64 {
+            let touching = get_king_moves(Square::new(sq as u8))
+                | BitBoard::from_square(Square::new(sq as u8));
+            let first = (0..28)
+                .find(|&s| encoding.map_a1d1d4[s] == idx as u64 && (idx != 0 || s == 1))
+                .unwrap();
+            let legal = touching & BitBoard::from_square(Square::new(first as u8))
+                == BitBoard::new(0)
+                && !(off_a1h8(first) == 0 && off_a1h8(sq) > 0);
+            if legal {
+                kk.push(encoding.map_kk[idx][sq]);
+            }
+        }
+    }
+    kk.sort();
+    assert_eq!(kk, (0..462).collect::<Vec<_>>());
+
+    assert_eq!(encoding.binomial[2][5], 10);
+    assert_eq!(encoding.binomial[5][63], 7028847);
+    assert_eq!(encoding.map_pawns[Square::A2.to_index()], 47);
+    assert_eq!(encoding.map_pawns[Square::H2.to_index()], 46);
+    assert_eq!(encoding.map_pawns[Square::D7.to_index()], 1);
+    assert_eq!(encoding.map_pawns[Square::E7.to_index()], 0);
+    assert_eq!(encoding.lead_pawns_size[1], [6, 6, 6, 6]);
+}
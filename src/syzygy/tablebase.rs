@@ -0,0 +1,826 @@
+use crate::board::{Board, BoardStatus};
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::error::Error;
+use crate::movegen::MoveGen;
+use crate::piece::{Piece, ALL_PIECES};
+use crate::syzygy::table::{Encoding, Table, TableKind, TableValue, MAX_TABLE_PIECES};
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A win/draw/loss result from the tablebases, from the point of view of the side to move.
+///
+/// A cursed win is a win that takes too long to be claimed under the 50-move rule, and a blessed
+/// loss is the other side of one.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+
+    fn signum(self) -> i32 {
+        match self {
+            Wdl::Loss | Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin | Wdl::Win => 1,
+        }
+    }
+
+    /// The DTZ of a position whose best move resets the halfmove clock.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::BlessedLoss => -101,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 101,
+            Wdl::Win => 1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+/// How a search result was reached.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum ProbeState {
+    Ok,
+    /// The best move resets the halfmove clock, so the DTZ table cannot be trusted.
+    ZeroingBestMove,
+}
+
+/// A table file that is only opened the first time it is needed.
+struct LazyTable {
+    path: PathBuf,
+    table: Mutex<Option<Table>>,
+}
+
+/// Syzygy endgame tablebases.
+///
+/// Point this at one or more directories of `.rtbw` (win/draw/loss) and `.rtbz` (distance to
+/// zeroing) files.  Table files are opened the first time a position needs them, and are read
+/// from disk as needed rather than loaded into memory.
+///
+/// Positions with castling rights are never in the tables.
+///
+/// ```no_run
+/// use chess::{Board, Tablebase, Wdl};
+/// use std::str::FromStr;
+///
+/// let tablebase = Tablebase::open("/path/to/syzygy").expect("Valid directory");
+///
+/// let board = Board::from_str("8/8/8/8/8/8/6k1/KQ6 w - - 0 1").expect("Valid FEN");
+/// assert_eq!(tablebase.probe_wdl(&board).expect("Table found"), Wdl::Win);
+/// ```
+pub struct Tablebase {
+    encoding: Encoding,
+    wdl: HashMap<String, LazyTable>,
+    dtz: HashMap<String, LazyTable>,
+    max_pieces: usize,
+}
+
+impl Default for Tablebase {
+    fn default() -> Self {
+        Tablebase::new()
+    }
+}
+
+impl Tablebase {
+    /// Create a `Tablebase` without any tables.
+    pub fn new() -> Tablebase {
+        Tablebase {
+            encoding: Encoding::new(),
+            wdl: HashMap::new(),
+            dtz: HashMap::new(),
+            max_pieces: 0,
+        }
+    }
+
+    /// Create a `Tablebase` with the tables in the directory at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Tablebase, Error> {
+        let mut result = Tablebase::new();
+        result.add_directory(path)?;
+        Ok(result)
+    }
+
+    /// Add the tables in the directory at `path`, and return how many files were found.
+    ///
+    /// Files that do not look like Syzygy tables are ignored.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, Error> {
+        let entries = fs::read_dir(path).map_err(|e| Error::Io {
+            message: e.to_string(),
+        })?;
+
+        let mut count = 0;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| Error::Io {
+                    message: e.to_string(),
+                })?
+                .path();
+            let (name, extension) = match (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|s| s.to_str()),
+            ) {
+                (Some(name), Some(extension)) => (name.to_string(), extension),
+                _ => continue,
+            };
+            let pieces = match table_pieces(&name) {
+                Some(pieces) => pieces,
+                None => continue,
+            };
+
+            let tables = match extension {
+                "rtbw" => {
+                    self.max_pieces = self.max_pieces.max(pieces);
+                    &mut self.wdl
+                }
+                "rtbz" => &mut self.dtz,
+                _ => continue,
+            };
+            tables.insert(
+                name,
+                LazyTable {
+                    path: path.clone(),
+                    table: Mutex::new(None),
+                },
+            );
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The most pieces, kings included, of any WDL table that has been added.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Get the win/draw/loss result for `board`, from the point of view of the side to move.
+    ///
+    /// This does not take the halfmove clock into account, which is what `CursedWin` and
+    /// `BlessedLoss` are for.
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl, Error> {
+        self.check(board)?;
+        Ok(self.search(board, false)?.0)
+    }
+
+    /// Get the distance to zeroing for `board`: the number of plies until the halfmove clock is
+    /// reset by a capture or pawn move, when both sides play the best moves.  The sign says who
+    /// is winning: positive when the side to move wins, negative when it loses, and 0 for a draw.
+    ///
+    /// Cursed wins and blessed losses have 100 added to their distance.  Together with the
+    /// halfmove clock of `board`, this tells whether the win can be reached before the 50-move
+    /// rule lets the losing side claim a draw.  The result may be one ply longer than the true
+    /// distance for some won positions.
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32, Error> {
+        self.check(board)?;
+        self.dtz(board)
+    }
+
+    /// Get the legal moves that keep the tablebase result of `board`.
+    ///
+    /// When winning, these are the moves that win before the 50-move rule, given the halfmove
+    /// clock of `board`.  When losing, these are all the moves, unless a 50-move draw is in
+    /// reach, in which case only the moves that take longest to lose are kept.  When drawing,
+    /// these are the moves that keep the draw.
+    ///
+    /// `Board` does not know the history of the game, so this does not account for repetitions.
+    pub fn root_moves(&self, board: &Board) -> Result<Vec<ChessMove>, Error> {
+        self.check(board)?;
+        let dtz = self.dtz(board)?;
+
+        let mut scored = vec![];
+        for m in MoveGen::new_legal(board) {
+            let after = board.make_move_new(m);
+            let value = if dtz > 0 && after.status() == BoardStatus::Checkmate {
+                1
+            } else if after.halfmove_clock() == 0 {
+                (-self.search(&after, false)?.0).dtz_before_zeroing()
+            } else {
+                let value = -self.dtz(&after)?;
+                value + value.signum()
+            };
+            scored.push((m, value));
+        }
+
+        let clock = board.halfmove_clock() as i32;
+        let keep: Box<dyn Fn(i32) -> bool> = if dtz > 0 {
+            // only wins, and not more of them than the halfmove clock allows
+            let best = scored
+                .iter()
+                .map(|&(_, v)| v)
+                .filter(|&v| v > 0)
+                .min()
+                .unwrap_or(0);
+            let max = if best + clock <= 99 { 99 - clock } else { best };
+            Box::new(move |v| v > 0 && v <= max)
+        } else if dtz < 0 {
+            // every move loses, so only hold on to the longest ones if a draw is in sight
+            let best = scored.iter().map(|&(_, v)| v).min().unwrap_or(0);
+            if -best * 2 + clock < 100 {
+                Box::new(|_| true)
+            } else {
+                Box::new(move |v| v == best)
+            }
+        } else {
+            Box::new(|v| v == 0)
+        };
+
+        Ok(scored
+            .into_iter()
+            .filter(|&(_, v)| keep(v))
+            .map(|(m, _)| m)
+            .collect())
+    }
+
+    /// Make sure the tables could possibly know about `board`.
+    fn check(&self, board: &Board) -> Result<(), Error> {
+        if board.castle_rights(Color::White).to_index() != 0
+            || board.castle_rights(Color::Black).to_index() != 0
+        {
+            return Err(Error::TablebaseCastling);
+        }
+        if board.combined().popcnt() as usize > self.max_pieces.max(2) {
+            return Err(Error::MissingTablebase {
+                name: material(board, Color::White),
+            });
+        }
+        Ok(())
+    }
+
+    /// Search the captures (and with `zeroing`, the pawn moves) of `board`, because the tables
+    /// are not to be trusted when a zeroing move is best, or when en passant is possible.
+    fn search(&self, board: &Board, zeroing: bool) -> Result<(Wdl, ProbeState), Error> {
+        let mut best = Wdl::Loss;
+        let moves = MoveGen::new_legal(board);
+        let total = moves.len();
+        let mut searched = 0;
+
+        for m in moves {
+            if !is_capture(board, m)
+                && (!zeroing || board.piece_on(m.get_source()) != Some(Piece::Pawn))
+            {
+                continue;
+            }
+            searched += 1;
+
+            let value = -self.search(&board.make_move_new(m), false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Ok((value, ProbeState::ZeroingBestMove));
+                }
+            }
+        }
+
+        // if every legal move has been searched, the table value is not needed, and might be
+        // wrong because of en passant
+        let no_more_moves = searched > 0 && searched == total;
+        let value = if no_more_moves {
+            best
+        } else {
+            self.probe_wdl_table(board)?
+        };
+
+        if best >= value {
+            let state = if best > Wdl::Draw || no_more_moves {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Ok
+            };
+            Ok((best, state))
+        } else {
+            Ok((value, ProbeState::Ok))
+        }
+    }
+
+    fn dtz(&self, board: &Board) -> Result<i32, Error> {
+        let (wdl, state) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if state == ProbeState::ZeroingBestMove {
+            return Ok(wdl.dtz_before_zeroing());
+        }
+
+        if let TableValue::Value(dtz) = self.probe_table(TableKind::Dtz, board, wdl)? {
+            let cursed = wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss;
+            return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // the table only has the other side to move, so look one ply ahead for the move that
+        // reaches a zeroing move fastest
+        let mut min_dtz = 0xffff;
+        for m in MoveGen::new_legal(board) {
+            let zeroing =
+                is_capture(board, m) || board.piece_on(m.get_source()) == Some(Piece::Pawn);
+            let after = board.make_move_new(m);
+
+            let mut dtz = if zeroing {
+                -self.search(&after, false)?.0.dtz_before_zeroing()
+            } else {
+                -self.dtz(&after)?
+            };
+
+            if dtz == 1 && after.status() == BoardStatus::Checkmate {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // no legal moves means we were mated
+        Ok(if min_dtz == 0xffff { -1 } else { min_dtz })
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Result<Wdl, Error> {
+        match self.probe_table(TableKind::Wdl, board, Wdl::Draw)? {
+            TableValue::Value(value) => Ok(Wdl::from_value(value)),
+            TableValue::ChangeStm => Ok(Wdl::Draw),
+        }
+    }
+
+    /// Look `board` up in the right table, opening it first if needed.
+    fn probe_table(&self, kind: TableKind, board: &Board, wdl: Wdl) -> Result<TableValue, Error> {
+        if board.combined().popcnt() == 2 {
+            return Ok(TableValue::Value(0));
+        }
+
+        let tables = match kind {
+            TableKind::Wdl => &self.wdl,
+            TableKind::Dtz => &self.dtz,
+        };
+
+        // tables are stored with the stronger side as white
+        let white = material(board, Color::White);
+        let black = material(board, Color::Black);
+        let (name, flip) = if tables.contains_key(&white) {
+            let symmetric = white == black;
+            (white, symmetric && board.side_to_move() == Color::Black)
+        } else if tables.contains_key(&black) {
+            (black, true)
+        } else {
+            return Err(Error::MissingTablebase { name: white });
+        };
+
+        let lazy = &tables[&name];
+        let mut guard = lazy.table.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(Table::open(&lazy.path, &name, kind, &self.encoding)?);
+        }
+        guard
+            .as_mut()
+            .unwrap()
+            .probe(&self.encoding, board, flip, wdl)
+    }
+}
+
+/// Is `m` a capture, en passant included?
+fn is_capture(board: &Board, m: ChessMove) -> bool {
+    board.color_on(m.get_dest()) == Some(!board.side_to_move())
+        || (board.piece_on(m.get_source()) == Some(Piece::Pawn)
+            && m.get_source().get_file() != m.get_dest().get_file())
+}
+
+/// The table name for the material on `board`, with `first` as the side named first, like
+/// `KRPvKR`.
+fn material(board: &Board, first: Color) -> String {
+    let side = |color: Color| {
+        ALL_PIECES
+            .iter()
+            .rev()
+            .map(|&piece| {
+                let count = (board.pieces(piece) & board.color_combined(color)).popcnt() as usize;
+                piece.to_string(Color::White).repeat(count)
+            })
+            .collect::<String>()
+    };
+    format!("{}v{}", side(first), side(!first))
+}
+
+/// How many pieces are in the table called `name`, or `None` if it is not a table name.
+fn table_pieces(name: &str) -> Option<usize> {
+    let mut sides = name.split('v');
+    let (white, black) = (sides.next()?, sides.next()?);
+    let valid = |side: &str| {
+        side.starts_with('K')
+            && side[1..].chars().all(|c| "QRBNP".contains(c))
+            && side.len() < MAX_TABLE_PIECES
+    };
+    if sides.next().is_some() || !valid(white) || !valid(black) {
+        return None;
+    }
+    let pieces = white.len() + black.len();
+    if pieces <= MAX_TABLE_PIECES {
+        Some(pieces)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+use std::str::FromStr;
+
+#[test]
+fn test_tablebase_material() {
+    let board = Board::from_str("8/8/4k3/8/2r5/8/1PN5/K7 b - - 0 1").expect("Valid FEN");
+    assert_eq!(material(&board, Color::White), "KNPvKR");
+    assert_eq!(material(&board, Color::Black), "KRvKNP");
+
+    assert_eq!(table_pieces("KNPvKR"), Some(5));
+    assert_eq!(table_pieces("KvK"), Some(2));
+    assert_eq!(table_pieces("KQQQQQvKQ"), None);
+    assert_eq!(table_pieces("KRvKX"), None);
+    assert_eq!(table_pieces("KR"), None);
+    assert_eq!(table_pieces("RKvK"), None);
+}
+
+#[test]
+fn test_tablebase_without_tables() {
+    let tablebase = Tablebase::new();
+
+    let board = Board::from_str("8/8/4k3/8/8/8/8/K7 w - - 0 1").expect("Valid FEN");
+    assert_eq!(tablebase.probe_wdl(&board).ok(), Some(Wdl::Draw));
+    assert_eq!(tablebase.probe_dtz(&board).ok(), Some(0));
+
+    let board = Board::from_str("8/8/4k3/8/8/8/8/KR6 w - - 0 1").expect("Valid FEN");
+    assert_eq!(tablebase.probe_wdl(&board).ok(), None);
+
+    let board = Board::from_str("r3k3/8/8/8/8/8/8/4K3 w q - 0 1").expect("Valid FEN");
+    assert!(tablebase.probe_wdl(&board).is_err());
+    assert!(Tablebase::open("/this/directory/does/not/exist").is_err());
+}
+
+#[test]
+fn test_tablebase_wdl_neg() {
+    assert_eq!(-Wdl::Win, Wdl::Loss);
+    assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+    assert_eq!(-Wdl::Draw, Wdl::Draw);
+    assert!(Wdl::Loss < Wdl::BlessedLoss && Wdl::CursedWin < Wdl::Win);
+}
+
+#[cfg(test)]
+use crate::board_builder::BoardBuilder;
+#[cfg(test)]
+use crate::square::ALL_SQUARES;
+#[cfg(test)]
+use std::convert::TryFrom;
+
+/// The tables in `tests/fixtures/syzygy`, made by `examples/syzygy_fixtures.rs`.
+#[cfg(test)]
+fn fixture_tablebase() -> Tablebase {
+    Tablebase::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/syzygy"
+    ))
+    .expect("The fixtures exist")
+}
+
+#[cfg(test)]
+fn fen(fen: &str) -> Board {
+    Board::from_str(fen).expect("Valid FEN")
+}
+
+/// Every legal position of `pieces`, with the first piece in the a1-d1-d4 triangle unless
+/// there are pawns, which covers every position up to symmetry.
+#[cfg(test)]
+fn fixture_positions(pieces: &[(Piece, Color)], side_to_move: Color) -> Vec<Board> {
+    let has_pawns = pieces.iter().any(|&(piece, _)| piece == Piece::Pawn);
+    let mut boards = vec![];
+    let mut squares = vec![0; pieces.len()];
+    loop {
+        let first = ALL_SQUARES[squares[0]];
+        let canonical = has_pawns
+            || (first.get_file().to_index() <= 3
+                && first.get_rank().to_index() <= first.get_file().to_index());
+        let distinct = (1..squares.len()).all(|i| !squares[..i].contains(&squares[i]));
+        let pawns_placed = pieces
+            .iter()
+            .zip(squares.iter())
+            .all(|(&(piece, _), &sq)| piece != Piece::Pawn || (sq >= 8 && sq < 56));
+        if canonical && distinct && pawns_placed {
+            let mut builder = BoardBuilder::new();
+            for (&(piece, color), &sq) in pieces.iter().zip(squares.iter()) {
+                builder.piece(ALL_SQUARES[sq], piece, color);
+            }
+            builder.side_to_move(side_to_move);
+            if let Ok(board) = Board::try_from(&builder) {
+                boards.push(board);
+            }
+        }
+
+        // next placement, like counting in base 64
+        let mut i = squares.len();
+        loop {
+            if i == 0 {
+                return boards;
+            }
+            i -= 1;
+            squares[i] += 1;
+            if squares[i] < 64 {
+                break;
+            }
+            squares[i] = 0;
+        }
+    }
+}
+
+#[test]
+fn test_tablebase_fixtures_open() {
+    let mut tablebase = Tablebase::new();
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+    assert_eq!(tablebase.add_directory(dir).ok(), Some(8));
+    assert_eq!(tablebase.max_pieces(), 3);
+
+    let board = fen("8/8/4k3/8/8/8/2N5/K5N1 w - - 0 1");
+    match tablebase.probe_wdl(&board) {
+        Err(Error::MissingTablebase { name }) => assert_eq!(name, "KNNvK"),
+        _ => panic!("There are no 4 piece tables"),
+    }
+}
+
+#[test]
+fn test_tablebase_fixtures_wdl() {
+    let tablebase = fixture_tablebase();
+    let wdl = |f: &str| tablebase.probe_wdl(&fen(f)).expect("Table found");
+
+    // KQvK and KRvK are won, unless the piece is lost at once or it is stalemate
+    assert_eq!(wdl("8/8/8/8/8/8/6k1/KQ6 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("8/8/8/8/8/8/6k1/KQ6 b - - 0 1"), Wdl::Loss);
+    assert_eq!(wdl("8/8/8/8/8/8/6k1/K6Q b - - 0 1"), Wdl::Draw);
+    assert_eq!(wdl("k7/8/1Q6/8/8/8/8/K7 b - - 0 1"), Wdl::Draw);
+    assert_eq!(wdl("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), Wdl::Loss);
+    assert_eq!(wdl("8/8/8/8/8/6k1/7R/K7 b - - 0 1"), Wdl::Draw);
+    assert_eq!(wdl("8/8/8/8/8/6k1/7R/K7 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), Wdl::Win);
+
+    // with colors swapped
+    assert_eq!(wdl("K7/8/1k6/8/8/8/7q/8 b - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("k6r/8/8/8/8/8/8/7K w - - 0 1"), Wdl::Loss);
+
+    // the halfmove clock is not taken into account
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/R3K3 w - - 99 80"), Wdl::Win);
+
+    // a king on the sixth rank in front of its pawn wins, whoever is to move
+    assert_eq!(wdl("4k3/8/4K3/8/4P3/8/8/8 w - - 0 1"), Wdl::Win);
+    assert_eq!(wdl("4k3/8/4K3/8/4P3/8/8/8 b - - 0 1"), Wdl::Loss);
+    assert_eq!(wdl("8/8/8/4p3/8/4k3/8/4K3 w - - 0 1"), Wdl::Loss);
+    assert_eq!(wdl("8/8/8/4p3/8/4k3/8/4K3 b - - 0 1"), Wdl::Win);
+    // but pushing the pawn to the seventh rank too early stalemates
+    assert_eq!(wdl("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Wdl::Draw);
+    // a rook pawn does not win against a king in the corner
+    assert_eq!(wdl("k7/8/8/8/P7/8/8/K7 w - - 0 1"), Wdl::Draw);
+    assert_eq!(wdl("8/8/8/8/8/8/7p/5K1k w - - 0 1"), Wdl::Draw);
+    // and a pawn that outruns the king wins
+    assert_eq!(wdl("8/8/8/8/8/8/P7/K6k w - - 0 1"), Wdl::Win);
+}
+
+#[test]
+fn test_tablebase_fixtures_dtz() {
+    let tablebase = fixture_tablebase();
+    let dtz = |f: &str| tablebase.probe_dtz(&fen(f)).expect("Table found");
+
+    // checkmate in one, and checkmate
+    assert_eq!(dtz("k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), 1);
+    assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R w - - 0 1"), 1);
+    assert_eq!(dtz("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), -1);
+    assert_eq!(dtz("K7/8/1k6/8/8/8/7r/8 b - - 0 1"), 1);
+    assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R b - - 0 1"), -2);
+    assert_eq!(dtz("8/8/8/8/8/6k1/7R/K7 b - - 0 1"), 0);
+
+    // a pawn move resets the clock
+    assert_eq!(dtz("4k3/8/4K3/8/4P3/8/8/8 w - - 0 1"), 1);
+    assert_eq!(dtz("8/8/8/4p3/8/4k3/8/4K3 b - - 0 1"), 1);
+    assert_eq!(dtz("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), 0);
+    assert_eq!(dtz("8/8/8/8/8/8/P7/K6k w - - 0 1"), 1);
+
+    // the longest wins are mate in 10 with the queen, and mate in 16 with the rook
+    let (w, b) = (Color::White, Color::Black);
+    for &(piece, longest) in [(Piece::Queen, 19), (Piece::Rook, 31)].iter() {
+        let pieces = [(Piece::King, w), (piece, w), (Piece::King, b)];
+        let values: Vec<i32> = fixture_positions(&pieces, w)
+            .iter()
+            .map(|board| tablebase.probe_dtz(board).expect("Table found"))
+            .collect();
+        assert_eq!(values.iter().max(), Some(&longest));
+        assert!(values.iter().all(|&v| v >= 0));
+
+        let values: Vec<i32> = fixture_positions(&pieces, b)
+            .iter()
+            .map(|board| tablebase.probe_dtz(board).expect("Table found"))
+            .collect();
+        assert_eq!(values.iter().min(), Some(&-(longest + 1)));
+        assert!(values.iter().all(|&v| v <= 0));
+    }
+}
+
+/// Every position agrees with the positions after each of its moves.
+#[test]
+fn test_tablebase_fixtures_consistent() {
+    let tablebase = fixture_tablebase();
+    let (w, b) = (Color::White, Color::Black);
+    let materials = [
+        [(Piece::King, w), (Piece::Queen, w), (Piece::King, b)],
+        [(Piece::King, w), (Piece::Rook, w), (Piece::King, b)],
+        [(Piece::Pawn, w), (Piece::King, w), (Piece::King, b)],
+        [(Piece::Pawn, b), (Piece::King, w), (Piece::King, b)],
+    ];
+
+    for pieces in materials.iter() {
+        for &side_to_move in [w, b].iter() {
+            for board in fixture_positions(pieces, side_to_move).iter().step_by(13) {
+                let wdl = tablebase.probe_wdl(board).expect("Table found");
+                let dtz = tablebase.probe_dtz(board).expect("Table found");
+
+                let mut best = if board.status() == BoardStatus::Stalemate {
+                    Wdl::Draw
+                } else {
+                    Wdl::Loss
+                };
+                // the DTZ after each move, from the point of view of the side to move now
+                let mut distances = vec![];
+                for m in MoveGen::new_legal(board) {
+                    let after = board.make_move_new(m);
+                    let value = -tablebase.probe_wdl(&after).expect("Table found");
+                    best = best.max(value);
+                    if value == wdl && wdl != Wdl::Draw {
+                        let zeroing =
+                            after.halfmove_clock() == 0 || after.status() == BoardStatus::Checkmate;
+                        let child = -tablebase.probe_dtz(&after).expect("Table found");
+                        distances.push(if zeroing {
+                            wdl.signum()
+                        } else {
+                            child + child.signum()
+                        });
+                    }
+                }
+                assert_eq!(wdl, best, "{}", board);
+
+                let expected = match wdl {
+                    Wdl::Win => distances.iter().min().copied().unwrap_or(0),
+                    Wdl::Loss => distances.iter().min().copied().unwrap_or(-1),
+                    _ => 0,
+                };
+                assert_eq!(dtz, expected, "{}", board);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tablebase_fixtures_root_moves() {
+    let tablebase = fixture_tablebase();
+    let moves = |f: &str| {
+        let mut moves: Vec<String> = tablebase
+            .root_moves(&fen(f))
+            .expect("Table found")
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        moves.sort();
+        moves
+    };
+
+    // with time to spare, every winning move is kept, so only the moves that give up the
+    // queen or stalemate are dropped
+    let board = fen("k7/8/1K6/8/8/8/7Q/8 w - - 0 1");
+    let winning: Vec<String> = MoveGen::new_legal(&board)
+        .filter(|&m| tablebase.probe_wdl(&board.make_move_new(m)).ok() == Some(Wdl::Loss))
+        .map(|m| m.to_string())
+        .collect();
+    assert!(winning.len() > 10);
+    assert!(!winning.contains(&"h2c7".to_string()));
+    let mut sorted = winning.clone();
+    sorted.sort();
+    assert_eq!(moves("k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), sorted);
+
+    // with the 50-move rule about to draw, only the mate is left
+    assert_eq!(moves("k7/8/1K6/8/8/8/7Q/8 w - - 99 80"), ["h2h8"]);
+
+    // in between, the moves that win in time are kept
+    let board = fen("8/8/8/8/4k3/8/8/R3K3 w - - 0 1");
+    let best = tablebase.probe_dtz(&board).expect("Table found");
+    let clock = 99 - best;
+    let in_time = |clock: i32| {
+        let board = fen(&format!("8/8/8/8/4k3/8/8/R3K3 w - - {} 60", clock));
+        let mut kept = tablebase.root_moves(&board).expect("Table found");
+        kept.sort();
+        kept
+    };
+    let by_distance = |limit: i32| {
+        let mut kept: Vec<ChessMove> = MoveGen::new_legal(&board)
+            .filter(|&m| {
+                let after = board.make_move_new(m);
+                let value = -tablebase.probe_dtz(&after).expect("Table found");
+                value > 0 && value + 1 <= limit
+            })
+            .collect();
+        kept.sort();
+        kept
+    };
+    assert_eq!(in_time(clock), by_distance(best));
+    assert_eq!(in_time(clock - 4), by_distance(best + 4));
+    assert!(in_time(clock - 4).len() > in_time(clock).len());
+    assert_eq!(in_time(clock + 20), in_time(clock));
+
+    // a draw keeps the moves that draw
+    assert_eq!(moves("8/8/8/8/8/6k1/7R/K7 b - - 0 1"), ["g3h2"]);
+
+    // when losing, every move is kept, unless the 50-move rule could save the game
+    let board = fen("8/8/8/8/4k3/8/8/R3K3 b - - 0 1");
+    let all: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+    let longest = {
+        let values: Vec<(ChessMove, i32)> = all
+            .iter()
+            .map(|&m| {
+                (
+                    m,
+                    tablebase
+                        .probe_dtz(&board.make_move_new(m))
+                        .expect("Table found"),
+                )
+            })
+            .collect();
+        let max = values.iter().map(|&(_, v)| v).max().expect("Legal moves");
+        let mut longest: Vec<ChessMove> = values
+            .into_iter()
+            .filter(|&(_, v)| v == max)
+            .map(|(m, _)| m)
+            .collect();
+        longest.sort();
+        longest
+    };
+    assert!(longest.len() < all.len());
+    let losing = |clock: u16| {
+        let board = fen(&format!("8/8/8/8/4k3/8/8/R3K3 b - - {} 60", clock));
+        let mut kept = tablebase.root_moves(&board).expect("Table found");
+        kept.sort();
+        kept
+    };
+    assert_eq!(losing(0).len(), all.len());
+    assert_eq!(losing(90), longest);
+}
+
+/// Tables cut short give errors, instead of panics or made up values.
+#[test]
+fn test_tablebase_truncated() {
+    let fixtures = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/syzygy"
+    ));
+    let dir = std::env::temp_dir().join(format!("chess-syzygy-truncated-{}", std::process::id()));
+    let (w, b) = (Color::White, Color::Black);
+    let pieces = [(Piece::King, w), (Piece::Rook, w), (Piece::King, b)];
+    let boards = fixture_positions(&pieces, w);
+
+    for &(name, other) in [("KRvK.rtbw", "KRvK.rtbz"), ("KRvK.rtbz", "KRvK.rtbw")].iter() {
+        let data = fs::read(fixtures.join(name)).expect("The fixtures exist");
+        for &len in [0, 16, 64, data.len() / 2].iter() {
+            fs::create_dir_all(&dir).expect("Temporary directory");
+            fs::copy(fixtures.join(other), dir.join(other)).expect("Table copied");
+            fs::write(dir.join(name), &data[..len]).expect("Table written");
+
+            let tablebase = Tablebase::open(&dir).expect("Directory read");
+            let errors = boards
+                .iter()
+                .step_by(7)
+                .filter(|board| tablebase.probe_dtz(board).is_err())
+                .count();
+            assert!(errors > 0, "{} cut to {} bytes", name, len);
+        }
+    }
+    fs::remove_dir_all(&dir).expect("Temporary directory removed");
+}
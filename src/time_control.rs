@@ -0,0 +1,344 @@
+use crate::error::Error;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One period of a `TimeControl`, such as "40 moves in 90 minutes".
+///
+/// ```
+/// use chess::TimePeriod;
+/// use std::time::Duration;
+///
+/// let mut period = TimePeriod::new(Duration::from_secs(90 * 60));
+/// period.moves(40).increment(Duration::from_secs(30));
+///
+/// assert_eq!(period.get_moves(), Some(40));
+/// assert_eq!(period.get_increment(), Duration::from_secs(30));
+/// assert_eq!(period.get_delay(), Duration::from_secs(0));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimePeriod {
+    moves: Option<u32>,
+    time: Duration,
+    increment: Duration,
+    delay: Duration,
+}
+
+impl TimePeriod {
+    /// A period of `time` for the rest of the game, without an increment or delay.
+    pub fn new(time: Duration) -> TimePeriod {
+        TimePeriod {
+            moves: None,
+            time,
+            increment: Duration::from_secs(0),
+            delay: Duration::from_secs(0),
+        }
+    }
+
+    /// The number of moves to make in this period, or `None` for the rest of the game.
+    pub fn get_moves(&self) -> Option<u32> {
+        self.moves
+    }
+
+    /// The time added to the clock at the start of this period.
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    /// The time added to the clock after every move.
+    pub fn get_increment(&self) -> Duration {
+        self.increment
+    }
+
+    /// How long each move can take before the clock starts running down (a simple, or US,
+    /// delay).
+    pub fn get_delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Make `moves` moves in this period.
+    pub fn moves(&mut self, moves: u32) -> &mut Self {
+        self.moves = Some(moves);
+        self
+    }
+
+    /// Add `increment` to the clock after every move.
+    pub fn increment(&mut self, increment: Duration) -> &mut Self {
+        self.increment = increment;
+        self
+    }
+
+    /// Only start the clock once a move has taken `delay`.
+    pub fn delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// The time each player has for their moves.
+///
+/// A time control is a list of periods.  When a player has made the moves of one period, the
+/// time of the next period is added to their clock.  If the last period has a number of moves,
+/// it repeats.  Unused time carries over from one period to the next.
+///
+/// `TimeControl` converts to and from the PGN `TimeControl` tag, where periods are separated by
+/// `:`, and written as `moves/seconds`, `seconds`, or `seconds+increment`.  Delays cannot be
+/// written in the tag, and are left out.
+///
+/// ```
+/// use chess::{TimeControl, TimePeriod};
+/// use std::str::FromStr;
+/// use std::time::Duration;
+///
+/// // 40 moves in 90 minutes, then 30 minutes for the rest of the game, with 30 seconds added
+/// // after every move
+/// let mut control = TimeControl::new(
+///     *TimePeriod::new(Duration::from_secs(5400))
+///         .moves(40)
+///         .increment(Duration::from_secs(30)),
+/// );
+/// control.then(*TimePeriod::new(Duration::from_secs(1800)).increment(Duration::from_secs(30)));
+///
+/// assert_eq!(control.to_string(), "40/5400+30:1800+30");
+/// assert_eq!(TimeControl::from_str("40/5400+30:1800+30").expect("Valid"), control);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimeControl {
+    periods: Vec<TimePeriod>,
+}
+
+impl TimeControl {
+    /// A time control starting with `period`.
+    pub fn new(period: TimePeriod) -> TimeControl {
+        TimeControl {
+            periods: vec![period],
+        }
+    }
+
+    /// A time control of `periods`, if they make one: there has to be at least one period, none
+    /// can be for no moves, and only the last can be for the rest of the game.
+    pub(crate) fn from_periods(periods: Vec<TimePeriod>) -> Option<TimeControl> {
+        let last = periods.len().checked_sub(1)?;
+        let valid = periods
+            .iter()
+            .enumerate()
+            .all(|(i, period)| period.moves != Some(0) && (period.moves.is_some() || i == last));
+        if valid {
+            Some(TimeControl { periods })
+        } else {
+            None
+        }
+    }
+
+    /// Add another period, after the ones already there.
+    pub fn then(&mut self, period: TimePeriod) -> &mut Self {
+        self.periods.push(period);
+        self
+    }
+
+    /// The periods of this time control.
+    pub fn periods(&self) -> &[TimePeriod] {
+        &self.periods
+    }
+
+    /// The time left on a clock, after moves that took `elapsed`, and `thinking` into the
+    /// current move.  Returns `None` if the time ran out.
+    pub(crate) fn remaining(&self, elapsed: &[Duration], thinking: Duration) -> Option<Duration> {
+        let mut index = 0;
+        let mut moves = 0;
+        let mut left = self.periods[0].time;
+        for e in elapsed.iter() {
+            let period = self.periods[index];
+            left = left.checked_sub(e.checked_sub(period.delay).unwrap_or_default())?;
+            left += period.increment;
+            moves += 1;
+            if period.moves == Some(moves) {
+                moves = 0;
+                index = (index + 1).min(self.periods.len() - 1);
+                left += self.periods[index].time;
+            }
+        }
+        let delay = self.periods[index].delay;
+        left.checked_sub(thinking.checked_sub(delay).unwrap_or_default())
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidTimeControl {
+            time_control: s.to_string(),
+        };
+        let seconds = |t: &str| {
+            t.parse::<u64>()
+                .map(Duration::from_secs)
+                .map_err(|_| invalid())
+        };
+
+        let mut periods = vec![];
+        for part in s.trim().split(':') {
+            let (moves, rest) = match part.find('/') {
+                Some(i) => (
+                    Some(part[..i].parse().map_err(|_| invalid())?),
+                    &part[i + 1..],
+                ),
+                None => (None, part),
+            };
+            let (time, increment) = match rest.find('+') {
+                Some(i) => (seconds(&rest[..i])?, seconds(&rest[i + 1..])?),
+                None => (seconds(rest)?, Duration::from_secs(0)),
+            };
+
+            let mut period = TimePeriod::new(time);
+            period.increment(increment);
+            if let Some(moves) = moves {
+                period.moves(moves);
+            }
+            periods.push(period);
+        }
+
+        TimeControl::from_periods(periods).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = period.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", period.time.as_secs())?;
+            if period.increment > Duration::from_secs(0) {
+                write!(f, "+{}", period.increment.as_secs())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a timed `Game` gets the time from.
+///
+/// `SystemClock` uses the real time, and `ManualClock` only moves when told to, which keeps
+/// tests deterministic.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The time since some fixed moment.
+    fn now(&self) -> Duration;
+}
+
+/// A `Clock` using the system's monotonic clock.
+#[derive(Copy, Clone, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// A clock counting from now.
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A `Clock` which is moved by hand.  Clones share the same time, so one can be given to a
+/// `Game` and the other kept to move the time forward.
+///
+/// ```
+/// use chess::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let copy = clock.clone();
+/// clock.advance(Duration::from_secs(3));
+/// assert_eq!(copy.now(), Duration::from_secs(3));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// A clock at zero.
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    /// Move the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().expect("Clock lock") += duration;
+    }
+
+    /// Set the time.
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().expect("Clock lock") = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().expect("Clock lock")
+    }
+}
+
+#[test]
+fn test_time_control_tag() {
+    let control = TimeControl::from_str("300+2").expect("Valid");
+    assert_eq!(control.periods().len(), 1);
+    assert_eq!(control.periods()[0].get_moves(), None);
+    assert_eq!(control.periods()[0].get_time(), Duration::from_secs(300));
+    assert_eq!(control.periods()[0].get_increment(), Duration::from_secs(2));
+
+    let control = TimeControl::from_str("40/7200:20/3600").expect("Valid");
+    assert_eq!(control.periods()[1].get_moves(), Some(20));
+    assert_eq!(control.to_string(), "40/7200:20/3600");
+
+    for tag in [
+        "", "?", "-", "*180", "40/", "0/60", "60:40/60", "60+", "1.5",
+    ]
+    .iter()
+    {
+        assert!(TimeControl::from_str(tag).is_err(), "{}", tag);
+    }
+}
+
+#[test]
+fn test_time_control_remaining() {
+    let secs = Duration::from_secs;
+    let control = TimeControl::from_str("2/60+5:30").expect("Valid");
+
+    assert_eq!(control.remaining(&[], secs(10)), Some(secs(50)));
+    // after the second move, the next period starts
+    assert_eq!(control.remaining(&[secs(20)], secs(0)), Some(secs(45)));
+    assert_eq!(
+        control.remaining(&[secs(20), secs(20)], secs(0)),
+        Some(secs(60))
+    );
+    assert_eq!(control.remaining(&[secs(61)], secs(0)), None);
+    assert_eq!(control.remaining(&[secs(20)], secs(46)), None);
+
+    let mut delayed = TimePeriod::new(secs(60));
+    delayed.delay(secs(5));
+    let control = TimeControl::new(delayed);
+    assert_eq!(control.remaining(&[secs(3)], secs(0)), Some(secs(60)));
+    assert_eq!(control.remaining(&[secs(3)], secs(8)), Some(secs(57)));
+}
@@ -0,0 +1,635 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::error::Error;
+use crate::game::{Action, Game};
+use crate::uci::{invalid, parse_millis, parse_value};
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A command sent from a GUI to a UCI engine.
+///
+/// Parsing follows the UCI rule that unknown tokens in front of a command are skipped, and the
+/// `Display` implementation writes the command back out in its canonical form.
+///
+/// ```
+/// use chess::{Board, ChessMove, Square, UciCommand};
+/// use std::str::FromStr;
+///
+/// match UciCommand::from_str("position startpos moves e2e4 e7e5").expect("Valid command") {
+///     UciCommand::Position(position) => {
+///         assert_eq!(position.moves().len(), 2);
+///         assert_eq!(
+///             position.board(),
+///             Board::default()
+///                 .make_move_new(ChessMove::new(Square::E2, Square::E4, None))
+///                 .make_move_new(ChessMove::new(Square::E7, Square::E5, None))
+///         );
+///     }
+///     _ => panic!("Expected a position command"),
+/// }
+///
+/// assert_eq!(UciCommand::from_str("joho debug on").unwrap(), UciCommand::Debug(true));
+/// assert_eq!(UciCommand::IsReady.to_string(), "isready");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciCommand {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    /// Everything after `register`, for example `later` or `name Stefan MK code 4359874324`.
+    Register(String),
+    UciNewGame,
+    Position(UciPosition),
+    Go(UciGo),
+    Stop,
+    PonderHit,
+    Quit,
+}
+
+impl FromStr for UciCommand {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            return match token {
+                "uci" => Ok(UciCommand::Uci),
+                "debug" => match tokens.next() {
+                    Some("on") => Ok(UciCommand::Debug(true)),
+                    Some("off") => Ok(UciCommand::Debug(false)),
+                    _ => Err(invalid("expected on or off after debug")),
+                },
+                "isready" => Ok(UciCommand::IsReady),
+                "setoption" => parse_setoption(tokens),
+                "register" => Ok(UciCommand::Register(tokens.collect::<Vec<_>>().join(" "))),
+                "ucinewgame" => Ok(UciCommand::UciNewGame),
+                "position" => Ok(UciCommand::Position(parse_position(tokens)?)),
+                "go" => Ok(UciCommand::Go(parse_go(tokens)?)),
+                "stop" => Ok(UciCommand::Stop),
+                "ponderhit" => Ok(UciCommand::PonderHit),
+                "quit" => Ok(UciCommand::Quit),
+                _ => continue,
+            };
+        }
+        Err(invalid(&format!("no command found in {:?}", line)))
+    }
+}
+
+impl fmt::Display for UciCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciCommand::Uci => write!(f, "uci"),
+            UciCommand::Debug(on) => write!(f, "debug {}", if *on { "on" } else { "off" }),
+            UciCommand::IsReady => write!(f, "isready"),
+            UciCommand::SetOption { name, value } => {
+                write!(f, "setoption name {}", name)?;
+                if let Some(value) = value {
+                    write!(f, " value {}", value)?;
+                }
+                Ok(())
+            }
+            UciCommand::Register(text) => write!(f, "register {}", text),
+            UciCommand::UciNewGame => write!(f, "ucinewgame"),
+            UciCommand::Position(position) => write!(f, "{}", position),
+            UciCommand::Go(go) => write!(f, "{}", go),
+            UciCommand::Stop => write!(f, "stop"),
+            UciCommand::PonderHit => write!(f, "ponderhit"),
+            UciCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+fn parse_setoption<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<UciCommand, Error> {
+    if tokens.next() != Some("name") {
+        return Err(invalid("expected name after setoption"));
+    }
+
+    let mut name = vec![];
+    let mut value = None;
+    for token in tokens {
+        match value {
+            None if token == "value" => value = Some(vec![]),
+            None => name.push(token),
+            Some(ref mut value) => value.push(token),
+        }
+    }
+
+    if name.is_empty() {
+        return Err(invalid("expected an option name"));
+    }
+    Ok(UciCommand::SetOption {
+        name: name.join(" "),
+        value: value.map(|v| v.join(" ")),
+    })
+}
+
+fn parse_position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<UciPosition, Error> {
+    let start = match tokens.next() {
+        Some("startpos") => {
+            if let Some(token) = tokens.next() {
+                if token != "moves" {
+                    return Err(invalid("expected moves after startpos"));
+                }
+            }
+            Board::default()
+        }
+        Some("fen") => {
+            let fen = tokens
+                .by_ref()
+                .take_while(|t| *t != "moves")
+                .collect::<Vec<_>>()
+                .join(" ");
+            Board::from_str(&fen)?
+        }
+        _ => return Err(invalid("expected startpos or fen after position")),
+    };
+
+    let moves = tokens
+        .map(|m| ChessMove::from_str(m).map_err(|_| invalid(&format!("invalid move {}", m))))
+        .collect::<Result<Vec<_>, _>>()?;
+    UciPosition::new(start, moves)
+}
+
+fn parse_go<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<UciGo, Error> {
+    let mut go = UciGo::new();
+    let mut tokens = tokens.peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            "searchmoves" => {
+                while let Some(m) = tokens.peek().and_then(|t| ChessMove::from_str(t).ok()) {
+                    go.search_moves.push(m);
+                    tokens.next();
+                }
+            }
+            "ponder" => go.ponder = true,
+            "wtime" => go.wtime = Some(parse_millis(&mut tokens, token)?),
+            "btime" => go.btime = Some(parse_millis(&mut tokens, token)?),
+            "winc" => go.winc = Some(parse_millis(&mut tokens, token)?),
+            "binc" => go.binc = Some(parse_millis(&mut tokens, token)?),
+            "movestogo" => go.moves_to_go = Some(parse_value(&mut tokens, token)?),
+            "depth" => go.depth = Some(parse_value(&mut tokens, token)?),
+            "nodes" => go.nodes = Some(parse_value(&mut tokens, token)?),
+            "mate" => go.mate = Some(parse_value(&mut tokens, token)?),
+            "movetime" => go.move_time = Some(parse_millis(&mut tokens, token)?),
+            "infinite" => go.infinite = true,
+            // unknown tokens are ignored
+            _ => {}
+        }
+    }
+    Ok(go)
+}
+
+/// The position given by a `position` command: a start position and the moves played from it.
+///
+/// ```
+/// use chess::{ChessMove, Game, Square, UciPosition};
+///
+/// let mut game = Game::new();
+/// game.make_move(ChessMove::new(Square::G1, Square::F3, None));
+///
+/// let position = UciPosition::from(&game);
+/// assert_eq!(position.to_string(), "position startpos moves g1f3");
+/// assert_eq!(position.board(), game.current_position());
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UciPosition {
+    start: Board,
+    moves: Vec<ChessMove>,
+    board: Board,
+}
+
+impl UciPosition {
+    /// Play `moves` from `start`, or fail if any of them is illegal.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square, UciPosition};
+    ///
+    /// let e4 = ChessMove::new(Square::E2, Square::E4, None);
+    ///
+    /// assert!(UciPosition::new(Board::default(), vec![e4]).is_ok());
+    /// assert!(UciPosition::new(Board::default(), vec![e4, e4]).is_err());
+    /// ```
+    pub fn new(start: Board, moves: Vec<ChessMove>) -> Result<UciPosition, Error> {
+        let mut board = start;
+        for m in moves.iter() {
+            if !board.legal(*m) {
+                return Err(invalid(&format!("illegal move {}", m)));
+            }
+            board = board.make_move_new(*m);
+        }
+        Ok(UciPosition {
+            start,
+            moves,
+            board,
+        })
+    }
+
+    /// The position the moves are played from.
+    pub fn start_position(&self) -> Board {
+        self.start
+    }
+
+    /// The moves played from the start position.
+    pub fn moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    /// The position after all of the moves.
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    /// A `Game` starting from the start position, with all of the moves played.
+    pub fn game(&self) -> Game {
+        let mut game = Game::new_with_board(self.start);
+        for m in self.moves.iter() {
+            game.make_move(*m);
+        }
+        game
+    }
+}
+
+impl From<&Game> for UciPosition {
+    fn from(game: &Game) -> UciPosition {
+        let moves = game
+            .actions()
+            .iter()
+            .filter_map(|action| match action {
+                Action::MakeMove(m) => Some(*m),
+                _ => None,
+            })
+            .collect();
+        UciPosition {
+            start: game.start_position(),
+            moves,
+            board: game.current_position(),
+        }
+    }
+}
+
+impl fmt::Display for UciPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start.to_string() == Board::default().to_string() {
+            write!(f, "position startpos")?;
+        } else {
+            write!(f, "position fen {}", self.start)?;
+        }
+        if !self.moves.is_empty() {
+            write!(f, " moves")?;
+            for m in self.moves.iter() {
+                write!(f, " {}", m)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The search limits of a `go` command.
+///
+/// This can be used directly, or in a builder pattern to write a `go` command.
+///
+/// ```
+/// use chess::{Color, UciGo};
+/// use std::str::FromStr;
+/// use std::time::Duration;
+///
+/// let go = UciGo::from_str("go wtime 300000 btime 298000 winc 2000 binc 2000").expect("Valid go");
+/// assert_eq!(go.get_time(Color::Black), Some(Duration::from_millis(298000)));
+/// assert_eq!(go.get_increment(Color::White), Some(Duration::from_secs(2)));
+/// assert_eq!(go.get_depth(), None);
+///
+/// assert_eq!(
+///     UciGo::new().depth(12).ponder(true).to_string(),
+///     "go ponder depth 12"
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct UciGo {
+    search_moves: Vec<ChessMove>,
+    ponder: bool,
+    wtime: Option<Duration>,
+    btime: Option<Duration>,
+    winc: Option<Duration>,
+    binc: Option<Duration>,
+    moves_to_go: Option<u32>,
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    mate: Option<u32>,
+    move_time: Option<Duration>,
+    infinite: bool,
+}
+
+impl UciGo {
+    /// A `go` command without any limits.
+    pub fn new() -> UciGo {
+        UciGo::default()
+    }
+
+    /// Only search these moves.
+    pub fn get_search_moves(&self) -> &[ChessMove] {
+        &self.search_moves
+    }
+
+    /// Search in pondering mode?
+    pub fn get_ponder(&self) -> bool {
+        self.ponder
+    }
+
+    /// The time left on `color`s clock.
+    pub fn get_time(&self, color: Color) -> Option<Duration> {
+        match color {
+            Color::White => self.wtime,
+            Color::Black => self.btime,
+        }
+    }
+
+    /// The increment `color` gets after each move.
+    pub fn get_increment(&self, color: Color) -> Option<Duration> {
+        match color {
+            Color::White => self.winc,
+            Color::Black => self.binc,
+        }
+    }
+
+    /// The number of moves until the next time control.
+    pub fn get_moves_to_go(&self) -> Option<u32> {
+        self.moves_to_go
+    }
+
+    /// Search this many plies only.
+    pub fn get_depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    /// Search this many nodes only.
+    pub fn get_nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    /// Search for a mate in this many moves.
+    pub fn get_mate(&self) -> Option<u32> {
+        self.mate
+    }
+
+    /// Search for exactly this long.
+    pub fn get_move_time(&self) -> Option<Duration> {
+        self.move_time
+    }
+
+    /// Search until `stop`?
+    pub fn get_infinite(&self) -> bool {
+        self.infinite
+    }
+
+    /// Only search these moves.
+    pub fn search_moves(&mut self, moves: &[ChessMove]) -> &mut Self {
+        self.search_moves = moves.to_vec();
+        self
+    }
+
+    /// Search in pondering mode.
+    pub fn ponder(&mut self, ponder: bool) -> &mut Self {
+        self.ponder = ponder;
+        self
+    }
+
+    /// Set the time left on `color`s clock.
+    pub fn time(&mut self, color: Color, time: Duration) -> &mut Self {
+        match color {
+            Color::White => self.wtime = Some(time),
+            Color::Black => self.btime = Some(time),
+        }
+        self
+    }
+
+    /// Set the increment `color` gets after each move.
+    pub fn increment(&mut self, color: Color, increment: Duration) -> &mut Self {
+        match color {
+            Color::White => self.winc = Some(increment),
+            Color::Black => self.binc = Some(increment),
+        }
+        self
+    }
+
+    /// Set the number of moves until the next time control.
+    pub fn moves_to_go(&mut self, moves_to_go: u32) -> &mut Self {
+        self.moves_to_go = Some(moves_to_go);
+        self
+    }
+
+    /// Search this many plies only.
+    pub fn depth(&mut self, depth: u32) -> &mut Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Search this many nodes only.
+    pub fn nodes(&mut self, nodes: u64) -> &mut Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Search for a mate in this many moves.
+    pub fn mate(&mut self, mate: u32) -> &mut Self {
+        self.mate = Some(mate);
+        self
+    }
+
+    /// Search for exactly this long.
+    pub fn move_time(&mut self, move_time: Duration) -> &mut Self {
+        self.move_time = Some(move_time);
+        self
+    }
+
+    /// Search until `stop`.
+    pub fn infinite(&mut self, infinite: bool) -> &mut Self {
+        self.infinite = infinite;
+        self
+    }
+}
+
+impl FromStr for UciGo {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        match UciCommand::from_str(line)? {
+            UciCommand::Go(go) => Ok(go),
+            _ => Err(invalid("expected a go command")),
+        }
+    }
+}
+
+impl fmt::Display for UciGo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "go")?;
+        if !self.search_moves.is_empty() {
+            write!(f, " searchmoves")?;
+            for m in self.search_moves.iter() {
+                write!(f, " {}", m)?;
+            }
+        }
+        if self.ponder {
+            write!(f, " ponder")?;
+        }
+        let times = [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+        ];
+        for (name, time) in times.iter() {
+            if let Some(time) = time {
+                write!(f, " {} {}", name, time.as_millis())?;
+            }
+        }
+        if let Some(moves_to_go) = self.moves_to_go {
+            write!(f, " movestogo {}", moves_to_go)?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(mate) = self.mate {
+            write!(f, " mate {}", mate)?;
+        }
+        if let Some(move_time) = self.move_time {
+            write!(f, " movetime {}", move_time.as_millis())?;
+        }
+        if self.infinite {
+            write!(f, " infinite")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use crate::piece::Piece;
+#[cfg(test)]
+use crate::square::Square;
+
+#[test]
+fn test_uci_command_transcript() {
+    // the GUI side of a session with an engine, with each kind of command a GUI can send
+    let transcript = "uci\n\
+                      debug off\n\
+                      setoption name Hash value 128\n\
+                      setoption name Clear Hash\n\
+                      setoption name SyzygyPath value /home/me/syzygy tables\n\
+                      register later\n\
+                      isready\n\
+                      ucinewgame\n\
+                      position startpos\n\
+                      go wtime 300000 btime 300000 winc 2000 binc 2000\n\
+                      position startpos moves e2e4 c7c5 g1f3\n\
+                      go ponder wtime 297520 btime 299010 winc 2000 binc 2000 movestogo 38\n\
+                      ponderhit\n\
+                      position fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1 moves e2e4 c7c5\n\
+                      go searchmoves b4b1 b4f4 depth 20 nodes 1000000 mate 5 movetime 1500\n\
+                      go infinite\n\
+                      stop\n\
+                      quit";
+
+    for line in transcript.lines() {
+        let command = UciCommand::from_str(line).expect("Valid command");
+        assert_eq!(command.to_string(), line);
+    }
+
+    match UciCommand::from_str("setoption name SyzygyPath value /home/me/syzygy tables") {
+        Ok(UciCommand::SetOption { name, value }) => {
+            assert_eq!(name, "SyzygyPath");
+            assert_eq!(value, Some("/home/me/syzygy tables".to_string()));
+        }
+        _ => panic!("Expected setoption"),
+    }
+
+    match UciCommand::from_str(
+        "position fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1 moves e2e4 c7c5",
+    ) {
+        Ok(UciCommand::Position(position)) => {
+            let game = position.game();
+            assert_eq!(game.actions().len(), 2);
+            assert_eq!(game.current_position(), position.board());
+            assert_eq!(position.board().piece_on(Square::C5), Some(Piece::Pawn));
+        }
+        _ => panic!("Expected position"),
+    }
+}
+
+#[test]
+fn test_uci_position_counters() {
+    // the start position with other move counters needs its FEN to keep them
+    for line in [
+        "position startpos",
+        "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 12",
+        "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 12 moves e2e4",
+    ]
+    .iter()
+    {
+        let command = UciCommand::from_str(line).expect("Valid command");
+        assert_eq!(command.to_string(), *line);
+    }
+}
+
+#[test]
+fn test_uci_go_limits() {
+    let go = UciGo::from_str(
+        "go searchmoves e2e4 d2d4 ponder wtime -20 btime 1000 winc 10 binc 20 movestogo 5 \
+         depth 7 nodes 123 mate 3 movetime 50 infinite",
+    )
+    .expect("Valid go");
+
+    assert_eq!(
+        go.get_search_moves(),
+        &[
+            ChessMove::new(Square::E2, Square::E4, None),
+            ChessMove::new(Square::D2, Square::D4, None)
+        ]
+    );
+    assert!(go.get_ponder());
+    assert_eq!(go.get_time(Color::White), Some(Duration::from_millis(0)));
+    assert_eq!(go.get_time(Color::Black), Some(Duration::from_millis(1000)));
+    assert_eq!(
+        go.get_increment(Color::White),
+        Some(Duration::from_millis(10))
+    );
+    assert_eq!(
+        go.get_increment(Color::Black),
+        Some(Duration::from_millis(20))
+    );
+    assert_eq!(go.get_moves_to_go(), Some(5));
+    assert_eq!(go.get_depth(), Some(7));
+    assert_eq!(go.get_nodes(), Some(123));
+    assert_eq!(go.get_mate(), Some(3));
+    assert_eq!(go.get_move_time(), Some(Duration::from_millis(50)));
+    assert!(go.get_infinite());
+
+    assert_eq!(UciGo::from_str("go").expect("Valid go"), UciGo::new());
+}
+
+#[test]
+fn test_uci_command_invalid() {
+    assert!(UciCommand::from_str("").is_err());
+    assert!(UciCommand::from_str("hello world").is_err());
+    assert!(UciCommand::from_str("debug maybe").is_err());
+    assert!(UciCommand::from_str("setoption Hash value 1").is_err());
+    assert!(UciCommand::from_str("position").is_err());
+    assert!(UciCommand::from_str("position startpos moves e2e5").is_err());
+    assert!(UciCommand::from_str("position startpos moves e2").is_err());
+    assert!(UciCommand::from_str("position fen 8/8/8 w - - moves").is_err());
+    // the en passant capture would expose the king
+    assert!(UciCommand::from_str(
+        "position fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1 moves e2e4 f4e3"
+    )
+    .is_err());
+    assert!(UciCommand::from_str("go depth").is_err());
+    assert!(UciCommand::from_str("go nodes lots").is_err());
+}
@@ -0,0 +1,442 @@
+use crate::board::Board;
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+use crate::game::Game;
+use crate::uci::{invalid, UciCommand, UciGo, UciInfo, UciMessage, UciOption, UciPosition};
+
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long `UciEngine::with_command()` waits for `uciok` and `readyok`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A UCI engine running in another process.
+///
+/// `UciEngine::new()` launches the engine and goes through the `uci` and `isready` handshake,
+/// remembering the engine's name, author and options.  After that, `set_position()` and `go()`
+/// run a search.  The moves the engine sends back (the best move, and every principal variation)
+/// are checked against the position it was given, so a confused engine gives an error instead of
+/// an illegal move.
+///
+/// An engine that does not answer `uci` or `isready` within the timeout (10 seconds unless set
+/// otherwise), or that exits, gives an error instead of blocking forever.
+///
+/// ```no_run
+/// use chess::{Game, UciEngine, UciGo};
+///
+/// let mut engine = UciEngine::new("stockfish").expect("Engine started");
+/// engine.set_option("Threads", Some("4")).expect("Option set");
+///
+/// engine.set_position(&Game::new()).expect("Position set");
+/// let search = engine.go(UciGo::new().depth(20)).expect("Search finished");
+///
+/// println!("{} plays {:?}", engine.name().unwrap_or("?"), search.best_move());
+/// if let Some(info) = search.principal_variation() {
+///     println!("score {:?} pv {:?}", info.get_score(), info.get_pv());
+/// }
+/// ```
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<std::io::Result<String>>,
+    timeout: Duration,
+    name: Option<String>,
+    author: Option<String>,
+    options: Vec<UciOption>,
+    board: Board,
+}
+
+impl UciEngine {
+    /// Launch the engine binary at `program`.
+    pub fn new<P: AsRef<OsStr>>(program: P) -> Result<UciEngine, Error> {
+        UciEngine::with_command(Command::new(program))
+    }
+
+    /// Launch an engine with a `Command`, for when it needs arguments or a working directory.
+    /// Its standard input and output are replaced with pipes.
+    pub fn with_command(command: Command) -> Result<UciEngine, Error> {
+        UciEngine::with_timeout(command, DEFAULT_TIMEOUT)
+    }
+
+    /// Launch an engine with a `Command`, waiting at most `timeout` for it to answer `uci` and
+    /// `isready`.
+    pub fn with_timeout(mut command: Command, timeout: Duration) -> Result<UciEngine, Error> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(io_error)?;
+        let stdin = child.stdin.take().expect("Piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("Piped stdout"));
+
+        // the output is read on its own thread, so that waiting for it can time out
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                let failed = line.is_err();
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine {
+            child,
+            stdin,
+            lines,
+            timeout,
+            name: None,
+            author: None,
+            options: vec![],
+            board: Board::default(),
+        };
+
+        engine.send(&UciCommand::Uci)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match engine.read_message_before(Some(deadline), "uciok")? {
+                UciMessage::IdName(name) => engine.name = Some(name),
+                UciMessage::IdAuthor(author) => engine.author = Some(author),
+                UciMessage::Option(option) => engine.options.push(option),
+                UciMessage::UciOk => break,
+                _ => {}
+            }
+        }
+        engine.is_ready()?;
+        Ok(engine)
+    }
+
+    /// The name the engine gave in the handshake.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The author the engine gave in the handshake.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// The options the engine gave in the handshake.
+    pub fn options(&self) -> &[UciOption] {
+        &self.options
+    }
+
+    /// How long `is_ready()` waits for `readyok`.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set how long `is_ready()` waits for `readyok`.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The position the engine was last given.
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    /// Send a command to the engine.  A `position` command also becomes the position that moves
+    /// from the engine are checked against.
+    pub fn send(&mut self, command: &UciCommand) -> Result<(), Error> {
+        if let UciCommand::Position(position) = command {
+            self.board = position.board();
+        }
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(io_error)
+    }
+
+    /// Read the next message from the engine, skipping any lines that are not UCI messages.
+    pub fn read_message(&mut self) -> Result<UciMessage, Error> {
+        self.read_message_before(None, "a message")
+    }
+
+    /// Read the next message, giving up at `deadline` (if there is one) with an error saying the
+    /// engine never sent `expected`.
+    fn read_message_before(
+        &mut self,
+        deadline: Option<Instant>,
+        expected: &str,
+    ) -> Result<UciMessage, Error> {
+        loop {
+            let line = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    match self.lines.recv_timeout(left) {
+                        Ok(line) => Some(line),
+                        Err(RecvTimeoutError::Timeout) => {
+                            return Err(Error::Io {
+                                message: format!("the engine did not send {} in time", expected),
+                            });
+                        }
+                        Err(RecvTimeoutError::Disconnected) => None,
+                    }
+                }
+                None => self.lines.recv().ok(),
+            };
+            let line = match line {
+                Some(line) => line.map_err(io_error)?,
+                None => {
+                    return Err(Error::Io {
+                        message: "the engine closed its output".to_string(),
+                    });
+                }
+            };
+            if let Ok(message) = UciMessage::from_str(&line) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Send `isready`, and wait for `readyok`, giving up after `timeout()`.
+    pub fn is_ready(&mut self) -> Result<(), Error> {
+        self.send(&UciCommand::IsReady)?;
+        let deadline = Instant::now() + self.timeout;
+        while self.read_message_before(Some(deadline), "readyok")? != UciMessage::ReadyOk {}
+        Ok(())
+    }
+
+    /// Set an engine option, and wait until the engine is ready again.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), Error> {
+        self.send(&UciCommand::SetOption {
+            name: name.to_string(),
+            value: value.map(|v| v.to_string()),
+        })?;
+        self.is_ready()
+    }
+
+    /// Tell the engine the next search is from a different game, and wait until it is ready.
+    pub fn new_game(&mut self) -> Result<(), Error> {
+        self.send(&UciCommand::UciNewGame)?;
+        self.is_ready()
+    }
+
+    /// Send the current position of `game`, as its start position and moves.
+    pub fn set_position(&mut self, game: &Game) -> Result<(), Error> {
+        self.send(&UciCommand::Position(UciPosition::from(game)))
+    }
+
+    /// Search the current position and wait for the best move.
+    ///
+    /// This blocks until the engine sends `bestmove`, so for `go infinite` or pondering, `send()`
+    /// and `read_message()` should be used instead.
+    ///
+    /// If the engine sends an illegal move, the rest of the search is still read up to `bestmove`
+    /// before the error is returned, so the next command starts from a quiet engine.
+    pub fn go(&mut self, go: &UciGo) -> Result<UciSearch, Error> {
+        self.send(&UciCommand::Go(go.clone()))?;
+
+        let mut infos = vec![];
+        let mut error = None;
+        loop {
+            match self.read_message()? {
+                UciMessage::Info(info) => {
+                    // keep the first illegal move, and read on to the end of the search
+                    if error.is_none() {
+                        let currmove = info.get_currmove().into_iter().collect::<Vec<_>>();
+                        error = validate_moves(self.board, info.get_pv())
+                            .and_then(|_| validate_moves(self.board, &currmove))
+                            .err();
+                    }
+                    infos.push(info);
+                }
+                UciMessage::BestMove { best_move, ponder } => {
+                    if let Some(e) = error {
+                        return Err(e);
+                    }
+                    let moves = best_move.iter().chain(ponder.iter()).cloned();
+                    validate_moves(self.board, &moves.collect::<Vec<_>>())?;
+                    return Ok(UciSearch {
+                        best_move,
+                        ponder,
+                        infos,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Send `quit`, and wait for the engine to exit.
+    pub fn quit(mut self) -> Result<ExitStatus, Error> {
+        self.send(&UciCommand::Quit)?;
+        self.child.wait().map_err(io_error)
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// The result of `UciEngine::go()`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UciSearch {
+    best_move: Option<ChessMove>,
+    ponder: Option<ChessMove>,
+    infos: Vec<UciInfo>,
+}
+
+impl UciSearch {
+    /// The move the engine plays, or `None` if it had no legal moves.
+    pub fn best_move(&self) -> Option<ChessMove> {
+        self.best_move
+    }
+
+    /// The reply the engine expects.
+    pub fn ponder(&self) -> Option<ChessMove> {
+        self.ponder
+    }
+
+    /// Every `info` message the engine sent during the search.
+    pub fn infos(&self) -> &[UciInfo] {
+        &self.infos
+    }
+
+    /// The last `info` message with a principal variation for the best line.
+    pub fn principal_variation(&self) -> Option<&UciInfo> {
+        self.infos
+            .iter()
+            .rev()
+            .find(|info| !info.get_pv().is_empty() && info.get_multipv().unwrap_or(1) == 1)
+    }
+}
+
+/// Check that `moves` can be played, one after another, from `board`.
+fn validate_moves(mut board: Board, moves: &[ChessMove]) -> Result<(), Error> {
+    for m in moves.iter() {
+        if !board.legal(*m) {
+            return Err(invalid(&format!("the engine sent the illegal move {}", m)));
+        }
+        board = board.make_move_new(*m);
+    }
+    Ok(())
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::Io {
+        message: error.to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+use crate::square::Square;
+
+/// A stand-in engine, which answers every `go` with the same canned search.
+#[cfg(all(test, unix))]
+fn stand_in_engine() -> UciEngine {
+    let script = r#"
+        echo "Stand-in engine, for testing only"
+        while read -r line; do
+            case "$line" in
+                uci)
+                    echo "id name Stand-in"
+                    echo "id author Nobody"
+                    echo "option name Hash type spin default 16 min 1 max 1024"
+                    echo "option name Ponder type check default false"
+                    echo "uciok" ;;
+                isready) echo "readyok" ;;
+                go*)
+                    echo "info string $line"
+                    echo "info depth 1 seldepth 1 multipv 1 score cp 20 nodes 30 pv e7e5"
+                    echo "info depth 2 currmove e7e5 currmovenumber 1"
+                    echo "info depth 2 seldepth 3 multipv 2 score cp 5 nodes 80 pv c7c5 g1f3"
+                    echo "info depth 2 seldepth 3 multipv 1 score cp 15 nodes 90 pv e7e5 g1f3"
+                    echo "bestmove e7e5 ponder g1f3" ;;
+                quit) exit 0 ;;
+            esac
+        done
+    "#;
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    UciEngine::with_command(command).expect("Stand-in engine started")
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uci_engine_search() {
+    let mut engine = stand_in_engine();
+    assert_eq!(engine.name(), Some("Stand-in"));
+    assert_eq!(engine.author(), Some("Nobody"));
+    assert_eq!(engine.options().len(), 2);
+    assert_eq!(engine.options()[0].name(), "Hash");
+
+    engine.set_option("Hash", Some("64")).expect("Option set");
+    engine.new_game().expect("New game");
+
+    let mut game = Game::new();
+    game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    engine.set_position(&game).expect("Position set");
+    assert_eq!(engine.board(), game.current_position());
+
+    let search = engine.go(UciGo::new().depth(2)).expect("Search finished");
+    let e5 = ChessMove::new(Square::E7, Square::E5, None);
+    let nf3 = ChessMove::new(Square::G1, Square::F3, None);
+    assert_eq!(search.best_move(), Some(e5));
+    assert_eq!(search.ponder(), Some(nf3));
+    assert_eq!(search.infos().len(), 5);
+    assert_eq!(search.infos()[0].get_string(), Some("go depth 2"));
+
+    let pv = search.principal_variation().expect("A principal variation");
+    assert_eq!(pv.get_depth(), Some(2));
+    assert_eq!(pv.get_score(), Some(crate::uci::UciScore::Centipawns(15)));
+    assert_eq!(pv.get_pv(), &[e5, nf3]);
+
+    assert!(engine.quit().expect("Engine quit").success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uci_engine_illegal_pv() {
+    let mut engine = stand_in_engine();
+
+    // the canned moves are for black, so they are illegal with white to move
+    engine.set_position(&Game::new()).expect("Position set");
+    match engine.go(UciGo::new().depth(2)) {
+        Err(Error::InvalidUci { message }) => assert!(message.contains("e7e5")),
+        _ => panic!("Expected an illegal move"),
+    }
+
+    // the rest of that search was read, so the next one only sees its own output
+    let mut game = Game::new();
+    game.make_move(ChessMove::new(Square::E2, Square::E4, None));
+    engine.set_position(&game).expect("Position set");
+    let search = engine.go(UciGo::new().depth(2)).expect("Search finished");
+    assert_eq!(search.infos().len(), 5);
+    assert_eq!(search.infos()[0].get_string(), Some("go depth 2"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uci_engine_silent() {
+    // never answers uci
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("while read -r line; do :; done");
+    match UciEngine::with_timeout(command, Duration::from_millis(200)) {
+        Err(Error::Io { message }) => assert!(message.contains("uciok")),
+        _ => panic!("Expected a timeout"),
+    }
+
+    // exits straight away
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("exit 0");
+    match UciEngine::with_command(command) {
+        Err(Error::Io { .. }) => {}
+        _ => panic!("Expected the engine to have closed its output"),
+    }
+}
+
+#[test]
+fn test_uci_engine_missing() {
+    assert!(UciEngine::new("/this/engine/does/not/exist").is_err());
+}
@@ -0,0 +1,682 @@
+use crate::chess_move::ChessMove;
+use crate::error::Error;
+use crate::uci::{invalid, parse_millis, parse_value};
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A message sent from a UCI engine to a GUI.
+///
+/// Like `UciCommand`, parsing skips unknown tokens in front of the message, and the `Display`
+/// implementation writes the message in its canonical form.
+///
+/// ```
+/// use chess::{ChessMove, Square, UciInfo, UciMessage, UciScore};
+/// use std::str::FromStr;
+///
+/// let e4 = ChessMove::new(Square::E2, Square::E4, None);
+/// let e5 = ChessMove::new(Square::E7, Square::E5, None);
+///
+/// assert_eq!(
+///     UciMessage::from_str("bestmove e2e4 ponder e7e5").unwrap(),
+///     UciMessage::BestMove { best_move: Some(e4), ponder: Some(e5) }
+/// );
+///
+/// let info = UciInfo::new()
+///     .depth(1)
+///     .score(UciScore::Centipawns(30))
+///     .pv(&[e4])
+///     .clone();
+/// assert_eq!(UciMessage::Info(info).to_string(), "info depth 1 score cp 30 pv e2e4");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciMessage {
+    IdName(String),
+    IdAuthor(String),
+    UciOk,
+    ReadyOk,
+    /// The move the engine plays, or `None` if it had no legal moves, and the move it would like
+    /// to ponder on.
+    BestMove {
+        best_move: Option<ChessMove>,
+        ponder: Option<ChessMove>,
+    },
+    /// `checking`, `ok` or `error`.
+    CopyProtection(String),
+    /// `checking`, `ok` or `error`.
+    Registration(String),
+    Info(UciInfo),
+    Option(UciOption),
+}
+
+impl FromStr for UciMessage {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut tokens = line.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            return match token {
+                "id" => {
+                    let kind = tokens.next();
+                    let value = tokens.collect::<Vec<_>>().join(" ");
+                    match kind {
+                        Some("name") => Ok(UciMessage::IdName(value)),
+                        Some("author") => Ok(UciMessage::IdAuthor(value)),
+                        _ => Err(invalid("expected name or author after id")),
+                    }
+                }
+                "uciok" => Ok(UciMessage::UciOk),
+                "readyok" => Ok(UciMessage::ReadyOk),
+                "bestmove" => {
+                    let best_move = match tokens.next() {
+                        Some("(none)") | Some("0000") => None,
+                        Some(m) => Some(parse_move(m)?),
+                        None => return Err(invalid("expected a move after bestmove")),
+                    };
+                    let ponder = match (tokens.next(), tokens.next()) {
+                        (Some("ponder"), Some(m)) => Some(parse_move(m)?),
+                        _ => None,
+                    };
+                    Ok(UciMessage::BestMove { best_move, ponder })
+                }
+                "copyprotection" => Ok(UciMessage::CopyProtection(
+                    tokens.collect::<Vec<_>>().join(" "),
+                )),
+                "registration" => Ok(UciMessage::Registration(
+                    tokens.collect::<Vec<_>>().join(" "),
+                )),
+                "info" => Ok(UciMessage::Info(parse_info(tokens)?)),
+                "option" => Ok(UciMessage::Option(parse_option(tokens)?)),
+                _ => continue,
+            };
+        }
+        Err(invalid(&format!("no message found in {:?}", line)))
+    }
+}
+
+impl fmt::Display for UciMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciMessage::IdName(name) => write!(f, "id name {}", name),
+            UciMessage::IdAuthor(author) => write!(f, "id author {}", author),
+            UciMessage::UciOk => write!(f, "uciok"),
+            UciMessage::ReadyOk => write!(f, "readyok"),
+            UciMessage::BestMove { best_move, ponder } => {
+                match best_move {
+                    Some(m) => write!(f, "bestmove {}", m)?,
+                    None => write!(f, "bestmove (none)")?,
+                }
+                if let Some(ponder) = ponder {
+                    write!(f, " ponder {}", ponder)?;
+                }
+                Ok(())
+            }
+            UciMessage::CopyProtection(status) => write!(f, "copyprotection {}", status),
+            UciMessage::Registration(status) => write!(f, "registration {}", status),
+            UciMessage::Info(info) => write!(f, "{}", info),
+            UciMessage::Option(option) => write!(f, "{}", option),
+        }
+    }
+}
+
+fn parse_move(m: &str) -> Result<ChessMove, Error> {
+    ChessMove::from_str(m).map_err(|_| invalid(&format!("invalid move {}", m)))
+}
+
+/// Parse moves until a token that is not a move.
+fn parse_moves<'a, I: Iterator<Item = &'a str>>(tokens: &mut Peekable<I>) -> Vec<ChessMove> {
+    let mut moves = vec![];
+    while let Some(m) = tokens.peek().and_then(|t| ChessMove::from_str(t).ok()) {
+        moves.push(m);
+        tokens.next();
+    }
+    moves
+}
+
+fn parse_info<'a, I: Iterator<Item = &'a str>>(mut tokens: Peekable<I>) -> Result<UciInfo, Error> {
+    let mut info = UciInfo::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = Some(parse_value(&mut tokens, token)?),
+            "seldepth" => info.seldepth = Some(parse_value(&mut tokens, token)?),
+            "multipv" => info.multipv = Some(parse_value(&mut tokens, token)?),
+            "score" => {
+                let kind = tokens.next();
+                let value = parse_value(&mut tokens, token)?;
+                info.score = Some(match kind {
+                    Some("cp") => UciScore::Centipawns(value),
+                    Some("mate") => UciScore::Mate(value),
+                    _ => return Err(invalid("expected cp or mate after score")),
+                });
+            }
+            "lowerbound" => info.bound = Some(UciBound::Lower),
+            "upperbound" => info.bound = Some(UciBound::Upper),
+            "nodes" => info.nodes = Some(parse_value(&mut tokens, token)?),
+            "nps" => info.nps = Some(parse_value(&mut tokens, token)?),
+            "hashfull" => info.hashfull = Some(parse_value(&mut tokens, token)?),
+            "tbhits" => info.tbhits = Some(parse_value(&mut tokens, token)?),
+            "time" => info.time = Some(parse_millis(&mut tokens, token)?),
+            "currmove" => info.currmove = Some(parse_move(tokens.next().unwrap_or(""))?),
+            "currmovenumber" => info.currmovenumber = Some(parse_value(&mut tokens, token)?),
+            "cpuload" => info.cpuload = Some(parse_value(&mut tokens, token)?),
+            "pv" => info.pv = parse_moves(&mut tokens),
+            "string" => {
+                info.string = Some(tokens.collect::<Vec<_>>().join(" "));
+                break;
+            }
+            // unknown tokens are ignored
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+fn parse_option<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<UciOption, Error> {
+    // split the tokens up into (keyword, value) sections
+    let mut sections: Vec<(&str, Vec<&str>)> = vec![];
+    for token in tokens {
+        match token {
+            "name" | "type" | "default" | "min" | "max" | "var" => sections.push((token, vec![])),
+            _ => match sections.last_mut() {
+                Some((_, value)) => value.push(token),
+                None => return Err(invalid("expected name after option")),
+            },
+        }
+    }
+
+    let value = |keyword: &str| {
+        sections
+            .iter()
+            .find(|(k, _)| *k == keyword)
+            .map(|(_, v)| v.join(" "))
+    };
+    let number = |keyword: &str| {
+        value(keyword)
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or_else(|| invalid(&format!("expected a number after {}", keyword)))
+    };
+    let default = || value("default").unwrap_or_default();
+
+    let name = value("name").ok_or_else(|| invalid("expected name after option"))?;
+    let kind = match value("type").as_deref() {
+        Some("check") => UciOptionKind::Check {
+            default: default() == "true",
+        },
+        Some("spin") => UciOptionKind::Spin {
+            default: number("default")?,
+            min: number("min")?,
+            max: number("max")?,
+        },
+        Some("combo") => UciOptionKind::Combo {
+            default: default(),
+            vars: sections
+                .iter()
+                .filter(|(k, _)| *k == "var")
+                .map(|(_, v)| v.join(" "))
+                .collect(),
+        },
+        Some("button") => UciOptionKind::Button,
+        Some("string") => UciOptionKind::String { default: default() },
+        _ => {
+            return Err(invalid(
+                "expected check, spin, combo, button or string after type",
+            ))
+        }
+    };
+    Ok(UciOption::new(&name, kind))
+}
+
+/// A search score from an `info` message.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum UciScore {
+    /// The score in centipawns, from the engine's point of view.
+    Centipawns(i32),
+    /// Mate in this many moves (not plies).  Negative if the engine is getting mated.
+    Mate(i32),
+}
+
+/// Whether a score is only a bound on the real score.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum UciBound {
+    Lower,
+    Upper,
+}
+
+/// The search information in an `info` message.
+///
+/// This can be used directly, or in a builder pattern to write an `info` message.
+///
+/// ```
+/// use chess::{UciInfo, UciScore};
+/// use std::str::FromStr;
+/// use std::time::Duration;
+///
+/// let info = UciInfo::from_str(
+///     "info depth 20 seldepth 28 multipv 1 score mate -3 nodes 1540117 nps 1138298 \
+///      hashfull 512 tbhits 0 time 1353 pv e2e4 e7e5",
+/// )
+/// .expect("Valid info");
+///
+/// assert_eq!(info.get_depth(), Some(20));
+/// assert_eq!(info.get_score(), Some(UciScore::Mate(-3)));
+/// assert_eq!(info.get_time(), Some(Duration::from_millis(1353)));
+/// assert_eq!(info.get_pv().len(), 2);
+/// assert_eq!(info.get_string(), None);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct UciInfo {
+    depth: Option<u32>,
+    seldepth: Option<u32>,
+    multipv: Option<u32>,
+    score: Option<UciScore>,
+    bound: Option<UciBound>,
+    nodes: Option<u64>,
+    nps: Option<u64>,
+    hashfull: Option<u32>,
+    tbhits: Option<u64>,
+    time: Option<Duration>,
+    currmove: Option<ChessMove>,
+    currmovenumber: Option<u32>,
+    cpuload: Option<u32>,
+    pv: Vec<ChessMove>,
+    string: Option<String>,
+}
+
+impl UciInfo {
+    /// An `info` message without any information.
+    pub fn new() -> UciInfo {
+        UciInfo::default()
+    }
+
+    /// The search depth in plies.
+    pub fn get_depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    /// The selective search depth in plies.
+    pub fn get_seldepth(&self) -> Option<u32> {
+        self.seldepth
+    }
+
+    /// Which of the best lines this is, starting at 1.
+    pub fn get_multipv(&self) -> Option<u32> {
+        self.multipv
+    }
+
+    /// The score of the line.
+    pub fn get_score(&self) -> Option<UciScore> {
+        self.score
+    }
+
+    /// Is the score only a bound?
+    pub fn get_bound(&self) -> Option<UciBound> {
+        self.bound
+    }
+
+    /// The number of nodes searched.
+    pub fn get_nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    /// The number of nodes searched per second.
+    pub fn get_nps(&self) -> Option<u64> {
+        self.nps
+    }
+
+    /// How full the hash table is, in permill.
+    pub fn get_hashfull(&self) -> Option<u32> {
+        self.hashfull
+    }
+
+    /// The number of positions found in the endgame tablebases.
+    pub fn get_tbhits(&self) -> Option<u64> {
+        self.tbhits
+    }
+
+    /// The time searched.
+    pub fn get_time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    /// The move currently being searched.
+    pub fn get_currmove(&self) -> Option<ChessMove> {
+        self.currmove
+    }
+
+    /// The number of the move currently being searched, starting at 1.
+    pub fn get_currmovenumber(&self) -> Option<u32> {
+        self.currmovenumber
+    }
+
+    /// The CPU usage of the engine, in permill.
+    pub fn get_cpuload(&self) -> Option<u32> {
+        self.cpuload
+    }
+
+    /// The principal variation.
+    pub fn get_pv(&self) -> &[ChessMove] {
+        &self.pv
+    }
+
+    /// Free text for the GUI to show.
+    pub fn get_string(&self) -> Option<&str> {
+        self.string.as_deref()
+    }
+
+    /// Set the search depth in plies.
+    pub fn depth(&mut self, depth: u32) -> &mut Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Set the selective search depth in plies.
+    pub fn seldepth(&mut self, seldepth: u32) -> &mut Self {
+        self.seldepth = Some(seldepth);
+        self
+    }
+
+    /// Set which of the best lines this is, starting at 1.
+    pub fn multipv(&mut self, multipv: u32) -> &mut Self {
+        self.multipv = Some(multipv);
+        self
+    }
+
+    /// Set the score of the line.
+    pub fn score(&mut self, score: UciScore) -> &mut Self {
+        self.score = Some(score);
+        self
+    }
+
+    /// Mark the score as a bound.
+    pub fn bound(&mut self, bound: UciBound) -> &mut Self {
+        self.bound = Some(bound);
+        self
+    }
+
+    /// Set the number of nodes searched.
+    pub fn nodes(&mut self, nodes: u64) -> &mut Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Set the number of nodes searched per second.
+    pub fn nps(&mut self, nps: u64) -> &mut Self {
+        self.nps = Some(nps);
+        self
+    }
+
+    /// Set how full the hash table is, in permill.
+    pub fn hashfull(&mut self, hashfull: u32) -> &mut Self {
+        self.hashfull = Some(hashfull);
+        self
+    }
+
+    /// Set the number of positions found in the endgame tablebases.
+    pub fn tbhits(&mut self, tbhits: u64) -> &mut Self {
+        self.tbhits = Some(tbhits);
+        self
+    }
+
+    /// Set the time searched.
+    pub fn time(&mut self, time: Duration) -> &mut Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set the move currently being searched.
+    pub fn currmove(&mut self, currmove: ChessMove) -> &mut Self {
+        self.currmove = Some(currmove);
+        self
+    }
+
+    /// Set the number of the move currently being searched, starting at 1.
+    pub fn currmovenumber(&mut self, currmovenumber: u32) -> &mut Self {
+        self.currmovenumber = Some(currmovenumber);
+        self
+    }
+
+    /// Set the CPU usage of the engine, in permill.
+    pub fn cpuload(&mut self, cpuload: u32) -> &mut Self {
+        self.cpuload = Some(cpuload);
+        self
+    }
+
+    /// Set the principal variation.
+    pub fn pv(&mut self, pv: &[ChessMove]) -> &mut Self {
+        self.pv = pv.to_vec();
+        self
+    }
+
+    /// Set free text for the GUI to show.  This is always written last, as it runs to the end of
+    /// the line.
+    pub fn string(&mut self, string: &str) -> &mut Self {
+        self.string = Some(string.to_string());
+        self
+    }
+}
+
+impl FromStr for UciInfo {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        match UciMessage::from_str(line)? {
+            UciMessage::Info(info) => Ok(info),
+            _ => Err(invalid("expected an info message")),
+        }
+    }
+}
+
+impl fmt::Display for UciInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "info")?;
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(seldepth) = self.seldepth {
+            write!(f, " seldepth {}", seldepth)?;
+        }
+        if let Some(multipv) = self.multipv {
+            write!(f, " multipv {}", multipv)?;
+        }
+        match self.score {
+            Some(UciScore::Centipawns(cp)) => write!(f, " score cp {}", cp)?,
+            Some(UciScore::Mate(mate)) => write!(f, " score mate {}", mate)?,
+            None => {}
+        }
+        match self.bound {
+            Some(UciBound::Lower) => write!(f, " lowerbound")?,
+            Some(UciBound::Upper) => write!(f, " upperbound")?,
+            None => {}
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(nps) = self.nps {
+            write!(f, " nps {}", nps)?;
+        }
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {}", hashfull)?;
+        }
+        if let Some(tbhits) = self.tbhits {
+            write!(f, " tbhits {}", tbhits)?;
+        }
+        if let Some(time) = self.time {
+            write!(f, " time {}", time.as_millis())?;
+        }
+        if let Some(currmove) = self.currmove {
+            write!(f, " currmove {}", currmove)?;
+        }
+        if let Some(currmovenumber) = self.currmovenumber {
+            write!(f, " currmovenumber {}", currmovenumber)?;
+        }
+        if let Some(cpuload) = self.cpuload {
+            write!(f, " cpuload {}", cpuload)?;
+        }
+        if !self.pv.is_empty() {
+            write!(f, " pv")?;
+            for m in self.pv.iter() {
+                write!(f, " {}", m)?;
+            }
+        }
+        if let Some(ref string) = self.string {
+            write!(f, " string {}", string)?;
+        }
+        Ok(())
+    }
+}
+
+/// The type, and limits, of a `UciOption`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UciOptionKind {
+    Check { default: bool },
+    Spin { default: i64, min: i64, max: i64 },
+    Combo { default: String, vars: Vec<String> },
+    Button,
+    String { default: String },
+}
+
+/// An option the engine supports, from an `option` message.
+///
+/// ```
+/// use chess::{UciMessage, UciOption, UciOptionKind};
+/// use std::str::FromStr;
+///
+/// let hash = UciOption::new("Hash", UciOptionKind::Spin { default: 16, min: 1, max: 33554432 });
+/// assert_eq!(
+///     UciMessage::Option(hash.clone()).to_string(),
+///     "option name Hash type spin default 16 min 1 max 33554432"
+/// );
+/// assert_eq!(
+///     UciMessage::from_str("option name Hash type spin default 16 min 1 max 33554432").unwrap(),
+///     UciMessage::Option(hash)
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UciOption {
+    name: String,
+    kind: UciOptionKind,
+}
+
+impl UciOption {
+    /// Create an option.
+    pub fn new(name: &str, kind: UciOptionKind) -> UciOption {
+        UciOption {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// The name of the option, as used by `setoption`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type, and limits, of the option.
+    pub fn kind(&self) -> &UciOptionKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match self.kind {
+            UciOptionKind::Check { default } => write!(f, "check default {}", default),
+            UciOptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            UciOptionKind::Combo {
+                ref default,
+                ref vars,
+            } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars.iter() {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            UciOptionKind::Button => write!(f, "button"),
+            UciOptionKind::String { ref default } => write!(f, "string default {}", default),
+        }
+    }
+}
+
+#[test]
+fn test_uci_message_transcript() {
+    // the engine side of a session, with each kind of message an engine can send
+    let transcript = "id name Example 1.0\n\
+                      id author the Example developers\n\
+                      option name Debug Log File type string default <empty>\n\
+                      option name Threads type spin default 1 min 1 max 1024\n\
+                      option name Clear Hash type button\n\
+                      option name Ponder type check default false\n\
+                      option name Analysis Contempt type combo default Both var Off var White var Black var Both\n\
+                      uciok\n\
+                      readyok\n\
+                      info string NNUE evaluation using nn-5af11540bbfe.nnue enabled\n\
+                      info depth 1 seldepth 1 multipv 1 score cp 18 nodes 20 nps 10000 hashfull 0 tbhits 0 time 2 pv e2e4\n\
+                      info depth 14 seldepth 18 multipv 1 score cp 35 lowerbound nodes 91827 nps 1530450 hashfull 41 tbhits 0 time 60 pv e2e4\n\
+                      info depth 12 currmove d2d4 currmovenumber 3\n\
+                      info depth 24 seldepth 30 multipv 2 score mate -4 upperbound nodes 3 time 1000 pv e7e8q a8a1 cpuload 900\n\
+                      bestmove e2e4 ponder e7e5\n\
+                      bestmove (none)\n\
+                      copyprotection ok\n\
+                      registration checking";
+
+    for line in transcript.lines() {
+        let message = UciMessage::from_str(line).expect("Valid message");
+        if line.contains("cpuload") {
+            // the canonical order puts cpuload before the pv
+            assert_eq!(
+                message.to_string(),
+                "info depth 24 seldepth 30 multipv 2 score mate -4 upperbound nodes 3 time 1000 \
+                 cpuload 900 pv e7e8q a8a1"
+            );
+        } else {
+            assert_eq!(message.to_string(), line);
+        }
+    }
+
+    match UciMessage::from_str(
+        "option name Analysis Contempt type combo default Both var Off var White var Black var Both",
+    ) {
+        Ok(UciMessage::Option(option)) => {
+            assert_eq!(option.name(), "Analysis Contempt");
+            match option.kind() {
+                UciOptionKind::Combo { default, vars } => {
+                    assert_eq!(default, "Both");
+                    assert_eq!(vars.len(), 4);
+                }
+                _ => panic!("Expected a combo option"),
+            }
+        }
+        _ => panic!("Expected an option"),
+    }
+}
+
+#[test]
+fn test_uci_info_fields() {
+    let info = UciInfo::from_str(
+        "info depth 9 unknown 7 score cp -20 upperbound currmove g1f3 nodes 70 pv b1c3 c7c5 string a b",
+    )
+    .expect("Valid info");
+    assert_eq!(info.get_depth(), Some(9));
+    assert_eq!(info.get_score(), Some(UciScore::Centipawns(-20)));
+    assert_eq!(info.get_bound(), Some(UciBound::Upper));
+    assert_eq!(info.get_nodes(), Some(70));
+    assert_eq!(info.get_pv().len(), 2);
+    assert_eq!(info.get_string(), Some("a b"));
+    assert_eq!(
+        info.get_currmove().map(|m| m.to_string()),
+        Some("g1f3".to_string())
+    );
+
+    assert!(UciMessage::from_str("info depth").is_err());
+    assert!(UciMessage::from_str("info score wdl 1 2 3").is_err());
+    assert!(UciMessage::from_str("option name Hash type spin default 16").is_err());
+    assert!(UciMessage::from_str("option type check").is_err());
+    assert!(UciMessage::from_str("bestmove").is_err());
+    assert!(UciMessage::from_str("Stockfish 16 by the Stockfish developers").is_err());
+}
@@ -0,0 +1,40 @@
+mod command;
+pub use self::command::*;
+
+mod message;
+pub use self::message::*;
+
+mod engine;
+pub use self::engine::*;
+
+use crate::error::Error;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidUci {
+        message: message.to_string(),
+    }
+}
+
+/// Parse the token following the keyword `name`.
+fn parse_value<'a, T: FromStr>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<T, Error> {
+    tokens
+        .next()
+        .and_then(|t| t.parse::<T>().ok())
+        .ok_or_else(|| invalid(&format!("expected a value after {}", name)))
+}
+
+/// Parse a number of milliseconds.  Some GUIs send negative times when a clock has run out, so
+/// those become zero.
+fn parse_millis<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<Duration, Error> {
+    let millis: i64 = parse_value(tokens, name)?;
+    Ok(Duration::from_millis(millis.max(0) as u64))
+}
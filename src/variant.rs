@@ -0,0 +1,161 @@
+use crate::color::Color;
+use crate::error::Error;
+use crate::piece::Piece;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The rules a `VariantBoard` is played by.
+///
+/// Each variant has its own starting position, legal moves, and ways to win or draw:
+///
+/// * `Standard` is orthodox chess.
+/// * In `Atomic`, a capture explodes the capturing piece, and every piece other than a pawn next
+///   to the capture square.  Exploding the enemy king wins.  Kings cannot capture, and a king
+///   next to the enemy king cannot be checked.
+/// * In `KingOfTheHill`, moving a king to d4, e4, d5 or e5 also wins.
+/// * In `ThreeCheck`, giving check for the third time also wins.
+/// * In `Antichess`, captures are compulsory, the king is an ordinary piece, there is no
+///   castling, and a player wins by losing all their pieces, or by having no legal moves.
+/// * In `Horde`, white has 36 pawns and no king, and wins by checkmating black.  Black wins by
+///   capturing all of white's pieces.  White's pawns on the first rank may move two squares.
+///
+/// Variants are named as in the PGN `Variant` tag.
+///
+/// ```
+/// use chess::Variant;
+/// use std::str::FromStr;
+///
+/// assert_eq!(Variant::from_str("King of the Hill").expect("Valid variant"), Variant::KingOfTheHill);
+/// assert_eq!(Variant::ThreeCheck.to_string(), "Three-check");
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    #[default]
+    Standard,
+    Atomic,
+    KingOfTheHill,
+    ThreeCheck,
+    Antichess,
+    Horde,
+}
+
+/// The number of variants.
+pub const NUM_VARIANTS: usize = 6;
+
+/// All the variants.
+pub const ALL_VARIANTS: [Variant; NUM_VARIANTS] = [
+    Variant::Standard,
+    Variant::Atomic,
+    Variant::KingOfTheHill,
+    Variant::ThreeCheck,
+    Variant::Antichess,
+    Variant::Horde,
+];
+
+impl Variant {
+    /// The FEN of the starting position.
+    ///
+    /// ```
+    /// use chess::Variant;
+    ///
+    /// assert_eq!(
+    ///     Variant::Antichess.start_fen(),
+    ///     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    /// );
+    /// ```
+    pub fn start_fen(&self) -> &'static str {
+        match *self {
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            Variant::Horde => {
+                "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+            }
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            _ => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        }
+    }
+
+    /// Can a king be put in check, and must it be kept out of check?
+    pub fn has_check(&self) -> bool {
+        *self != Variant::Antichess
+    }
+
+    /// Is castling allowed?
+    pub fn has_castling(&self) -> bool {
+        *self != Variant::Antichess
+    }
+
+    /// How many kings must `color` have?  `None` means any number.
+    pub fn kings(&self, color: Color) -> Option<u32> {
+        match (*self, color) {
+            (Variant::Antichess, _) => None,
+            (Variant::Horde, Color::White) => Some(0),
+            _ => Some(1),
+        }
+    }
+
+    /// The pieces a pawn can promote to.
+    pub fn promotion_pieces(&self) -> &'static [Piece] {
+        match *self {
+            Variant::Antichess => &[
+                Piece::Queen,
+                Piece::Knight,
+                Piece::Rook,
+                Piece::Bishop,
+                Piece::King,
+            ],
+            _ => &[Piece::Queen, Piece::Knight, Piece::Rook, Piece::Bishop],
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Variant::Standard => "Standard",
+                Variant::Atomic => "Atomic",
+                Variant::KingOfTheHill => "King of the Hill",
+                Variant::ThreeCheck => "Three-check",
+                Variant::Antichess => "Antichess",
+                Variant::Horde => "Horde",
+            }
+        )
+    }
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+
+    /// Parse the name of a variant.  Case, spaces and dashes are ignored, and a few other common
+    /// names are accepted, such as "3check", "koth" and "giveaway".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "standard" | "chess" => Ok(Variant::Standard),
+            "atomic" => Ok(Variant::Atomic),
+            "kingofthehill" | "koth" => Ok(Variant::KingOfTheHill),
+            "threecheck" | "3check" => Ok(Variant::ThreeCheck),
+            "antichess" | "giveaway" => Ok(Variant::Antichess),
+            "horde" => Ok(Variant::Horde),
+            _ => Err(Error::InvalidVariant {
+                name: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// How a game on a `VariantBoard` ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    Winner(Color),
+    Draw,
+}
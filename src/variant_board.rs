@@ -0,0 +1,926 @@
+use crate::bitboard::{BitBoard, EMPTY};
+use crate::board_builder::BoardBuilder;
+use crate::castle_rights::CastleRights;
+use crate::chess_move::ChessMove;
+use crate::color::{Color, ALL_COLORS, NUM_COLORS};
+use crate::error::Error;
+use crate::file::File;
+use crate::magic::{
+    between, get_adjacent_files, get_bishop_moves, get_king_moves, get_knight_moves,
+    get_pawn_attacks, get_pawn_quiets, get_rank, get_rook_moves,
+};
+use crate::piece::{Piece, ALL_PIECES, NUM_PIECES};
+use crate::square::{Square, ALL_SQUARES};
+#[cfg(test)]
+use crate::variant::ALL_VARIANTS;
+use crate::variant::{Outcome, Variant};
+use crate::zobrist::Zobrist;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The squares a king has to reach in King of the Hill: d4, e4, d5 and e5.
+const HILL: BitBoard = BitBoard(0x0000_0018_1800_0000);
+
+/// A position in one of the chess variants.
+///
+/// `Board` only knows the orthodox rules, and relies on each side having exactly one king.  A
+/// `VariantBoard` keeps the pieces in the same bitboards, and uses the same attack tables, but
+/// asks its `Variant` which moves are legal and how the game ends.
+///
+/// It is simpler and slower than `Board`: moves are checked by playing them.  Variants are played
+/// with standard castling only, not Chess960.  A game can be played on a `VariantBoard` with a
+/// `VariantGame`.
+///
+/// ```
+/// use chess::{ChessMove, Color, Outcome, Square, Variant, VariantBoard};
+///
+/// let board = VariantBoard::from_fen(Variant::Atomic, "4k3/8/8/8/8/1rq5/3N4/4K3 w - - 0 1")
+///     .expect("Valid position");
+///
+/// // taking the rook blows up the knight that took it, and the queen next to it
+/// let m = ChessMove::new(Square::D2, Square::B3, None);
+/// assert!(board.legal(m));
+/// let board = board.make_move_new(m);
+/// assert_eq!(board.piece_on(Square::B3), None);
+/// assert_eq!(board.piece_on(Square::C3), None);
+/// assert_eq!(board.combined().popcnt(), 2);
+///
+/// let board = VariantBoard::from_fen(Variant::KingOfTheHill, "4k3/8/8/8/8/4K3/8/8 w - - 0 1")
+///     .expect("Valid position");
+/// let board = board.make_move_new(ChessMove::new(Square::E3, Square::E4, None));
+/// assert_eq!(board.outcome(), Some(Outcome::Winner(Color::White)));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct VariantBoard {
+    variant: Variant,
+    pieces: [BitBoard; NUM_PIECES],
+    color_combined: [BitBoard; NUM_COLORS],
+    combined: BitBoard,
+    side_to_move: Color,
+    castle_rights: [CastleRights; NUM_COLORS],
+    en_passant: Option<Square>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    remaining_checks: [u8; NUM_COLORS],
+    hash: u64,
+}
+
+impl VariantBoard {
+    /// The starting position of `variant`.
+    ///
+    /// ```
+    /// use chess::{Color, Variant, VariantBoard};
+    ///
+    /// let board = VariantBoard::new(Variant::Horde);
+    /// assert_eq!(board.color_combined(Color::White).popcnt(), 36);
+    /// assert_eq!(board.legal_moves().len(), 8);
+    /// ```
+    pub fn new(variant: Variant) -> VariantBoard {
+        VariantBoard::from_fen(variant, variant.start_fen()).expect("Valid starting position")
+    }
+
+    /// Set up a position of `variant` from a FEN string.
+    ///
+    /// For Three-check, the number of checks each side still has to give can follow the en
+    /// passant square, as in `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1`.
+    /// Without it, both sides need three checks.
+    ///
+    /// ```
+    /// use chess::{Color, Variant, VariantBoard};
+    ///
+    /// let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 1+2 0 1";
+    /// let board = VariantBoard::from_fen(Variant::ThreeCheck, fen).expect("Valid position");
+    /// assert_eq!(board.remaining_checks(Color::White), 1);
+    /// assert_eq!(board.to_string(), fen);
+    ///
+    /// // Horde needs white to have no king
+    /// assert!(VariantBoard::from_fen(Variant::Horde, "4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+    /// ```
+    pub fn from_fen(variant: Variant, fen: &str) -> Result<VariantBoard, Error> {
+        let invalid = || Error::InvalidFen {
+            fen: fen.to_string(),
+        };
+
+        let mut tokens: Vec<&str> = fen.split_whitespace().collect();
+        let mut remaining_checks = [3, 3];
+        if variant == Variant::ThreeCheck && matches!(tokens.get(4), Some(t) if t.contains('+')) {
+            let checks = tokens.remove(4);
+            let mut parts = checks.split('+');
+            for color in ALL_COLORS.iter() {
+                remaining_checks[color.to_index()] = parts
+                    .next()
+                    .and_then(|n| u8::from_str(n).ok())
+                    .filter(|n| *n <= 3)
+                    .ok_or_else(invalid)?;
+            }
+            if parts.next().is_some() {
+                return Err(invalid());
+            }
+        }
+
+        let builder = BoardBuilder::from_str(&tokens.join(" "))?;
+        if builder.is_chess960() {
+            return Err(Error::InvalidBoard);
+        }
+
+        let mut board = VariantBoard {
+            variant,
+            pieces: [EMPTY; NUM_PIECES],
+            color_combined: [EMPTY; NUM_COLORS],
+            combined: EMPTY,
+            side_to_move: builder.get_side_to_move(),
+            castle_rights: [
+                builder.get_castle_rights(Color::White),
+                builder.get_castle_rights(Color::Black),
+            ],
+            en_passant: builder.get_en_passant(),
+            halfmove_clock: builder.get_halfmove_clock(),
+            fullmove_number: builder.get_fullmove_number(),
+            remaining_checks,
+            hash: 0,
+        };
+        for square in ALL_SQUARES.iter() {
+            if let Some((piece, color)) = builder[*square] {
+                board.xor(piece, color, *square);
+            }
+        }
+
+        if board.is_sane() {
+            Ok(board)
+        } else {
+            Err(Error::InvalidBoard)
+        }
+    }
+
+    /// Does this position follow the rules of its variant?
+    fn is_sane(&self) -> bool {
+        for color in ALL_COLORS.iter() {
+            let ours = self.color_combined(*color);
+            if let Some(kings) = self.variant.kings(*color) {
+                if (self.pieces(Piece::King) & ours).popcnt() != kings {
+                    return false;
+                }
+            }
+
+            // pawns may only be on their own first rank in Horde
+            let mut backranks = get_rank(color.to_their_backrank());
+            if self.variant != Variant::Horde {
+                backranks |= get_rank(color.to_my_backrank());
+            }
+            if self.pieces(Piece::Pawn) & ours & backranks != EMPTY {
+                return false;
+            }
+
+            // castle rights need the king and rook on their starting squares
+            let rights = self.castle_rights(*color);
+            if rights != CastleRights::NoRights && !self.variant.has_castling() {
+                return false;
+            }
+            if rights.remove(self.lost_castle_rights(*color)) != rights {
+                return false;
+            }
+        }
+
+        if let Some(ep) = self.en_passant {
+            if self.piece_on(ep) != Some(Piece::Pawn)
+                || self.color_on(ep) != Some(!self.side_to_move)
+            {
+                return false;
+            }
+        }
+
+        !self.in_check(!self.side_to_move)
+    }
+
+    /// The variant this position is played by.
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Who's turn is it?
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// All of the pieces on the board.
+    pub fn combined(&self) -> &BitBoard {
+        &self.combined
+    }
+
+    /// The pieces of `color`.
+    pub fn color_combined(&self, color: Color) -> &BitBoard {
+        &self.color_combined[color.to_index()]
+    }
+
+    /// The pieces of type `piece`, of both colors.
+    pub fn pieces(&self, piece: Piece) -> &BitBoard {
+        &self.pieces[piece.to_index()]
+    }
+
+    /// What piece is on `square`?
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        let bb = BitBoard::from_square(square);
+        if self.combined & bb == EMPTY {
+            return None;
+        }
+        ALL_PIECES
+            .iter()
+            .cloned()
+            .find(|piece| self.pieces(*piece) & bb != EMPTY)
+    }
+
+    /// What color is the piece on `square`?
+    pub fn color_on(&self, square: Square) -> Option<Color> {
+        let bb = BitBoard::from_square(square);
+        ALL_COLORS
+            .iter()
+            .cloned()
+            .find(|color| self.color_combined(*color) & bb != EMPTY)
+    }
+
+    /// The castle rights of `color`.
+    pub fn castle_rights(&self, color: Color) -> CastleRights {
+        self.castle_rights[color.to_index()]
+    }
+
+    /// The square of the pawn that just moved two squares, if an enemy pawn is next to it.
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    /// How many halfmoves have been made since the last capture or pawn move?
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    /// The number of the current move, starting at 1 and going up after every move by black.
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    /// How many more checks does `color` have to give to win at Three-check?
+    pub fn remaining_checks(&self, color: Color) -> u8 {
+        self.remaining_checks[color.to_index()]
+    }
+
+    /// Get a Zobrist hash of the position, made the same way as `Board::get_hash()`.  In
+    /// Three-check, the checks each side has left are part of the hash.
+    ///
+    /// ```
+    /// use chess::{Board, ChessMove, Square, Variant, VariantBoard};
+    ///
+    /// let board = VariantBoard::new(Variant::Standard);
+    /// assert_eq!(board.get_hash(), Board::default().get_hash());
+    ///
+    /// // Qh5+ gives a check in Three-check, so the knight moves do not repeat the position
+    /// let board = VariantBoard::from_fen(
+    ///     Variant::ThreeCheck,
+    ///     "rnbqkbnr/ppppp1pp/8/5p2/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+    /// )
+    /// .expect("Valid position");
+    /// let checked = [(Square::D1, Square::H5), (Square::G7, Square::G6), (Square::H5, Square::D1)]
+    ///     .iter()
+    ///     .fold(board, |b, (from, to)| b.make_move_new(ChessMove::new(*from, *to, None)));
+    /// assert_ne!(checked.get_hash(), board.make_move_new(ChessMove::new(Square::G8, Square::F6, None)).get_hash());
+    /// ```
+    pub fn get_hash(&self) -> u64 {
+        let mut hash = self.hash;
+        if let Some(ep) = self.en_passant {
+            hash ^= Zobrist::en_passant(ep.get_file(), !self.side_to_move);
+        }
+        for color in ALL_COLORS.iter() {
+            hash ^= Zobrist::castles(self.castle_rights(*color), *color)
+                ^ Zobrist::remaining_checks(self.remaining_checks(*color), *color);
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= Zobrist::color();
+        }
+        hash
+    }
+
+    /// Is the side to move in check?  This is never the case in Antichess, or in Atomic when
+    /// the kings are next to each other.
+    ///
+    /// ```
+    /// use chess::{Variant, VariantBoard};
+    ///
+    /// let fen = "8/8/8/8/8/3k4/4K3/4R3 b - - 0 1";
+    /// assert!(VariantBoard::from_fen(Variant::Standard, fen).is_err());
+    /// assert!(!VariantBoard::from_fen(Variant::Atomic, fen).expect("Valid").is_check());
+    /// ```
+    pub fn is_check(&self) -> bool {
+        self.in_check(self.side_to_move)
+    }
+
+    /// The legal moves in this position.  There are none once the game is over.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Square, Variant, VariantBoard};
+    ///
+    /// // captures are compulsory in Antichess
+    /// let board = VariantBoard::new(Variant::Antichess)
+    ///     .make_move_new(ChessMove::new(Square::E2, Square::E4, None))
+    ///     .make_move_new(ChessMove::new(Square::D7, Square::D5, None));
+    /// assert_eq!(
+    ///     board.legal_moves(),
+    ///     vec![ChessMove::new(Square::E4, Square::D5, None)]
+    /// );
+    /// ```
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        if self.variant_end().is_some() {
+            return vec![];
+        }
+
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|m| self.is_legal_after_pseudo(*m));
+        if self.variant == Variant::Antichess && moves.iter().any(|m| self.is_capture(*m)) {
+            moves.retain(|m| self.is_capture(*m));
+        }
+        moves
+    }
+
+    /// Is `m` legal in this position?
+    pub fn legal(&self, m: ChessMove) -> bool {
+        self.legal_moves().contains(&m)
+    }
+
+    /// Does `m` capture a piece?
+    pub fn is_capture(&self, m: ChessMove) -> bool {
+        self.color_on(m.get_dest()) == Some(!self.side_to_move) || self.is_en_passant(m)
+    }
+
+    /// Make a move, which must be legal, and return the new position.
+    pub fn make_move_new(&self, m: ChessMove) -> VariantBoard {
+        let us = self.side_to_move;
+        let source = m.get_source();
+        let dest = m.get_dest();
+        let piece = self.piece_on(source).expect("A piece to move");
+        let captured = self.piece_on(dest);
+        let en_passant = self.is_en_passant(m);
+
+        let mut result = *self;
+        result.en_passant = None;
+        result.halfmove_clock += 1;
+        if us == Color::Black {
+            result.fullmove_number += 1;
+        }
+
+        result.xor(piece, us, source);
+        if let Some(captured) = captured {
+            result.xor(captured, !us, dest);
+        }
+        if en_passant {
+            result.xor(Piece::Pawn, !us, dest.ubackward(us));
+        }
+        let placed = m.get_promotion().unwrap_or(piece);
+        result.xor(placed, us, dest);
+
+        if piece == Piece::Pawn {
+            result.halfmove_clock = 0;
+            if source.get_rank() == us.to_second_rank() && dest.get_rank() == us.to_fourth_rank() {
+                let neighbours = get_adjacent_files(dest.get_file())
+                    & get_rank(dest.get_rank())
+                    & result.pieces(Piece::Pawn)
+                    & result.color_combined(!us);
+                if neighbours != EMPTY {
+                    result.en_passant = Some(dest);
+                }
+            }
+        }
+
+        if self.is_castle(m) {
+            let rank = us.to_my_backrank();
+            let (from, to) = if dest.get_file() == File::G {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            result.xor(Piece::Rook, us, Square::make_square(rank, from));
+            result.xor(Piece::Rook, us, Square::make_square(rank, to));
+        }
+
+        if captured.is_some() || en_passant {
+            result.halfmove_clock = 0;
+            if self.variant == Variant::Atomic {
+                result.xor(placed, us, dest);
+                for square in get_king_moves(dest) & result.combined & !result.pieces(Piece::Pawn) {
+                    let piece = result.piece_on(square).expect("A piece to explode");
+                    let color = result.color_on(square).expect("A piece to explode");
+                    result.xor(piece, color, square);
+                }
+            }
+        }
+
+        for color in ALL_COLORS.iter() {
+            let lost = result.lost_castle_rights(*color);
+            result.castle_rights[color.to_index()] = result.castle_rights(*color).remove(lost);
+        }
+
+        result.side_to_move = !us;
+        if self.variant == Variant::ThreeCheck && result.is_check() {
+            result.remaining_checks[us.to_index()] -= 1;
+        }
+        result
+    }
+
+    /// Is the game over, and how did it end?
+    ///
+    /// ```
+    /// use chess::{Color, Outcome, Variant, VariantBoard};
+    ///
+    /// // in Antichess, the player with no pieces left wins
+    /// let board = VariantBoard::from_fen(Variant::Antichess, "8/8/8/8/8/8/8/4K3 b - - 0 1")
+    ///     .expect("Valid position");
+    /// assert_eq!(board.outcome(), Some(Outcome::Winner(Color::Black)));
+    ///
+    /// assert_eq!(VariantBoard::new(Variant::Horde).outcome(), None);
+    /// ```
+    pub fn outcome(&self) -> Option<Outcome> {
+        if let Some(outcome) = self.variant_end() {
+            return Some(outcome);
+        }
+        if !self.legal_moves().is_empty() {
+            None
+        } else if self.variant == Variant::Antichess {
+            Some(Outcome::Winner(self.side_to_move))
+        } else if self.is_check() {
+            Some(Outcome::Winner(!self.side_to_move))
+        } else {
+            Some(Outcome::Draw)
+        }
+    }
+
+    /// The ways a game can end that are particular to the variant, and do not depend on the
+    /// legal moves.
+    pub(crate) fn variant_end(&self) -> Option<Outcome> {
+        let kings = |color: Color| self.pieces(Piece::King) & self.color_combined(color);
+        match self.variant {
+            Variant::Atomic => ALL_COLORS
+                .iter()
+                .find(|color| kings(**color) == EMPTY)
+                .map(|color| Outcome::Winner(!*color)),
+            Variant::KingOfTheHill => ALL_COLORS
+                .iter()
+                .find(|color| kings(**color) & HILL != EMPTY)
+                .map(|color| Outcome::Winner(*color)),
+            Variant::ThreeCheck => ALL_COLORS
+                .iter()
+                .find(|color| self.remaining_checks(**color) == 0)
+                .map(|color| Outcome::Winner(*color)),
+            Variant::Antichess if *self.color_combined(self.side_to_move) == EMPTY => {
+                Some(Outcome::Winner(self.side_to_move))
+            }
+            Variant::Horde if *self.color_combined(Color::White) == EMPTY => {
+                Some(Outcome::Winner(Color::Black))
+            }
+            _ => None,
+        }
+    }
+
+    /// Every move by the side to move, including moves that leave a king in check, and
+    /// non-captures when a capture is compulsory.
+    fn pseudo_legal_moves(&self) -> Vec<ChessMove> {
+        let us = self.side_to_move;
+        let ours = *self.color_combined(us);
+        let theirs = *self.color_combined(!us);
+        let occupied = self.combined;
+        let mut moves = Vec::with_capacity(64);
+
+        for piece in [
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+            Piece::King,
+        ]
+        .iter()
+        {
+            for source in self.pieces(*piece) & ours {
+                let mut targets = match *piece {
+                    Piece::Knight => get_knight_moves(source),
+                    Piece::Bishop => get_bishop_moves(source, occupied),
+                    Piece::Rook => get_rook_moves(source, occupied),
+                    Piece::Queen => {
+                        get_bishop_moves(source, occupied) | get_rook_moves(source, occupied)
+                    }
+                    _ => get_king_moves(source),
+                } & !ours;
+                // a king would blow itself up
+                if *piece == Piece::King && self.variant == Variant::Atomic {
+                    targets &= !theirs;
+                }
+                for dest in targets {
+                    moves.push(ChessMove::new(source, dest, None));
+                }
+            }
+        }
+
+        let promotion_rank = get_rank(us.to_their_backrank());
+        let en_passant = self
+            .en_passant
+            .map(|ep| BitBoard::from_square(ep.uforward(us)));
+        for source in self.pieces(Piece::Pawn) & ours {
+            let mut targets = get_pawn_attacks(source, us, theirs | en_passant.unwrap_or(EMPTY));
+            if source.get_rank() == us.to_my_backrank() {
+                // Horde pawns on the first rank can move one or two squares
+                let one = source.uforward(us);
+                if occupied & BitBoard::from_square(one) == EMPTY {
+                    targets |= BitBoard::from_square(one);
+                    let two = one.uforward(us);
+                    if occupied & BitBoard::from_square(two) == EMPTY {
+                        targets |= BitBoard::from_square(two);
+                    }
+                }
+            } else {
+                targets |= get_pawn_quiets(source, us, occupied);
+            }
+
+            for dest in targets {
+                if BitBoard::from_square(dest) & promotion_rank != EMPTY {
+                    for promotion in self.variant.promotion_pieces().iter() {
+                        moves.push(ChessMove::new(source, dest, Some(*promotion)));
+                    }
+                } else {
+                    moves.push(ChessMove::new(source, dest, None));
+                }
+            }
+        }
+
+        if self.variant.has_castling() && !self.is_check() {
+            let rank = us.to_my_backrank();
+            let king = Square::make_square(rank, File::E);
+            let rights = self.castle_rights(us);
+            let sides = [
+                (rights.has_kingside(), File::H, File::F, File::G),
+                (rights.has_queenside(), File::A, File::D, File::C),
+            ];
+            for (allowed, rook, through, dest) in sides.iter() {
+                let rook = Square::make_square(rank, *rook);
+                let through = Square::make_square(rank, *through);
+                if *allowed
+                    && between(king, rook) & occupied == EMPTY
+                    && !self.king_attacked(through, us, occupied)
+                {
+                    moves.push(ChessMove::new(king, Square::make_square(rank, *dest), None));
+                }
+            }
+        }
+
+        moves
+    }
+
+    /// Would `m`, which is pseudo-legal, keep the king of the side to move out of check?
+    fn is_legal_after_pseudo(&self, m: ChessMove) -> bool {
+        if !self.variant.has_check() {
+            return true;
+        }
+
+        let us = self.side_to_move;
+        let after = self.make_move_new(m);
+        if self.variant == Variant::Atomic {
+            let kings = |color: Color| after.pieces(Piece::King) & after.color_combined(color);
+            if kings(us) == EMPTY {
+                return false;
+            }
+            if kings(!us) == EMPTY {
+                return true;
+            }
+        }
+        !after.in_check(us)
+    }
+
+    /// Is a king of `color` in check?
+    fn in_check(&self, color: Color) -> bool {
+        self.variant.has_check()
+            && (self.pieces(Piece::King) & self.color_combined(color))
+                .any(|king| self.king_attacked(king, color, self.combined))
+    }
+
+    /// Would a king of `color` on `square` be attacked?  In Atomic, a king next to the enemy
+    /// king cannot be, as capturing it would blow up both kings.
+    fn king_attacked(&self, square: Square, color: Color, occupied: BitBoard) -> bool {
+        if self.variant == Variant::Atomic
+            && get_king_moves(square) & self.pieces(Piece::King) & self.color_combined(!color)
+                != EMPTY
+        {
+            return false;
+        }
+        self.attackers(square, !color, occupied) != EMPTY
+    }
+
+    /// The pieces of `color` attacking `square`.
+    fn attackers(&self, square: Square, color: Color, occupied: BitBoard) -> BitBoard {
+        let diagonal = self.pieces(Piece::Bishop) | self.pieces(Piece::Queen);
+        let straight = self.pieces(Piece::Rook) | self.pieces(Piece::Queen);
+        (get_pawn_attacks(square, !color, *self.pieces(Piece::Pawn))
+            | (get_knight_moves(square) & self.pieces(Piece::Knight))
+            | (get_king_moves(square) & self.pieces(Piece::King))
+            | (get_bishop_moves(square, occupied) & diagonal)
+            | (get_rook_moves(square, occupied) & straight))
+            & self.color_combined(color)
+            & occupied
+    }
+
+    /// The castle rights of `color` that its king and rooks are no longer in place for.
+    fn lost_castle_rights(&self, color: Color) -> CastleRights {
+        let rank = color.to_my_backrank();
+        let on = |piece: Piece, file: File| {
+            let square = Square::make_square(rank, file);
+            self.piece_on(square) == Some(piece) && self.color_on(square) == Some(color)
+        };
+        if !on(Piece::King, File::E) {
+            return CastleRights::Both;
+        }
+        let mut lost = CastleRights::NoRights;
+        if !on(Piece::Rook, File::H) {
+            lost = lost.add(CastleRights::KingSide);
+        }
+        if !on(Piece::Rook, File::A) {
+            lost = lost.add(CastleRights::QueenSide);
+        }
+        lost
+    }
+
+    fn is_en_passant(&self, m: ChessMove) -> bool {
+        self.en_passant.map(|ep| ep.uforward(self.side_to_move)) == Some(m.get_dest())
+            && self.piece_on(m.get_source()) == Some(Piece::Pawn)
+            && m.get_source().get_file() != m.get_dest().get_file()
+    }
+
+    fn is_castle(&self, m: ChessMove) -> bool {
+        self.variant.has_castling()
+            && self.piece_on(m.get_source()) == Some(Piece::King)
+            && m.get_source().get_file() == File::E
+            && (m.get_dest().get_file() == File::G || m.get_dest().get_file() == File::C)
+            && m.get_source().get_rank() == m.get_dest().get_rank()
+    }
+
+    fn xor(&mut self, piece: Piece, color: Color, square: Square) {
+        let bb = BitBoard::from_square(square);
+        self.pieces[piece.to_index()] ^= bb;
+        self.color_combined[color.to_index()] ^= bb;
+        self.combined ^= bb;
+        self.hash ^= Zobrist::piece(piece, square, color);
+    }
+}
+
+impl fmt::Display for VariantBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fen = BoardBuilder::from(self).to_string();
+        if self.variant == Variant::ThreeCheck {
+            let tokens: Vec<&str> = fen.split(' ').collect();
+            write!(
+                f,
+                "{} {}+{} {}",
+                tokens[..4].join(" "),
+                self.remaining_checks(Color::White),
+                self.remaining_checks(Color::Black),
+                tokens[4..].join(" ")
+            )
+        } else {
+            write!(f, "{}", fen)
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::board::Board;
+#[cfg(test)]
+use crate::movegen::MoveGen;
+#[cfg(test)]
+use crate::perft::{perft, perft_variant};
+
+/// Count the leaves of the move tree with the standard `Board`, stopping at any position where
+/// `ended` says the game is over.
+#[cfg(test)]
+fn perft_until(
+    board: &Board,
+    depth: usize,
+    checks: [u8; 2],
+    ended: &dyn Fn(&Board, [u8; 2]) -> bool,
+) -> usize {
+    if depth == 0 {
+        return 1;
+    }
+    if ended(board, checks) {
+        return 0;
+    }
+    MoveGen::new_legal(board)
+        .map(|m| {
+            let next = board.make_move_new(m);
+            let mut checks = checks;
+            if *next.checkers() != EMPTY {
+                checks[board.side_to_move().to_index()] -= 1;
+            }
+            perft_until(&next, depth - 1, checks, ended)
+        })
+        .sum()
+}
+
+#[test]
+fn test_variant_perft_matches_board() {
+    let positions = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "4k3/8/8/3p4/8/4K3/8/8 w - - 0 1",
+        "8/8/3k4/8/2P5/4K3/8/8 b - - 0 1",
+    ];
+    let on_hill = |board: &Board, _| {
+        ALL_COLORS
+            .iter()
+            .any(|color| BitBoard::from_square(board.king_square(*color)) & HILL != EMPTY)
+    };
+    let no_checks = |_: &Board, checks: [u8; 2]| checks.contains(&0);
+
+    for fen in positions.iter() {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        let standard = VariantBoard::from_fen(Variant::Standard, fen).expect("Valid FEN");
+        let hill = VariantBoard::from_fen(Variant::KingOfTheHill, fen).expect("Valid FEN");
+        let three_check = VariantBoard::from_fen(Variant::ThreeCheck, fen).expect("Valid FEN");
+        let mut tokens: Vec<&str> = fen.split(' ').collect();
+        tokens.insert(4, "1+2");
+        let one_check =
+            VariantBoard::from_fen(Variant::ThreeCheck, &tokens.join(" ")).expect("Valid FEN");
+
+        for depth in 1..4 {
+            assert_eq!(
+                perft_variant(&standard, depth),
+                perft(&board, depth),
+                "{}",
+                fen
+            );
+            assert_eq!(
+                perft_variant(&three_check, depth),
+                perft(&board, depth),
+                "{}",
+                fen
+            );
+            assert_eq!(
+                perft_variant(&hill, depth),
+                perft_until(&board, depth, [3, 3], &on_hill),
+                "{}",
+                fen
+            );
+            assert_eq!(
+                perft_variant(&one_check, depth),
+                perft_until(&board, depth, [1, 2], &no_checks),
+                "{}",
+                fen
+            );
+        }
+    }
+}
+
+#[test]
+fn test_atomic() {
+    // capturing next to the enemy king wins, even when in check
+    let board = VariantBoard::from_fen(Variant::Atomic, "4k3/3p4/8/8/8/8/8/3QK2r w - - 0 1")
+        .expect("Valid position");
+    assert!(board.is_check());
+    assert!(!board.legal(ChessMove::new(Square::D1, Square::A4, None)));
+    let board = board.make_move_new(ChessMove::new(Square::D1, Square::D7, None));
+    assert_eq!(board.outcome(), Some(Outcome::Winner(Color::White)));
+    assert!(board.legal_moves().is_empty());
+
+    // a capture that blows up our own king is not allowed
+    let board = VariantBoard::from_fen(Variant::Atomic, "4k3/8/8/8/8/8/3p4/3QK3 w - - 0 1")
+        .expect("Valid position");
+    assert!(!board.legal(ChessMove::new(Square::D1, Square::D2, None)));
+
+    // kings next to each other cannot give check
+    let board = VariantBoard::from_fen(Variant::Atomic, "8/8/8/3k4/3K4/8/8/3r4 w - - 0 1")
+        .expect("Valid position");
+    assert!(!board.is_check());
+    assert!(!board.legal(ChessMove::new(Square::D4, Square::D3, None)));
+    assert!(board.legal(ChessMove::new(Square::D4, Square::E5, None)));
+
+    // the explosion takes the rook on h1 with it, and the castle rights too
+    let board = VariantBoard::from_fen(Variant::Atomic, "4k3/8/8/8/8/8/6p1/4K2R b K - 0 1")
+        .expect("Valid position");
+    let board = board.make_move_new(ChessMove::new(Square::G2, Square::G1, Some(Piece::Knight)));
+    let board = board.make_move_new(ChessMove::new(Square::H1, Square::G1, None));
+    assert_eq!(
+        *board.combined(),
+        BitBoard::from_square(Square::E1) | BitBoard::from_square(Square::E8)
+    );
+    assert_eq!(board.castle_rights(Color::White), CastleRights::NoRights);
+}
+
+#[test]
+fn test_antichess_and_horde() {
+    // captures are compulsory, and the king can be captured
+    let board = VariantBoard::from_fen(Variant::Antichess, "8/8/8/8/8/8/1k6/K7 w - - 0 1")
+        .expect("Valid position");
+    assert_eq!(
+        board.legal_moves(),
+        vec![ChessMove::new(Square::A1, Square::B2, None)]
+    );
+    let board = board.make_move_new(ChessMove::new(Square::A1, Square::B2, None));
+    assert_eq!(board.outcome(), Some(Outcome::Winner(Color::Black)));
+
+    // pawns can promote to a king
+    let board = VariantBoard::from_fen(Variant::Antichess, "8/P7/8/8/8/8/8/7k w - - 0 1")
+        .expect("Valid position");
+    assert!(board.legal(ChessMove::new(Square::A7, Square::A8, Some(Piece::King))));
+
+    // a stalemated player wins
+    let board = VariantBoard::from_fen(Variant::Antichess, "8/8/8/8/8/p7/P7/8 w - - 0 1")
+        .expect("Valid position");
+    assert_eq!(board.outcome(), Some(Outcome::Winner(Color::White)));
+
+    // white's first rank pawns can move two squares, but not capture en passant that way
+    let board = VariantBoard::from_fen(Variant::Horde, "4k3/8/8/8/8/8/8/P7 w - - 0 1")
+        .expect("Valid position");
+    assert!(board.legal(ChessMove::new(Square::A1, Square::A3, None)));
+    let board = board.make_move_new(ChessMove::new(Square::A1, Square::A3, None));
+    assert_eq!(board.en_passant(), None);
+
+    // black wins by taking every white piece
+    let board = VariantBoard::from_fen(Variant::Horde, "4k3/8/8/8/8/8/8/8 w - - 0 1")
+        .expect("Valid position");
+    assert_eq!(board.outcome(), Some(Outcome::Winner(Color::Black)));
+}
+
+#[test]
+fn test_variant_fen() {
+    for variant in ALL_VARIANTS.iter() {
+        let board = VariantBoard::new(*variant);
+        assert_eq!(board.to_string(), variant.start_fen());
+        assert_eq!(board.outcome(), None);
+    }
+
+    let fen = "r1bqkbnr/pppp1Qpp/2n5/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4";
+    assert!(VariantBoard::from_fen(Variant::Standard, fen).is_ok());
+    let board = VariantBoard::from_fen(Variant::ThreeCheck, fen).expect("Valid position");
+    assert_eq!(board.remaining_checks(Color::White), 3);
+    assert_eq!(board.outcome(), Some(Outcome::Winner(Color::White)));
+
+    for (variant, fen) in [
+        (
+            Variant::Antichess,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ),
+        (
+            Variant::Standard,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1",
+        ),
+        (
+            Variant::Standard,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w - - 0 1",
+        ),
+        (
+            Variant::Atomic,
+            "rnbqkbnr/pppppppp/8/8/8/8/8/PNBQKBNR w - - 0 1",
+        ),
+        (Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K3 w - - 4+3 0 1"),
+        (Variant::ThreeCheck, "4k3/8/8/8/8/8/8/4K3 w - - 3+3+3 0 1"),
+        (Variant::Standard, "4k3/8/8/8/8/8/8/4K3 w - e6 0 1"),
+    ]
+    .iter()
+    {
+        assert!(VariantBoard::from_fen(*variant, fen).is_err(), "{}", fen);
+    }
+}
+
+#[test]
+fn test_variant_hash() {
+    // the hash made move by move matches the hash of the same position set up from its FEN,
+    // unless the game has ended with a king blown up, which cannot be set up
+    fn walk(board: &VariantBoard, depth: usize) {
+        if board.variant_end().is_some() {
+            return;
+        }
+        let fresh = VariantBoard::from_fen(board.variant(), &board.to_string()).expect("Valid");
+        assert_eq!(board.get_hash(), fresh.get_hash(), "{}", board);
+        if depth > 0 {
+            for m in board.legal_moves() {
+                walk(&board.make_move_new(m), depth - 1);
+            }
+        }
+    }
+    for variant in ALL_VARIANTS.iter() {
+        walk(&VariantBoard::new(*variant), 3);
+    }
+    walk(
+        &VariantBoard::from_fen(
+            Variant::Atomic,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .expect("Valid position"),
+        2,
+    );
+
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    ]
+    .iter()
+    {
+        let board = Board::from_str(fen).expect("Valid FEN");
+        let standard = VariantBoard::from_fen(Variant::Standard, fen).expect("Valid FEN");
+        assert_eq!(standard.get_hash(), board.get_hash());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::chess_move::ChessMove;
+use crate::color::Color;
+use crate::game::{Action, Game, GameResult};
+use crate::time_control::{Clock, TimeControl};
+use crate::variant::Variant;
+use crate::variant_board::VariantBoard;
+
+use std::time::Duration;
+
+/// A `Game` played by the rules of a chess variant, on a `VariantBoard`.
+///
+/// The moves are those of `VariantBoard`, and the game ends as the variant says, as well as by
+/// the repetition and move count rules of standard chess.  A game is never drawn for insufficient
+/// material.  Draw offers, resignations, take backs and clocks work as they do in a `Game`.
+///
+/// ```
+/// use chess::{ChessMove, GameResult, Square, Variant, VariantBoard, VariantGame};
+///
+/// let mut game = VariantGame::new(VariantBoard::new(Variant::KingOfTheHill));
+/// for (from, to) in [
+///     (Square::E2, Square::E3),
+///     (Square::E7, Square::E6),
+///     (Square::E1, Square::E2),
+///     (Square::E8, Square::E7),
+///     (Square::E2, Square::D3),
+///     (Square::E7, Square::D6),
+/// ]
+/// .iter()
+/// {
+///     assert!(game.make_move(ChessMove::new(*from, *to, None)));
+/// }
+///
+/// assert!(game.make_move(ChessMove::new(Square::D3, Square::D4, None)));
+/// assert_eq!(game.result(), Some(GameResult::WhiteVariantWin));
+/// assert_eq!(game.variant(), Variant::KingOfTheHill);
+/// ```
+#[derive(Clone, Debug)]
+pub struct VariantGame {
+    game: Game,
+}
+
+impl VariantGame {
+    /// Create a new `VariantGame`, starting from `board`.
+    pub fn new(board: VariantBoard) -> VariantGame {
+        VariantGame {
+            game: Game::new_variant(board),
+        }
+    }
+
+    /// The variant this game is played by.
+    pub fn variant(&self) -> Variant {
+        self.game.variant()
+    }
+
+    /// The game underneath, whose positions are all `VariantBoard`s.
+    pub(crate) fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Get all actions made in this game (moves, draw offers, resignations, etc.)
+    pub fn actions(&self) -> &Vec<Action> {
+        self.game.actions()
+    }
+
+    /// What is the status of this game?  See `Game::result()`.
+    pub fn result(&self) -> Option<GameResult> {
+        self.game.result()
+    }
+
+    /// Get the position this game started from.
+    ///
+    /// ```
+    /// use chess::{Variant, VariantBoard, VariantGame};
+    ///
+    /// let game = VariantGame::new(VariantBoard::new(Variant::Horde));
+    /// assert_eq!(game.start_position(), VariantBoard::new(Variant::Horde));
+    /// ```
+    pub fn start_position(&self) -> VariantBoard {
+        self.game
+            .start_variant_position()
+            .expect("A variant game has variant positions")
+    }
+
+    /// Get the current position.
+    ///
+    /// ```
+    /// use chess::{ChessMove, Piece, Square, Variant, VariantBoard, VariantGame};
+    ///
+    /// let mut game = VariantGame::new(VariantBoard::new(Variant::Horde));
+    /// game.make_move(ChessMove::new(Square::F5, Square::F6, None));
+    /// assert_eq!(game.current_position().piece_on(Square::F6), Some(Piece::Pawn));
+    /// ```
+    pub fn current_position(&self) -> VariantBoard {
+        self.game
+            .current_variant_position()
+            .expect("A variant game has variant positions")
+    }
+
+    /// How many times has the current position appeared in this game, counting this time?  In
+    /// Three-check, the checks each side has left are part of the position.
+    pub fn repetitions(&self) -> usize {
+        self.game.repetitions()
+    }
+
+    /// Determine if a player can legally declare a draw by 3-fold repetition or 50-move rule.
+    pub fn can_declare_draw(&self) -> bool {
+        self.game.can_declare_draw()
+    }
+
+    /// Declare a draw by 3-fold repetition or 50-move rule.
+    pub fn declare_draw(&mut self) -> bool {
+        self.game.declare_draw()
+    }
+
+    /// Claim a draw that `chess_move` would bring about.  See `Game::claim_draw_with_move()`.
+    pub fn claim_draw_with_move(&mut self, chess_move: ChessMove) -> bool {
+        self.game.claim_draw_with_move(chess_move)
+    }
+
+    /// Make a move, if it is legal in the variant.
+    pub fn make_move(&mut self, chess_move: ChessMove) -> bool {
+        self.game.make_move(chess_move)
+    }
+
+    /// Who's turn is it to move?
+    pub fn side_to_move(&self) -> Color {
+        self.game.side_to_move()
+    }
+
+    /// Offer a draw to my opponent.  See `Game::offer_draw()`.
+    pub fn offer_draw(&mut self, color: Color) -> bool {
+        self.game.offer_draw(color)
+    }
+
+    /// Accept a draw offer from my opponent.
+    pub fn accept_draw(&mut self) -> bool {
+        self.game.accept_draw()
+    }
+
+    /// Decline a draw offer from my opponent.
+    pub fn decline_draw(&mut self) -> bool {
+        self.game.decline_draw()
+    }
+
+    /// Who has a draw offer open?
+    pub fn draw_offer(&self) -> Option<Color> {
+        self.game.draw_offer()
+    }
+
+    /// `color` resigns the game
+    pub fn resign(&mut self, color: Color) -> bool {
+        self.game.resign(color)
+    }
+
+    /// `color` ran out of time, and loses.  See `Game::timeout()`.
+    pub fn timeout(&mut self, color: Color) -> bool {
+        self.game.timeout(color)
+    }
+
+    /// Run the game with `control`, using the time from `clock`.  See
+    /// `Game::set_time_control()`.
+    pub fn set_time_control<C: Clock + 'static>(&mut self, control: TimeControl, clock: C) {
+        self.game.set_time_control(control, clock)
+    }
+
+    /// The time control of this game, if it is timed.
+    pub fn time_control(&self) -> Option<&TimeControl> {
+        self.game.time_control()
+    }
+
+    /// How long each action took, in the same order as `actions()`.
+    pub fn elapsed_times(&self) -> &[Option<Duration>] {
+        self.game.elapsed_times()
+    }
+
+    /// The time `color` has left, or `None` if the game is not timed.
+    pub fn remaining_time(&self, color: Color) -> Option<Duration> {
+        self.game.remaining_time(color)
+    }
+
+    /// If the side to move has run out of time, record a timeout and return true.
+    pub fn check_timeout(&mut self) -> bool {
+        self.game.check_timeout()
+    }
+
+    /// How many moves have been made in this game.
+    pub fn ply(&self) -> usize {
+        self.game.ply()
+    }
+
+    /// Go back to the position after `ply` moves.  See `Game::truncate()`.
+    pub fn truncate(&mut self, ply: usize) -> bool {
+        self.game.truncate(ply)
+    }
+
+    /// Take back the last move, and anything that happened after it.
+    pub fn undo(&mut self) -> Option<ChessMove> {
+        self.game.undo()
+    }
+
+    /// `color` asks to take back their last move.  See `Game::takeback_request()`.
+    pub fn takeback_request(&mut self, color: Color) -> bool {
+        self.game.takeback_request(color)
+    }
+
+    /// Accept a take back request from my opponent.  See `Game::accept_takeback()`.
+    pub fn accept_takeback(&mut self) -> bool {
+        self.game.accept_takeback()
+    }
+}
+
+#[cfg(test)]
+use std::str::FromStr;
+
+#[cfg(test)]
+fn play_variant(variant: Variant, fen: &str, moves: &str) -> VariantGame {
+    let board = VariantBoard::from_fen(variant, fen).expect("Valid position");
+    moves
+        .split_whitespace()
+        .fold(VariantGame::new(board), |mut g, m| {
+            assert!(
+                g.make_move(ChessMove::from_str(m).expect("Valid UCI move")),
+                "{}",
+                m
+            );
+            g
+        })
+}
+
+#[test]
+fn test_variant_game_results() {
+    // exploding the king
+    let game = play_variant(Variant::Atomic, "4k3/3p4/8/8/8/8/8/3QK2r w - - 0 1", "d1d7");
+    assert_eq!(game.result(), Some(GameResult::WhiteVariantWin));
+    assert_eq!(game.current_position().combined().popcnt(), 2);
+
+    // taking every white pawn in Horde
+    let game = play_variant(Variant::Horde, "4k3/8/8/8/8/8/8/P6r b - - 0 1", "h1a1");
+    assert_eq!(game.result(), Some(GameResult::BlackVariantWin));
+
+    // losing every piece in Antichess
+    let mut game = play_variant(Variant::Antichess, "8/8/8/8/8/8/1k6/K7 w - - 0 1", "a1b2");
+    assert_eq!(game.result(), Some(GameResult::BlackVariantWin));
+    assert!(!game.make_move(ChessMove::from_str("b2b3").unwrap()));
+
+    // a checkmate is still a checkmate, and a stalemate a draw
+    let game = play_variant(
+        Variant::ThreeCheck,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "f2f3 e7e5 g2g4 d8h4",
+    );
+    assert_eq!(game.result(), Some(GameResult::BlackCheckmates));
+    assert!(game.current_position().is_check());
+    assert!(game.current_position().legal_moves().is_empty());
+    let game = play_variant(
+        Variant::KingOfTheHill,
+        "k7/8/8/8/8/8/8/1Q4K1 w - - 0 1",
+        "b1b6",
+    );
+    assert_eq!(game.result(), Some(GameResult::Stalemate));
+
+    // no draw for insufficient material, but running out of time still loses
+    let mut game = play_variant(Variant::Atomic, "4k3/8/8/8/8/8/8/4K3 w - - 0 1", "");
+    assert_eq!(game.result(), None);
+    assert!(game.timeout(Color::White));
+    assert_eq!(game.result(), Some(GameResult::WhiteLosesOnTime));
+}
+
+#[test]
+fn test_variant_game_repetitions() {
+    // the checks given are part of the position in Three-check
+    let moves = "a1a8 e8e7 a8a1 e7e8";
+    let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+    assert_eq!(play_variant(Variant::Standard, fen, moves).repetitions(), 2);
+    assert_eq!(
+        play_variant(Variant::ThreeCheck, fen, moves).repetitions(),
+        1
+    );
+    assert_eq!(play_variant(Variant::Atomic, fen, moves).repetitions(), 2);
+
+    let mut game = play_variant(Variant::ThreeCheck, fen, moves);
+    assert_eq!(game.undo(), Some(ChessMove::from_str("e7e8").unwrap()));
+    assert_eq!(game.side_to_move(), Color::Black);
+    assert_eq!(game.variant(), Variant::ThreeCheck);
+    assert_eq!(game.current_position().remaining_checks(Color::White), 2);
+
+    // an en passant capture that is not legal does not make the position a different one
+    let fen = "1n2k3/2p5/8/KP5r/8/8/8/1N6 b - - 0 1";
+    let moves = "c7c5 b1c3 b8a6 c3b1 a6b8 b1c3 b8a6 c3b1 a6b8";
+    assert_eq!(
+        play_variant(Variant::ThreeCheck, fen, moves).repetitions(),
+        3
+    );
+    let fen = "4k1n1/3p4/8/4P3/8/8/8/4K1N1 b - - 0 1";
+    let moves = "d7d5 g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8";
+    assert_eq!(
+        play_variant(Variant::ThreeCheck, fen, moves).repetitions(),
+        2
+    );
+}
@@ -41,8 +41,25 @@ impl Zobrist {
         }
     }
 
+    /// Get the value for `color` having `checks` more checks to give in Three-check.  Having all
+    /// three left is the value 0, so that a position hashes the same as in standard chess.
+    #[inline]
+    pub fn remaining_checks(checks: u8, color: Color) -> u64 {
+        if checks >= 3 {
+            0
+        } else {
+            ZOBRIST_CHECKS[color.to_index()][checks as usize]
+        }
+    }
+
     #[inline]
     pub fn color() -> u64 {
         SIDE_TO_MOVE
     }
+
+    /// Get the value for a position of Chess960.
+    #[inline]
+    pub fn chess960() -> u64 {
+        CHESS960
+    }
 }